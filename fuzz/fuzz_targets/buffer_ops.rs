@@ -0,0 +1,175 @@
+#![no_main]
+
+use std::ops::Bound;
+
+use arbitrary::Arbitrary;
+use fim::editor::{
+    buffer::{Buffer, Cursor, Line},
+    Mode,
+};
+use fim_fuzz::{check_buffer, too_large};
+use libfuzzer_sys::fuzz_target;
+
+/*
+* edits the way the editor makes them. line and char numbers wrap around to land in the buffer
+* since the buffer methods expect valid ones, except for the ranges of Line::delete_range and
+* Buffer::delete_lines which have to cope with anything
+*/
+#[derive(Arbitrary, Debug)]
+enum Op {
+    LineInsert { line: u16, pos: u16, text: String },
+    LineDeleteChar { line: u16, pos: u16 },
+    LineDeleteRange { line: u16, start: Bound<u8>, end: Bound<u8> },
+    InsertText { text: String, move_cursor: bool },
+    ReplaceRange { start: (u16, u16), end: (u16, u16), text: String },
+    SetLine { line: u16, text: String },
+    InsertLine { line: u16, text: String },
+    DeleteLine { line: u16 },
+    DeleteCharCursor,
+    InsertLinesAbove { amount: u8, move_cursor: bool },
+    InsertLines { amount: u8, move_cursor: bool },
+    DeleteLineCurr,
+    DeleteLines { first: u16, end: u16 },
+    JoinLineBelow,
+    SplitLineBelow,
+    InsertWithPairs(char),
+    DeletePair,
+    NewlineIndented,
+    OpenLineBelow,
+    OpenLineAbove,
+    DedentForCloser(char),
+    ShiftLine { line: u16, amount: i8 },
+    InsertTab,
+    ToggleComment { first: u16, last: u16 },
+    CreateFold { first: u16, last: u16 },
+    ToggleFold,
+    SetAllFolds(bool),
+    // visual mode from anchor to the cursor, then d
+    DeleteSelection { anchor: (u16, u16), linewise: bool },
+    JumpToMatchingBracket,
+    MoveBy { chars: i16, lines: i16, sync: bool },
+    MoveTo { line: u16, char: u16 },
+    MoveToLastChar,
+    MoveToFirstNonBlank,
+    MoveToLastLine,
+    MoveDisplayBy(i8),
+    SetMode(bool),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    text: String,
+    ops: Vec<Op>,
+}
+
+// a line number from 1 to the line count
+fn line_pos(buf: &Buffer, line: u16) -> usize {
+    line as usize % buf.line_count() + 1
+}
+
+// a position on a line from its start to just past its end
+fn cursor_at(buf: &Buffer, (line, char): (u16, u16)) -> Cursor {
+    let line_pos = line_pos(buf, line);
+    let char_pos = char as usize % (buf.get_line(line_pos - 1).len() + 1);
+    Cursor { line_pos, char_pos }
+}
+
+fn apply(buf: &mut Buffer, op: Op) {
+    match op {
+        Op::LineInsert { line, pos, text } => {
+            let line = buf.get_line_mut(line_pos(buf, line) - 1);
+            let pos = pos as usize % (line.len() + 1);
+            line.insert(pos, &text.replace('\n', ""));
+            // a Line does not know the cursor, the editor moves it back onto the text
+            buf.move_cursor_by(0, 0, false);
+        }
+        Op::LineDeleteChar { line, pos } => {
+            let idx = line_pos(buf, line) - 1;
+            buf.get_line_mut(idx).delete_char(pos as usize);
+            buf.move_cursor_by(0, 0, false);
+        }
+        Op::LineDeleteRange { line, start, end } => {
+            let idx = line_pos(buf, line) - 1;
+            let range = (start.map(usize::from), end.map(usize::from));
+            buf.get_line_mut(idx).delete_range(range);
+            buf.move_cursor_by(0, 0, false);
+        }
+        Op::InsertText { text, move_cursor } => buf.insert_text(&text, move_cursor),
+        Op::ReplaceRange { start, end, text } => {
+            let (start, end) = (cursor_at(buf, start), cursor_at(buf, end));
+            let (start, end) = match (start.line_pos, start.char_pos) <= (end.line_pos, end.char_pos) {
+                true => (start, end),
+                false => (end, start),
+            };
+            buf.replace_range(start, end, &text);
+        }
+        Op::SetLine { line, text } => buf.set_line(line_pos(buf, line), &text),
+        Op::InsertLine { line, text } => {
+            let line_pos = line as usize % (buf.line_count() + 1) + 1;
+            buf.insert_line(line_pos, &text);
+        }
+        Op::DeleteLine { line } => buf.delete_line(line_pos(buf, line)),
+        Op::DeleteCharCursor => buf.delete_char_cursor(),
+        Op::InsertLinesAbove { amount, move_cursor } => buf.insert_lines_above(amount as usize, move_cursor),
+        Op::InsertLines { amount, move_cursor } => buf.insert_lines(amount as usize, move_cursor),
+        Op::DeleteLineCurr => buf.delete_line_curr(),
+        Op::DeleteLines { first, end } => buf.delete_lines(first as usize..end as usize),
+        Op::JoinLineBelow => buf.join_line_below(),
+        Op::SplitLineBelow => buf.split_line_below(),
+        Op::InsertWithPairs(c) => {
+            let text = c.to_string().replace('\n', "");
+            if !buf.insert_with_pairs(&text) {
+                buf.insert_text(&text, true);
+            }
+        }
+        Op::DeletePair => {
+            buf.delete_pair();
+        }
+        Op::NewlineIndented => buf.newline_indented(),
+        Op::OpenLineBelow => buf.open_line_below(),
+        Op::OpenLineAbove => buf.open_line_above(),
+        Op::DedentForCloser(c) => buf.dedent_for_closer(&c.to_string()),
+        Op::ShiftLine { line, amount } => buf.shift_line(line_pos(buf, line) - 1, amount as isize),
+        Op::InsertTab => buf.insert_tab(),
+        Op::ToggleComment { first, last } => buf.toggle_comment(first as usize, last as usize),
+        Op::CreateFold { first, last } => {
+            let (first, last) = (line_pos(buf, first), line_pos(buf, last));
+            buf.create_fold(first.min(last), first.max(last));
+        }
+        Op::ToggleFold => buf.toggle_fold(),
+        Op::SetAllFolds(closed) => buf.set_all_folds(closed),
+        Op::DeleteSelection { anchor, linewise } => {
+            let cursor = *buf.cursor();
+            buf.move_cursor_to(cursor_at(buf, anchor));
+            buf.start_selection();
+            buf.move_cursor_to(cursor);
+            buf.delete_selection(linewise);
+        }
+        Op::JumpToMatchingBracket => buf.jump_to_matching_bracket(),
+        Op::MoveBy { chars, lines, sync } => buf.move_cursor_by(chars as isize, lines as isize, sync),
+        Op::MoveTo { line, char } => buf.move_cursor_to(Cursor {
+            line_pos: line as usize,
+            char_pos: char as usize,
+        }),
+        Op::MoveToLastChar => buf.move_cursor_to_last_char(),
+        Op::MoveToFirstNonBlank => buf.move_cursor_to_first_non_blank(),
+        Op::MoveToLastLine => buf.move_cursor_to_last_line(),
+        Op::MoveDisplayBy(rows) => buf.move_cursor_display_by(None, rows as isize),
+        Op::SetMode(insert) => {
+            buf.editor_mode = if insert { Mode::Insert } else { Mode::Normal };
+            buf.move_cursor_by(0, 0, false);
+        }
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let mut buf = Buffer::new();
+    buf.lines = input.text.split('\n').map(|line| Line::from(line.to_owned())).collect();
+    for op in input.ops {
+        if too_large(&buf) {
+            break;
+        }
+        apply(&mut buf, op);
+        check_buffer(&buf);
+    }
+});
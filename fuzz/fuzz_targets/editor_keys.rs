@@ -0,0 +1,100 @@
+#![no_main]
+
+use arbitrary::Arbitrary;
+use fim::editor::{
+    buffer::Line,
+    input::{InputEvent, Key, KeyEvent, Modifiers},
+    Editor,
+};
+use fim_fuzz::{check_buffer, too_large};
+use libfuzzer_sys::fuzz_target;
+
+/*
+* chars the normal, visual and insert mode commands use along with some that are more than a byte.
+* : is left out, command lines can write files and run lua
+*/
+const CHARS: &[char] = &[
+    'h', 'j', 'k', 'l', '0', '$', '%', 'G', 'g', 'x', 'd', 'y', 'i', 'a', 'o', 'O', 'v', 'V', 'z',
+    'f', 'c', 's', 'w', '1', '2', '9', '<', '>', ' ', '(', ')', '[', ']', '{', '}', '"', '\'', 'e',
+    'é', '\u{301}', '日', '😀', 'א',
+];
+
+const KEYS: &[Key] = &[
+    Key::Escape,
+    Key::Return,
+    Key::Backspace,
+    Key::Tab,
+    Key::Delete,
+    Key::Up,
+    Key::Down,
+    Key::Left,
+    Key::Right,
+    Key::Home,
+    Key::End,
+];
+
+#[derive(Arbitrary, Debug)]
+enum Press {
+    Char(u8),
+    Key(u8),
+    Ctrl(u8),
+    // text without a key, like an input method commits
+    Text(String),
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    text: String,
+    columns: u8,
+    presses: Vec<Press>,
+}
+
+// a char key is followed by its text like in the sdl frontend
+fn press(editor: &mut Editor, press: Press) {
+    let pick = |idx: u8| CHARS[idx as usize % CHARS.len()];
+    match press {
+        Press::Char(idx) => {
+            let c = pick(idx);
+            let mods = Modifiers {
+                shift: c.is_uppercase(),
+                ..Modifiers::default()
+            };
+            editor.handle_input(InputEvent::Key(KeyEvent { key: Key::Char(c), mods }));
+            editor.handle_input(InputEvent::Text(c.to_string()));
+        }
+        Press::Key(idx) => {
+            let key = KEYS[idx as usize % KEYS.len()];
+            editor.handle_input(InputEvent::Key(KeyEvent::new(key)));
+        }
+        Press::Ctrl(idx) => editor.handle_input(InputEvent::Key(KeyEvent::ctrl(pick(idx)))),
+        Press::Text(text) => editor.handle_input(InputEvent::Text(text)),
+    }
+}
+
+// what main asks for every frame
+fn draw(editor: &mut Editor, columns: usize) {
+    editor.set_window_columns(columns);
+    editor.get_text_window();
+    let (line, _) = editor.get_cursor();
+    editor.get_screen_row(line);
+    for (first, _) in editor.get_closed_folds() {
+        editor.get_screen_row(first);
+        editor.get_line(first);
+    }
+    editor.get_selection();
+    editor.get_matching_bracket();
+    editor.get_command_line();
+}
+
+fuzz_target!(|input: Input| {
+    let mut editor = Editor::new("");
+    editor.buffers.curr_buffer_mut().lines = input.text.split('\n').map(|line| Line::from(line.to_owned())).collect();
+    for p in input.presses {
+        if too_large(editor.buffers.curr_buffer()) {
+            break;
+        }
+        press(&mut editor, p);
+        check_buffer(editor.buffers.curr_buffer());
+        draw(&mut editor, input.columns as usize);
+    }
+});
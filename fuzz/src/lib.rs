@@ -0,0 +1,46 @@
+use fim::editor::{buffer::Buffer, Mode};
+
+/*
+* what must hold after any edit or key, whatever came before it. a broken invariant is reported
+* with the text so the artifact does not have to be replayed to see what went wrong
+*/
+pub fn check_buffer(buf: &Buffer) {
+    assert!(!buf.lines.is_empty(), "a buffer always has a line");
+
+    let cursor = buf.cursor();
+    assert!(
+        (1..=buf.line_count()).contains(&cursor.line_pos),
+        "cursor on line {} of {}",
+        cursor.line_pos,
+        buf.line_count()
+    );
+
+    // insert mode may sit after the last char, the other modes sit on one
+    let line = buf.curr_line();
+    let last = match buf.editor_mode {
+        Mode::Insert => line.len(),
+        _ => line.len().saturating_sub(1),
+    };
+    assert!(
+        cursor.char_pos <= last,
+        "cursor at char {} of {:?} in {} mode",
+        cursor.char_pos,
+        line.content,
+        buf.editor_mode.name()
+    );
+    assert!(line.content.is_char_boundary(line.offset_of(cursor.char_pos)));
+    assert_eq!(
+        line.cluster_start(cursor.char_pos),
+        cursor.char_pos,
+        "cursor inside a grapheme cluster of {:?}",
+        line.content
+    );
+}
+
+// edits that copy text, like opening a line with the indent of a long one and joining it back,
+// can double the buffer each time. past this there is nothing new to find, only memory to run out of
+const MAX_TEXT: usize = 64 * 1024;
+
+pub fn too_large(buf: &Buffer) -> bool {
+    buf.lines.iter().map(|line| line.content.len() + 1).sum::<usize>() > MAX_TEXT
+}
@@ -1,12 +1,19 @@
 use core::fmt;
+use std::collections::HashMap;
 use std::fs;
-use std::sync::OnceLock;
+use std::path::Path;
+use std::sync::{LazyLock, OnceLock};
 
 pub static CONFIG: OnceLock<Config> = OnceLock::new();
 
-static DEFAULT_CONF: Config = Config {
+static DEFAULT_CONF: LazyLock<Config> = LazyLock::new(|| Config {
     general: GeneralConfig {
         font: String::new(),
+        proportional: false,
+        large_file_mb: 64,
+        poll_files: false,
+        swap_interval_ms: 4000,
+        plugins: Vec::new(),
     },
     camera: CameraConfig {
         follow_strength: 0.3,
@@ -16,11 +23,64 @@ static DEFAULT_CONF: Config = Config {
         trail_length: 0.9,
         lerp_factor: 0.1,
     },
-};
+    indent: IndentConfig {
+        tabstop: 4,
+        shiftwidth: 4,
+        expandtab: true,
+    },
+    brackets: BracketsConfig {
+        match_pairs: String::from("(:),[:],{:}"),
+        highlight_match: true,
+        auto_pairs: String::from("(:),[:],{:},\":\",':',`:`"),
+        match_pairs_ft: HashMap::from([
+            (String::from("html"), String::from("(:),[:],{:},<:>")),
+            (String::from("xml"), String::from("(:),[:],{:},<:>")),
+        ]),
+        // quotes double as lifetimes and quoting in these languages
+        auto_pairs_ft: HashMap::from([
+            (String::from("rs"), String::from("(:),[:],{:},\":\"")),
+            (String::from("lisp"), String::from("(:),[:],{:},\":\"")),
+            (String::from("el"), String::from("(:),[:],{:},\":\"")),
+            (String::from("clj"), String::from("(:),[:],{:},\":\"")),
+            (String::from("scm"), String::from("(:),[:],{:},\":\"")),
+        ]),
+    },
+    folding: FoldingConfig {
+        method: String::from("manual"),
+        method_ft: HashMap::new(),
+        sheet_effect: true,
+    },
+    wrap: WrapConfig {
+        enabled: true,
+        width: 0,
+        linebreak: true,
+        breakindent: true,
+        showbreak: String::new(),
+        sidescroll: 0,
+        sidescrolloff: 5,
+        precedes: String::from("<"),
+        extends: String::from(">"),
+    },
+    keymap: KeymapConfig {
+        leader: String::from("\\"),
+        timeout_ms: 1000,
+        maps: Vec::new(),
+    },
+});
 
 #[derive(Debug, Clone)]
 pub struct GeneralConfig {
     pub font: String,
+    // lay text out with the advance of each glyph instead of a grid of cells
+    pub proportional: bool,
+    // files at least this big open in large file mode
+    pub large_file_mb: u64,
+    // look for changes to open files by polling instead of filesystem notifications
+    pub poll_files: bool,
+    // how often swap files of modified buffers are written, 0 disables them
+    pub swap_interval_ms: u64,
+    // compiled in plugins to turn on, by name
+    pub plugins: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -35,11 +95,80 @@ pub struct CursorConfig {
     pub lerp_factor: f32,
 }
 
+#[derive(Debug, Clone)]
+pub struct IndentConfig {
+    pub tabstop: usize,
+    pub shiftwidth: usize,
+    pub expandtab: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct BracketsConfig {
+    pub match_pairs: String,
+    pub highlight_match: bool,
+    pub auto_pairs: String,
+    // per file extension overrides, set as `match_pairs.<ext>` and `auto_pairs.<ext>`
+    pub match_pairs_ft: HashMap<String, String>,
+    pub auto_pairs_ft: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FoldingConfig {
+    // manual, indent, marker or syntax
+    pub method: String,
+    // per file extension overrides, set as `method.<ext>`
+    pub method_ft: HashMap<String, String>,
+    pub sheet_effect: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct WrapConfig {
+    pub enabled: bool,
+    // wrap column, 0 wraps at the window width
+    pub width: usize,
+    pub linebreak: bool,
+    pub breakindent: bool,
+    pub showbreak: String,
+    // horizontal scrolling when lines are not wrapped
+    pub sidescroll: usize,
+    pub sidescrolloff: usize,
+    pub precedes: String,
+    pub extends: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeymapConfig {
+    pub leader: String,
+    // how long to wait for the rest of a mapping
+    pub timeout_ms: u64,
+    // map command, keys and what they map to, in file order
+    pub maps: Vec<(String, String, String)>,
+}
+
 #[derive(Debug, Clone)] 
 pub struct Config { 
     pub general: GeneralConfig,
     pub camera: CameraConfig,
     pub cursor: CursorConfig,
+    pub indent: IndentConfig,
+    pub brackets: BracketsConfig,
+    pub folding: FoldingConfig,
+    pub wrap: WrapConfig,
+    pub keymap: KeymapConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        DEFAULT_CONF.clone()
+    }
+}
+
+// read by main at startup, init.lua is looked for next to it
+pub const CONFIG_PATH: &str = "example.conf";
+
+// where the config file lives
+pub fn config_dir() -> &'static Path {
+    Path::new(CONFIG_PATH).parent().unwrap_or(Path::new(""))
 }
 
 impl Config {
@@ -62,6 +191,11 @@ enum Section {
     General,
     Camera,
     Cursor,
+    Indent,
+    Brackets,
+    Folding,
+    Wrap,
+    Keymap,
     None,
 }
 
@@ -71,6 +205,11 @@ impl fmt::Display for Section {
             Section::General => write!(f, "[General]"),
             Section::Camera => write!(f, "[Camera]"),
             Section::Cursor => write!(f, "[Cursor]"),
+            Section::Indent => write!(f, "[Indent]"),
+            Section::Brackets => write!(f, "[Brackets]"),
+            Section::Folding => write!(f, "[Folding]"),
+            Section::Wrap => write!(f, "[Wrap]"),
+            Section::Keymap => write!(f, "[Keymap]"),
             Section::None => write!(f, "None"),
         }
     }
@@ -109,6 +248,21 @@ fn parse(contents: &str) -> Config {
                 "[Cursor]" => {
                     section = Section::Cursor;
                 }
+                "[Indent]" => {
+                    section = Section::Indent;
+                }
+                "[Brackets]" => {
+                    section = Section::Brackets;
+                }
+                "[Folding]" => {
+                    section = Section::Folding;
+                }
+                "[Wrap]" => {
+                    section = Section::Wrap;
+                }
+                "[Keymap]" => {
+                    section = Section::Keymap;
+                }
                 _ => {
                     println!("Class {} on line {} is invalid. Ignoring.", line, num + 1);
                 }
@@ -117,7 +271,8 @@ fn parse(contents: &str) -> Config {
         }
 
         // try to parse key-value pair
-        let tokens: Vec<&str> = line.split("=").map(|token| token.trim()).collect();
+        // only the first = separates, mappings may contain more
+        let tokens: Vec<&str> = line.splitn(2, "=").map(|token| token.trim()).collect();
 
         // check validity
         if tokens.len() != 2 {
@@ -137,6 +292,45 @@ fn parse(contents: &str) -> Config {
                             println!("Could not parse value: '{}' for font (line {})", tokens[1], num + 1);
                         }
                     }
+                    "proportional" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.general.proportional = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for proportional (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "large_file_mb" => {
+                        let maybe = tokens[1].parse::<u64>();
+                        if maybe.is_ok() {
+                            conf.general.large_file_mb = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for large_file_mb (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "poll_files" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.general.poll_files = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for poll_files (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "swap_interval_ms" => {
+                        let maybe = tokens[1].parse::<u64>();
+                        if maybe.is_ok() {
+                            conf.general.swap_interval_ms = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for swap_interval_ms (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "plugins" => {
+                        conf.general.plugins = tokens[1]
+                            .split(',')
+                            .map(|name| name.trim().to_string())
+                            .filter(|name| !name.is_empty())
+                            .collect();
+                    }
                     _ => {
                         println!("Variable {}.{} on line {} not a valid config option. Skipping!", section, tokens[0], num + 1);
                     }
@@ -189,6 +383,184 @@ fn parse(contents: &str) -> Config {
                 }
 
             }
+            Section::Indent => {
+                match tokens[0] {
+                    "tabstop" => {
+                        let maybe = tokens[1].parse::<usize>();
+                        if maybe.as_ref().is_ok_and(|n| *n > 0) {
+                            conf.indent.tabstop = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for tabstop (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "shiftwidth" => {
+                        let maybe = tokens[1].parse::<usize>();
+                        if maybe.as_ref().is_ok_and(|n| *n > 0) {
+                            conf.indent.shiftwidth = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for shiftwidth (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "expandtab" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.indent.expandtab = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for expandtab (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    _ => {
+                        println!("Variable {}.{} on line {} not a valid config option. Skipping!", section, tokens[0], num + 1);
+                    }
+                }
+            }
+            Section::Brackets => {
+                match tokens[0] {
+                    "match_pairs" => {
+                        conf.brackets.match_pairs = tokens[1].to_owned();
+                    }
+                    "auto_pairs" => {
+                        conf.brackets.auto_pairs = tokens[1].to_owned();
+                    }
+                    key if key.starts_with("match_pairs.") => {
+                        let ext = key.trim_start_matches("match_pairs.").to_owned();
+                        conf.brackets.match_pairs_ft.insert(ext, tokens[1].to_owned());
+                    }
+                    key if key.starts_with("auto_pairs.") => {
+                        let ext = key.trim_start_matches("auto_pairs.").to_owned();
+                        conf.brackets.auto_pairs_ft.insert(ext, tokens[1].to_owned());
+                    }
+                    "highlight_match" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.brackets.highlight_match = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for highlight_match (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    _ => {
+                        println!("Variable {}.{} on line {} not a valid config option. Skipping!", section, tokens[0], num + 1);
+                    }
+                }
+            }
+            Section::Folding => {
+                let valid_method = |method: &str| ["manual", "indent", "marker", "syntax"].contains(&method);
+                match tokens[0] {
+                    "method" => {
+                        if valid_method(tokens[1]) {
+                            conf.folding.method = tokens[1].to_owned();
+                        } else {
+                            println!("Could not parse value: '{}' for method (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    key if key.starts_with("method.") => {
+                        if valid_method(tokens[1]) {
+                            let ext = key.trim_start_matches("method.").to_owned();
+                            conf.folding.method_ft.insert(ext, tokens[1].to_owned());
+                        } else {
+                            println!("Could not parse value: '{}' for {} (line {})", tokens[1], key, num + 1);
+                        }
+                    }
+                    "sheet_effect" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.folding.sheet_effect = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for sheet_effect (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    _ => {
+                        println!("Variable {}.{} on line {} not a valid config option. Skipping!", section, tokens[0], num + 1);
+                    }
+                }
+            }
+            Section::Wrap => {
+                match tokens[0] {
+                    "enabled" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.wrap.enabled = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for enabled (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "width" => {
+                        let maybe = tokens[1].parse::<usize>();
+                        if maybe.is_ok() {
+                            conf.wrap.width = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for width (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "linebreak" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.wrap.linebreak = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for linebreak (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "breakindent" => {
+                        let maybe = tokens[1].parse::<bool>();
+                        if maybe.is_ok() {
+                            conf.wrap.breakindent = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for breakindent (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "showbreak" => {
+                        conf.wrap.showbreak = tokens[1].to_owned();
+                    }
+                    "sidescroll" => {
+                        let maybe = tokens[1].parse::<usize>();
+                        if maybe.is_ok() {
+                            conf.wrap.sidescroll = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for sidescroll (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "sidescrolloff" => {
+                        let maybe = tokens[1].parse::<usize>();
+                        if maybe.is_ok() {
+                            conf.wrap.sidescrolloff = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for sidescrolloff (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    "precedes" => {
+                        conf.wrap.precedes = tokens[1].to_owned();
+                    }
+                    "extends" => {
+                        conf.wrap.extends = tokens[1].to_owned();
+                    }
+                    _ => {
+                        println!("Variable {}.{} on line {} not a valid config option. Skipping!", section, tokens[0], num + 1);
+                    }
+                }
+            }
+            Section::Keymap => {
+                match tokens[0] {
+                    "leader" => {
+                        conf.keymap.leader = tokens[1].to_owned();
+                    }
+                    "timeout_ms" => {
+                        let maybe = tokens[1].parse::<u64>();
+                        if maybe.is_ok() {
+                            conf.keymap.timeout_ms = maybe.unwrap();
+                        } else {
+                            println!("Could not parse value: '{}' for timeout_ms (line {})", tokens[1], num + 1);
+                        }
+                    }
+                    // `nnoremap <leader>w = :w<CR>`, checked when the editor defines it
+                    key if key.contains(char::is_whitespace) => {
+                        let (command, lhs) = key.split_once(char::is_whitespace).unwrap();
+                        conf.keymap.maps.push((command.to_owned(), lhs.trim().to_owned(), tokens[1].to_owned()));
+                    }
+                    _ => {
+                        println!("Variable {}.{} on line {} not a valid config option. Skipping!", section, tokens[0], num + 1);
+                    }
+                }
+            }
             Section::None => {
                 println!("Section for key-value pair, {{{}:{}}} on line {} not specified. Skipping!", tokens[0], tokens[1], num + 1);
             }
@@ -0,0 +1,601 @@
+/*
+* key sequences and what vim (with autoindent, as in neovim) leaves behind. a test that fails
+* means fim and vim disagree, the ignored ones are vim commands fim does not have yet
+*/
+use std::{cell::RefCell, collections::HashMap, fs, path::Path, rc::Rc, sync::Arc, time::Duration};
+
+use notify::{
+    event::{EventKind, ModifyKind},
+    Event,
+};
+
+use crate::{configuration::Config, plugins};
+
+use super::{
+    comment::CommentTokens,
+    harness::{assert_cursor, assert_keys, Harness},
+    layout::{Advances, FontAdvances},
+    plugin::EditorPlugin,
+    session::Session,
+    swap,
+    testdir::TestDir,
+    watch::FileWatcher,
+    Editor, Mode,
+};
+
+// a made up proportional font, i is narrow and m is wide
+fn advances() -> Option<Advances> {
+    Some(Arc::new(FontAdvances {
+        chars: HashMap::from([('i', 0.5), ('m', 1.5)]),
+        kerning: HashMap::new(),
+    }))
+}
+
+#[test]
+fn motions() {
+    assert_cursor("hello\nworld", "jl", (2, 1));
+    assert_cursor("abc", "$", (1, 2));
+    assert_cursor("abc", "$0", (1, 0));
+    assert_cursor("a\nb\nc", "G", (3, 0));
+    assert_cursor("a\nb\nc", "Ggg", (1, 0));
+    assert_cursor("a\nb\nc", "2G", (2, 0));
+    assert_cursor("a\nb\nc\nd", "3j", (4, 0));
+    assert_cursor("a\nb\nc", "10j", (3, 0));
+    assert_cursor("abc", "5l", (1, 2));
+    assert_cursor("(a [b] c)", "%", (1, 8));
+}
+
+#[test]
+fn matching_bracket_highlight() {
+    let mut harness = Harness::new("(a)");
+    assert_eq!(harness.editor.get_matching_bracket(), Some((0, 2., 1.)));
+    // the bracket just typed before the insert cursor is matched without moving the cursor
+    harness.keys("$a");
+    assert_eq!(harness.editor.get_matching_bracket(), Some((0, 0., 1.)));
+    assert_eq!(harness.cursor(), (1, 3));
+}
+
+#[test]
+fn vertical_motions_keep_the_column() {
+    assert_cursor("abc\nx", "$j", (2, 0));
+    assert_cursor("abc\nx", "$jk", (1, 2));
+    assert_cursor("abcdef\nab\nabcdef", "$jj", (3, 5));
+    // the column is the one on screen, not the char count
+    assert_cursor("\tx\nabcdef", "lj", (2, 4));
+    assert_cursor("日本語\nabcdef", "llj", (2, 4));
+    assert_cursor("abcdef\n日本語", "lllj", (2, 1));
+}
+
+#[test]
+fn clicking() {
+    let mut harness = Harness::new("abc\ndef");
+    harness.editor.click(1, 1.5);
+    assert_eq!(harness.cursor(), (2, 1));
+    harness.editor.click(0, 10.);
+    assert_eq!(harness.cursor(), (1, 2));
+    harness.editor.click(5, 0.);
+    assert_eq!(harness.cursor(), (2, 0));
+    // a click anywhere on a tab lands on it
+    let mut harness = Harness::new("\tx");
+    harness.editor.click(0, 2.5);
+    assert_eq!(harness.cursor(), (1, 0));
+    harness.editor.click(0, 4.5);
+    assert_eq!(harness.cursor(), (1, 1));
+}
+
+#[test]
+fn deleting() {
+    assert_keys("abc", "x", "bc");
+    assert_keys("abcd", "2x", "cd");
+    assert_keys("abc", "$x", "ab");
+    assert_keys("abc", "l5x", "a");
+    assert_keys("abc", "$2x", "ab");
+    assert_keys("a\nb\nc", "dd", "b\nc");
+    assert_keys("a\nb\nc", "2dd", "c");
+    assert_keys("a\nb\nc", "jdd", "a\nc");
+    assert_keys("only", "dd", "");
+    assert_cursor("a\nb\nc", "Gdd", (2, 0));
+}
+
+#[test]
+fn grapheme_clusters() {
+    // thai and devanagari vowel signs and combining accents go with the char before them
+    assert_keys("กำลัง", "x", "ลัง");
+    assert_cursor("กำลัง", "l", (1, 2));
+    assert_cursor("กำลัง", "$", (1, 4));
+    assert_keys("हिंदी", "x", "दी");
+    assert_cursor("हिंदी", "$", (1, 3));
+    assert_cursor("हिंदी", "$h", (1, 0));
+    assert_keys("e\u{301}x", "x", "x");
+    assert_keys("ae\u{301}", "$x", "a");
+
+    // emoji joined with zwj, skin tones and flags made of two regional indicators
+    assert_keys("👨\u{200d}👩\u{200d}👧 ok", "x", " ok");
+    assert_cursor("👨\u{200d}👩\u{200d}👧 ok", "l", (1, 5));
+    assert_keys("🇯🇵🇫🇷", "lx", "🇯🇵");
+    assert_keys("🇯🇵🇫🇷", "vd", "🇫🇷");
+    assert_keys("ab👍🏽", "$a<BS><Esc>", "ab");
+    assert_keys("कि", "a<BS><Esc>", "");
+
+    let mut harness = Harness::new("e\u{301}e\u{301}x");
+    harness.keys("$");
+    assert_eq!(harness.cursor(), (1, 4));
+    assert_eq!(harness.editor.buffers.curr_buffer().cursor_display_col(), 2);
+}
+
+#[test]
+fn wide_chars() {
+    // east asian wide chars and emoji take two columns, like virtcol() in vim
+    let mut harness = Harness::new("日本語abc");
+    harness.keys("$");
+    assert_eq!(harness.editor.buffers.curr_buffer().cursor_display_col(), 8);
+    harness.keys("0l");
+    assert_eq!(harness.editor.buffers.curr_buffer().cursor_display_col(), 2);
+    assert_eq!(harness.editor.buffers.curr_buffer().cursor_width(), 2.);
+    assert_eq!(Harness::new("😀a").keys("l").editor.buffers.curr_buffer().cursor_display_col(), 2);
+
+    // a wide char that does not fit at the end of a row moves to the next one
+    let mut harness = Harness::new("日本語abc");
+    harness.editor.set_window_columns(5);
+    harness.keys("gj");
+    assert_eq!(harness.cursor(), (1, 2));
+}
+
+#[test]
+fn proportional_fonts() {
+    // vertical motions keep to the x on screen, not the char count
+    let mut harness = Harness::new("mmmm\niiiiiiiiii");
+    harness.editor.set_advances(advances());
+    harness.keys("llj");
+    assert_eq!(harness.cursor(), (2, 6));
+    harness.keys("k");
+    assert_eq!(harness.cursor(), (1, 2));
+}
+
+#[test]
+fn right_to_left() {
+    // the buffer stays in logical order, hebrew after latin is drawn reversed on its right
+    let mut harness = Harness::new("abc אבג");
+    harness.keys("4l");
+    assert_eq!(harness.cursor(), (1, 4));
+    assert_eq!(harness.editor.buffers.curr_buffer().cursor_x(), 6.);
+    harness.editor.click(0, 4.5);
+    assert_eq!(harness.cursor(), (1, 6));
+
+    // a line that starts right to left is laid out from the right
+    assert_cursor("אבגד\nabcdef", "lj", (2, 2));
+
+    // a selection across a change of direction is split where it shows
+    let mut harness = Harness::new("ab אבג");
+    harness.keys("lvll");
+    assert_eq!(harness.editor.get_selection(), vec![(0, 1., 3.), (0, 5., 6.)]);
+}
+
+#[test]
+fn shifting() {
+    assert_keys("foo", ">>", "    foo");
+    assert_keys("        foo", "<<", "    foo");
+    assert_keys("a\nb", "2>>", "    a\n    b");
+    assert_cursor("foo", ">>", (1, 4));
+    assert_keys("foo", "V99999999999>", "foo");
+}
+
+#[test]
+fn inserting() {
+    assert_keys("", "ihello<Esc>", "hello");
+    assert_keys("world", "ihello <Esc>", "hello world");
+    assert_keys("abc", "ax<Esc>", "axbc");
+    assert_keys("abcd", "lli<CR><Esc>", "ab\ncd");
+    assert_keys("abc", "$a<BS><Esc>", "ab");
+    assert_keys("ab\ncd", "ji<BS><Esc>", "abcd");
+    assert_cursor("", "iab<Esc>", (1, 1));
+}
+
+#[test]
+fn opening_lines() {
+    assert_keys("a", "ob<Esc>", "a\nb");
+    assert_keys("a", "Ob<Esc>", "b\na");
+    assert_keys("  foo", "obar<Esc>", "  foo\n  bar");
+}
+
+#[test]
+fn insert_mode_indenting() {
+    assert_keys("foo", "i<C-t><Esc>", "    foo");
+    assert_keys("    foo", "i<C-d><Esc>", "foo");
+}
+
+#[test]
+fn mode_switch_keys_type_nothing() {
+    assert_keys("", "ix<Esc>", "x");
+    assert_keys("", "ox<Esc>", "\nx");
+    let mut harness = Harness::new("");
+    harness.keys(":");
+    assert_eq!(harness.mode(), Mode::Command);
+    assert_eq!(harness.editor.get_command_line().as_deref(), Some(":"));
+}
+
+#[test]
+fn visual_mode() {
+    assert_keys("abcdef", "lvld", "adef");
+    assert_keys("a\nb\nc", "Vjd", "c");
+    assert_keys("a\nb\nc", "Vj>", "    a\n    b\nc");
+    let mut harness = Harness::new("a");
+    harness.keys("v");
+    assert_eq!(harness.mode(), Mode::Visual);
+    harness.keys("<Esc>V");
+    assert_eq!(harness.mode(), Mode::VisualLine);
+    harness.keys("V");
+    assert_eq!(harness.mode(), Mode::Normal);
+}
+
+#[test]
+fn commenting() {
+    assert_keys("a\nb", "gcc", "# a\nb");
+    assert_keys("# a\nb", "gcc", "a\nb");
+    assert_keys("a\nb\nc\nd", "3gcc", "# a\n# b\n# c\nd");
+    assert_keys("a\nb\nc", "gcj", "# a\n# b\nc");
+    assert_keys("a\nb\nc", "jVjgc", "a\n# b\n# c");
+    // a range with some lines commented is commented as a whole, uncommenting needs all of them
+    assert_keys("# a\nb", "gcj", "# # a\n# b");
+    assert_keys("# a\n# b", "gcj", "a\nb");
+    // markers line up at the smallest indentation, blank lines are left alone
+    assert_keys("    a\n\n  b", "gc2j", "  #   a\n\n  # b");
+
+    // a filetype with only block comments wraps each line
+    let mut harness = Harness::new("a {}\nb {}");
+    harness.editor.buffers.curr_buffer_mut().comment_tokens = CommentTokens::for_file("style.css", None);
+    harness.keys("gcj");
+    assert_eq!(harness.text(), "/* a {} */\n/* b {} */");
+    harness.keys("gcc");
+    assert_eq!(harness.text(), "a {}\n/* b {} */");
+}
+
+#[test]
+fn folds() {
+    assert_cursor("a\nb\nc\nd", "zfjj", (3, 0));
+    assert_cursor("a\nb\nc\nd", "zfjzojj", (3, 0));
+}
+
+#[test]
+fn screen_rows_follow_changes() {
+    let mut harness = Harness::new("abcdef\nx\ny");
+    harness.editor.set_window_columns(4);
+    assert_eq!(harness.editor.get_screen_row(2), Some(2));
+    // the rows are laid out again after an edit, a resize and a fold
+    harness.keys("xx");
+    assert_eq!(harness.editor.get_screen_row(2), Some(1));
+    harness.editor.set_window_columns(2);
+    assert_eq!(harness.editor.get_screen_row(2), Some(2));
+    harness.keys("zfj");
+    assert_eq!(harness.editor.get_screen_row(2), None);
+    assert_eq!(harness.editor.get_screen_row(3), Some(1));
+}
+
+#[test]
+fn surround() {
+    assert_keys("foo", "ysiw)", "(foo)");
+    assert_keys("foo", "ysiw(", "( foo )");
+    assert_keys("(foo)", "lds(", "foo");
+    assert_keys("( foo )", "llds(", "foo");
+    assert_keys("( foo )", "llds)", " foo ");
+    assert_keys("foo", "ysiw(ds(", "foo");
+    assert_keys("( )", "ds(", "");
+    assert_keys("( foo )", "llcs([", "[ foo ]");
+    assert_keys("\"foo\"", "lcs\"'", "'foo'");
+}
+
+#[test]
+fn command_line() {
+    let mut harness = Harness::new("a");
+    harness.keys(":bogus<CR>");
+    assert_eq!(harness.message(), Some("not an editor command: bogus"));
+    assert_eq!(harness.mode(), Mode::Normal);
+
+    let mut harness = Harness::new("a");
+    harness.keys(":abc<Esc>");
+    assert_eq!(harness.mode(), Mode::Normal);
+    assert_eq!(harness.text(), "a");
+
+    let mut harness = Harness::new("a");
+    harness.keys(":<BS>");
+    assert_eq!(harness.mode(), Mode::Normal);
+}
+
+#[test]
+fn mappings() {
+    assert_keys("a\nb", ":nnoremap Q dd<CR>Q", "b");
+
+    let mut harness = Harness::new("");
+    harness.keys(":inoremap jk <lt>Esc><CR>ihijk");
+    assert_eq!(harness.text(), "hi");
+    assert_eq!(harness.mode(), Mode::Normal);
+
+    // a j that may still become jk waits, then types itself
+    let mut harness = Harness::new("");
+    harness.keys(":inoremap jk <lt>Esc><CR>ij");
+    assert_eq!(harness.text(), "");
+    harness.timeout();
+    assert_eq!(harness.text(), "j");
+}
+
+#[test]
+fn config() {
+    // the harness only sees the config it is given, not example.conf or init.lua
+    let mut config = Config::default();
+    config.indent.shiftwidth = 2;
+    config.keymap.maps.push(("nnoremap".to_owned(), "Q".to_owned(), "dd".to_owned()));
+    let mut harness = Harness::with_config("a\nb", &config);
+    harness.keys(">>jQ");
+    assert_eq!(harness.text(), "  a");
+}
+
+#[test]
+fn quitting() {
+    let dir = TestDir::new("quitting");
+    let (first, second) = (dir.file("a", "a\n"), dir.file("b", "b\n"));
+    let read = |path: &Path| fs::read_to_string(path).unwrap();
+
+    // :q keeps changes from being lost, :q! drops them
+    let mut harness = Harness::open(&[&first, &second], &Config::default());
+    harness.keys("x:q<CR>");
+    assert!(!harness.editor.should_quit());
+    assert!(harness.message().is_some_and(|message| message.contains("no write since last change")));
+    harness.keys(":q!<CR>");
+    assert!(harness.editor.should_quit());
+    assert_eq!(read(&first), "a\n");
+
+    // :wqa writes every modified buffer first
+    let mut harness = Harness::open(&[&first, &second], &Config::default());
+    harness.keys("x");
+    harness.editor.buffers.get_mut(1).delete_line_curr();
+    harness.keys(":wqa<CR>");
+    assert!(harness.editor.should_quit());
+    assert_eq!((read(&first), read(&second)), (String::new(), String::new()));
+
+    // closing the window asks, c and escape cancel, d quits without writing and w writes all
+    let mut harness = Harness::open(&[&first], &Config::default());
+    harness.keys("ia<Esc>");
+    for answer in ["c", "<Esc>", "d"] {
+        harness.editor.request_quit();
+        assert!(harness.message().is_some_and(|message| message.contains("[w]rite all and quit")));
+        harness.keys(answer);
+        assert_eq!(harness.editor.should_quit(), answer == "d");
+    }
+    assert_eq!(read(&first), "");
+    let mut harness = Harness::open(&[&first], &Config::default());
+    harness.keys("ia<Esc>");
+    harness.editor.request_quit();
+    harness.keys("w");
+    assert!(harness.editor.should_quit());
+    assert_eq!(read(&first), "a\n");
+}
+
+#[test]
+fn scripts() {
+    // commands get the rest of the : line and edit through the api
+    let mut harness = Harness::with_script(
+        "one\ntwo",
+        "fim.command('shout', function(args) fim.set_line(2, args:upper()) fim.set_cursor(2, 1) end)",
+    );
+    harness.keys(":shout hey<CR>");
+    assert_eq!((harness.text().as_str(), harness.cursor()), ("one\nHEY", (2, 1)));
+    harness.keys(":nosuch<CR>");
+    assert!(harness.message().is_some());
+
+    // change handlers run once per frame with edits, not per key
+    let mut harness = Harness::with_script(
+        "abc",
+        "changes = 0 fim.on('change', function() changes = changes + 1 end)",
+    );
+    harness.keys("xx");
+    harness.editor.poll();
+    harness.editor.poll();
+    harness.editor.run_lua("fim.message(tostring(changes))");
+    assert_eq!((harness.text().as_str(), harness.message()), ("c", Some("1")));
+
+    // mappings made from lua act like :nnoremap and the shiftwidth never drops to 0
+    let mut harness = Harness::with_script(
+        "a\nb\nc",
+        "fim.map('nnoremap', 'Q', 'dd') fim.set_option('shiftwidth', 0)",
+    );
+    harness.keys("Q");
+    assert_eq!(harness.text(), "b\nc");
+    harness.editor.run_lua("fim.message(tostring(fim.get_option('shiftwidth')))");
+    assert_eq!(harness.message(), Some("1"));
+
+    // keys fed from a key handler do not call the handler again. the handler runs before the key
+    // acts, so x deletes before l moves
+    let mut harness = Harness::with_script(
+        "abcdef",
+        "keys = 0 fim.on('key', function() keys = keys + 1 fim.feed('x') end)",
+    );
+    harness.keys("l");
+    harness.editor.run_lua("fim.message(tostring(keys))");
+    assert_eq!((harness.text().as_str(), harness.message()), ("bcdef", Some("1")));
+}
+
+#[test]
+fn files_changed_on_disk() {
+    let dir = TestDir::new("watched");
+    let (first, second) = (dir.file("a", "a\n"), dir.file("b", "b\n"));
+    let mut harness = Harness::open(&[&first, &second], &Config::default());
+    let (watcher, changes) = FileWatcher::channel();
+    harness.editor.set_watcher(watcher);
+    let change = |path: &Path| {
+        let event = Event::new(EventKind::Modify(ModifyKind::Any)).add_path(fs::canonicalize(path).unwrap());
+        changes.send(Ok(event)).unwrap();
+    };
+
+    // a buffer that is not the current one and has no changes of its own reloads
+    fs::write(&second, "changed\n").unwrap();
+    change(&second);
+    harness.editor.poll();
+    assert_eq!(harness.editor.buffers.iter().nth(1).unwrap().get_line(0).content, "changed");
+
+    // one with changes asks first
+    harness.keys("x");
+    fs::write(&first, "from outside\n").unwrap();
+    change(&first);
+    harness.editor.poll();
+    assert!(harness.message().is_some_and(|message| message.contains("changed on disk")));
+    harness.keys("r");
+    assert_eq!(harness.text(), "from outside");
+}
+
+#[test]
+fn swap_files() {
+    let dir = TestDir::new("swapped");
+    let (first, second) = (dir.file("a", "a\n"), dir.file("b", "b\n"));
+    let mut config = Config::default();
+    config.general.swap_interval_ms = 1;
+    let swapped = |path: &Path| swap::read(path.to_str().unwrap()).map(|swap| swap.lines);
+
+    // every modified buffer gets its changes written to its swap file, which outlives a crash
+    let mut harness = Harness::open(&[&first, &second], &config);
+    harness.editor.open_swaps();
+    harness.editor.swap_interval = Some(Duration::ZERO);
+    harness.keys("x");
+    harness.editor.buffers.get_mut(1).delete_line_curr();
+    harness.editor.poll();
+    let empty = Some(vec![String::new()]);
+    assert_eq!((swapped(&first), swapped(&second)), (empty.clone(), empty));
+    drop(harness);
+
+    // the next editor on the files offers them back, one file at a time
+    let mut harness = Harness::open(&[&first, &second], &config);
+    harness.editor.open_swaps();
+    assert!(harness.message().is_some_and(|message| message.contains("has a swap file")));
+    harness.keys("r");
+    assert_eq!(harness.text(), "");
+    assert!(harness.message().is_some_and(|message| message.contains("has a swap file")));
+    harness.keys("e");
+    assert_eq!(harness.editor.buffers.iter().nth(1).unwrap().get_line(0).content, "b");
+
+    // the changes were not kept, a clean exit takes the swap files with it
+    let mut harness = Harness::open(&[&first], &config);
+    harness.editor.open_swaps();
+    harness.keys("e");
+    assert_eq!(harness.text(), "a");
+    harness.editor.close();
+    assert_eq!(swapped(&first), None);
+}
+
+#[test]
+fn sessions() {
+    let dir = TestDir::new("sessions");
+    let (first, second) = (dir.file("a", "abc\ndef\n"), dir.file("b\nc", "ghi\n"));
+    let session_file = dir.path().join("session.fim");
+    let session_path = session_file.to_str().unwrap();
+
+    // :mksession writes the files, cursors, register and history, loading it gives them back
+    let mut harness = Harness::open(&[&first, &second], &Config::default());
+    // nothing yanks yet, the register is filled by hand
+    harness.editor.yank_buffer = "two\nlines".to_owned();
+    harness.keys("jl");
+    harness.keys(&format!(":mksession {}<CR>", session_path));
+    let session = Session::load(session_path).unwrap();
+    let paths: Vec<_> = session.buffers.iter().map(|buf| Path::new(&buf.path).to_path_buf()).collect();
+    assert_eq!(paths, [fs::canonicalize(&first).unwrap(), fs::canonicalize(&second).unwrap()]);
+    assert_eq!(session.current, 0);
+
+    let mut restored = Harness::open(&[&first, &second], &Config::default());
+    restored.editor.restore(&session);
+    assert_eq!(restored.cursor(), (2, 1));
+    assert_eq!(restored.register('"'), Some("two\nlines"));
+    assert_eq!(restored.editor.session().command_history, [format!("mksession {}", session_path)]);
+}
+
+// a plugin that writes down which hooks ran, it takes Q and :hello
+struct Recorder {
+    name: &'static str,
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+impl EditorPlugin for Recorder {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn on_startup(&mut self, _editor: &mut Editor) {
+        self.log.borrow_mut().push(format!("startup {}", self.name));
+    }
+
+    fn on_key(&mut self, _editor: &mut Editor, key: &str) -> bool {
+        self.log.borrow_mut().push(format!("key {}", key));
+        key == "Q"
+    }
+
+    fn on_buffer_changed(&mut self, editor: &mut Editor) {
+        self.log.borrow_mut().push(format!("changed {}", editor.get_text()));
+    }
+
+    fn on_command(&mut self, _editor: &mut Editor, name: &str, args: &str) -> bool {
+        self.log.borrow_mut().push(format!("command {} {}", name, args));
+        name == "hello"
+    }
+}
+
+#[test]
+fn plugins() {
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let mut harness = Harness::new("abc");
+    harness.editor.register_plugin(Box::new(Recorder { name: "recorder", log: log.clone() }));
+    // a second plugin by the same name is not registered
+    harness.editor.register_plugin(Box::new(Recorder { name: "recorder", log: log.clone() }));
+    assert_eq!(*log.borrow(), ["startup recorder"]);
+
+    // keys the plugin takes do nothing else, the others go on to the editor
+    log.borrow_mut().clear();
+    harness.keys("Qx");
+    assert_eq!(harness.text(), "bc");
+    assert_eq!(*log.borrow(), ["key Q", "key x"]);
+
+    // edits are reported once the editor polls
+    log.borrow_mut().clear();
+    harness.editor.poll();
+    harness.editor.poll();
+    assert_eq!(*log.borrow(), ["changed bc"]);
+
+    log.borrow_mut().clear();
+    harness.keys(":hello world<CR>");
+    assert!(log.borrow().contains(&"command hello world".to_owned()));
+    assert_eq!(harness.message(), None);
+    harness.keys(":bogus<CR>");
+    assert_eq!(harness.message(), Some("not an editor command: bogus"));
+}
+
+#[test]
+fn column_guide() {
+    // lines are measured as they are shown, tabs to their tab stop and wide chars as two columns
+    let mut harness = Harness::new("\tx\n日本語\nabcd");
+    harness.editor.register_plugin(plugins::by_name("guide").unwrap());
+    harness.keys(":guide 4<CR>");
+    assert_eq!(harness.message(), Some("guide at 4, 2 lines past it"));
+    harness.keys("<C-g>");
+    assert_eq!(harness.cursor(), (2, 0));
+}
+
+#[test]
+fn registers() {
+    let harness = Harness::new("a");
+    assert_eq!(harness.register('"'), Some(""));
+}
+
+#[test]
+#[ignore = "p is not implemented"]
+fn swapping_lines() {
+    assert_keys("hello\nworld", "ddp", "world\nhello");
+}
+
+#[test]
+#[ignore = "dw is not implemented"]
+fn deleting_words() {
+    assert_keys("hello world", "dw", "world");
+}
+
+#[test]
+#[ignore = "undo and redo are not implemented"]
+fn undo_redo() {
+    assert_keys("abc", "xu", "abc");
+    assert_keys("abc", "xu<C-r>", "bc");
+}
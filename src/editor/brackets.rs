@@ -0,0 +1,131 @@
+use super::buffer::Line;
+
+// how many lines to scan for a match before giving up
+pub const SEARCH_LIMIT: usize = 5000;
+
+// parses pairs written like vim's matchpairs, "(:),[:],{:}"
+pub fn parse_pairs(s: &str) -> Vec<(char, char)> {
+    s.split(',')
+        .filter_map(|pair| {
+            let mut chars = pair.trim().chars();
+            match (chars.next(), chars.next(), chars.next(), chars.next()) {
+                (Some(open), Some(':'), Some(close), None) => Some((open, close)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/*
+* finds the bracket matching the one at (line, char_pos), or with search_line the first bracket
+* at or after char_pos on the line. positions are 0 indexed (line, char). is_code(line, byte)
+* tells brackets in code from those in strings and comments, which only match each other
+*/
+pub fn find_match(
+    lines: &[Line],
+    line: usize,
+    char_pos: usize,
+    search_line: bool,
+    pairs: &[(char, char)],
+    is_code: impl Fn(usize, usize) -> bool,
+) -> Option<(usize, usize)> {
+    let content = &lines.get(line)?.content;
+
+    let (start_char, start_offset, c) = content
+        .char_indices()
+        .enumerate()
+        .skip(char_pos)
+        .take(if search_line { usize::MAX } else { 1 })
+        .find(|(_, (_, c))| pairs.iter().any(|(open, close)| c == open || c == close))
+        .map(|(i, (offset, c))| (i, offset, c))?;
+
+    let &(open, close) = pairs.iter().find(|(open, close)| c == *open || c == *close)?;
+    let forward = c == open;
+    let start_is_code = is_code(line, start_offset);
+
+    let mut depth = 0;
+    if forward {
+        for l in line..lines.len().min(line + SEARCH_LIMIT) {
+            for (i, (offset, c)) in lines[l].content.char_indices().enumerate() {
+                if l == line && offset <= start_offset {
+                    continue;
+                }
+                if (c != open && c != close) || is_code(l, offset) != start_is_code {
+                    continue;
+                }
+                if c == open {
+                    depth += 1;
+                } else if depth == 0 {
+                    return Some((l, i));
+                } else {
+                    depth -= 1;
+                }
+            }
+        }
+    } else {
+        for l in (line.saturating_sub(SEARCH_LIMIT)..=line).rev() {
+            let chars: Vec<(usize, char)> = lines[l].content.char_indices().collect();
+            let end = if l == line { start_char } else { chars.len() };
+            for i in (0..end).rev() {
+                let (offset, c) = chars[i];
+                if (c != open && c != close) || is_code(l, offset) != start_is_code {
+                    continue;
+                }
+                if c == close {
+                    depth += 1;
+                } else if depth == 0 {
+                    return Some((l, i));
+                } else {
+                    depth -= 1;
+                }
+            }
+        }
+    }
+    None
+}
+
+// the innermost open/close pair around (line, char_pos), as positions of the two brackets
+pub fn find_enclosing(
+    lines: &[Line],
+    line: usize,
+    char_pos: usize,
+    pair: (char, char),
+    is_code: impl Fn(usize, usize) -> bool,
+) -> Option<((usize, usize), (usize, usize))> {
+    let (open, close) = pair;
+    let pairs = [pair];
+
+    // on a bracket already
+    match lines.get(line)?.content.chars().nth(char_pos) {
+        Some(c) if c == open => {
+            let end = find_match(lines, line, char_pos, false, &pairs, &is_code)?;
+            return Some(((line, char_pos), end));
+        }
+        Some(c) if c == close => {
+            let start = find_match(lines, line, char_pos, false, &pairs, &is_code)?;
+            return Some((start, (line, char_pos)));
+        }
+        _ => (),
+    }
+
+    let mut depth = 0;
+    for l in (line.saturating_sub(SEARCH_LIMIT)..=line).rev() {
+        let chars: Vec<(usize, char)> = lines[l].content.char_indices().collect();
+        let end = if l == line { char_pos.min(chars.len()) } else { chars.len() };
+        for i in (0..end).rev() {
+            let (offset, c) = chars[i];
+            if (c != open && c != close) || !is_code(l, offset) {
+                continue;
+            }
+            if c == close {
+                depth += 1;
+            } else if depth == 0 {
+                let end = find_match(lines, l, i, false, &pairs, &is_code)?;
+                return Some(((l, i), end));
+            } else {
+                depth -= 1;
+            }
+        }
+    }
+    None
+}
@@ -15,7 +15,8 @@ pub struct Buffer {
 
     pub lines: Vec<Line>,
     cursor: Cursor,
-    pub want_cursor: usize,
+    // x in columns that vertical motions keep to, infinite after $
+    pub want_x: f32,
 
     range_start: Cursor,
     range_end: Cursor,
@@ -23,9 +24,68 @@ pub struct Buffer {
     // save dest
     file_path: String,
     is_modified: bool,
+    // the file as last read or written, to notice changes made by other programs
+    disk_stamp: Option<DiskStamp>,
+    // whether the swap file is ours to write and the revision last written to it
+    pub swap_owner: SwapOwner,
+    pub swapped_revision: Option<u64>,
+    // bumped on every edit so derived data knows when to rebuild
+    revision: u64,
+
+    syntax: Option<String>,
+    syntax_mask: Option<SyntaxMask>,
+    folds: Folds,
+
+    pub indent_rules: IndentRules,
+    pub comment_tokens: CommentTokens,
+    pub settings: BufferSettings,
+
+    // set for files opened in large file mode, holds the lines not loaded yet
+    large_file: Option<LargeFile>,
+    // lines loaded in front of the others since whoever draws the buffer last asked
+    rows_shifted: usize,
+    // the rows last laid out, asked for several times a frame
+    row_cache: RefCell<Option<RowCache>>,
 
     // EXTERNAL STATE
     pub editor_mode: Mode,
+    // glyph advances of the font the buffer is drawn with, none for a monospace font
+    pub advances: Option<Advances>,
+}
+
+// per-buffer options, initialized from the global config and .editorconfig
+#[derive(Debug, Clone)]
+pub struct BufferSettings {
+    pub tabstop: usize,
+    pub shiftwidth: usize,
+    pub expandtab: bool,
+    pub match_pairs: Vec<(char, char)>,
+    pub auto_pairs: Vec<(char, char)>,
+
+    // applied when writing the file
+    pub line_ending: LineEnding,
+    pub charset: Charset,
+    pub trim_trailing_whitespace: bool,
+    pub insert_final_newline: bool,
+    pub max_line_length: Option<usize>,
+
+    pub fold_method: FoldMethod,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Charset {
+    Utf8,
+    Utf8Bom,
+    Latin1,
+    Utf16Be,
+    Utf16Le,
 }
 
 pub struct Buffers {
@@ -33,6 +93,23 @@ pub struct Buffers {
     current_buffer: usize,
 }
 
+/*
+* display rows of a buffer and what they were laid out from, a change to any of it lays them out
+* again. loading lines of a large file changes their count or first line, not the revision
+*/
+struct RowCache {
+    revision: u64,
+    lines: usize,
+    first_line: usize,
+    hidden: Vec<(usize, usize)>,
+    wrap: Option<Wrap>,
+    tabstop: usize,
+    advances: Option<Advances>,
+    rows: Rc<[DisplayRow]>,
+    // the row each 1 indexed line starts on at index line - 1, None inside a closed fold
+    line_rows: Vec<Option<usize>>,
+}
+
 impl Cursor {
     fn new() -> Self {
         Cursor {
@@ -42,6 +119,150 @@ impl Cursor {
     }
 }
 
+impl BufferSettings {
+    pub fn from_config() -> Self {
+        match CONFIG.get() {
+            Some(config) => Self::of(config),
+            None => Self::of(&Config::default()),
+        }
+    }
+
+    // the settings a config gives buffers of any file
+    pub fn of(config: &Config) -> Self {
+        BufferSettings {
+            tabstop: config.indent.tabstop,
+            shiftwidth: config.indent.shiftwidth,
+            expandtab: config.indent.expandtab,
+            match_pairs: match_pairs(&config.brackets.match_pairs),
+            auto_pairs: parse_pairs(&config.brackets.auto_pairs),
+
+            line_ending: LineEnding::Lf,
+            charset: Charset::Utf8,
+            trim_trailing_whitespace: false,
+            insert_final_newline: true,
+            max_line_length: None,
+
+            fold_method: FoldMethod::parse(&config.folding.method).unwrap_or(FoldMethod::Manual),
+        }
+    }
+
+    // config settings with the overrides for the file's extension
+    pub fn for_file(file_path: &str) -> Self {
+        let mut settings = Self::from_config();
+        let ext = Path::new(file_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
+
+        let default;
+        let config = match CONFIG.get() {
+            Some(config) => config,
+            None => {
+                default = Config::default();
+                &default
+            }
+        };
+        if let Some(pairs) = config.brackets.match_pairs_ft.get(ext) {
+            settings.match_pairs = match_pairs(pairs);
+        }
+        if let Some(pairs) = config.brackets.auto_pairs_ft.get(ext) {
+            settings.auto_pairs = parse_pairs(pairs);
+        }
+        if let Some(method) = config.folding.method_ft.get(ext).and_then(|m| FoldMethod::parse(m)) {
+            settings.fold_method = method;
+        }
+        settings
+    }
+}
+
+// quote-like pairs cannot be matched by direction
+fn match_pairs(s: &str) -> Vec<(char, char)> {
+    parse_pairs(s)
+        .into_iter()
+        .filter(|(open, close)| open != close)
+        .collect()
+}
+
+impl LineEnding {
+    // uses the first line break in the file, defaulting to lf
+    pub fn detect(bytes: &[u8]) -> Self {
+        match bytes.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            Some(i) if bytes[i] == b'\n' => LineEnding::Lf,
+            Some(i) if bytes.get(i + 1) == Some(&b'\n') => LineEnding::CrLf,
+            Some(_) => LineEnding::Cr,
+            None => LineEnding::Lf,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+        }
+    }
+}
+
+impl Charset {
+    // only byte order marks are detected, everything else is assumed utf-8
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            Charset::Utf8Bom
+        } else if bytes.starts_with(&[0xFE, 0xFF]) {
+            Charset::Utf16Be
+        } else if bytes.starts_with(&[0xFF, 0xFE]) {
+            Charset::Utf16Le
+        } else {
+            Charset::Utf8
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Charset::Utf8 | Charset::Utf8Bom => {
+                let bytes = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+                String::from_utf8_lossy(bytes).into_owned()
+            }
+            Charset::Latin1 => bytes.iter().map(|b| *b as char).collect(),
+            Charset::Utf16Be | Charset::Utf16Le => {
+                let units = bytes.chunks_exact(2).map(|pair| match self {
+                    Charset::Utf16Be => u16::from_be_bytes([pair[0], pair[1]]),
+                    _ => u16::from_le_bytes([pair[0], pair[1]]),
+                });
+                char::decode_utf16(units)
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .skip_while(|c| *c == '\u{FEFF}')
+                    .collect()
+            }
+        }
+    }
+
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Charset::Utf8 => text.as_bytes().to_vec(),
+            Charset::Utf8Bom => [&[0xEF, 0xBB, 0xBF], text.as_bytes()].concat(),
+            Charset::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+                .collect(),
+            Charset::Utf16Be => text.encode_utf16().flat_map(|u| u.to_be_bytes()).collect(),
+            Charset::Utf16Le => text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect(),
+        }
+    }
+}
+
+// splits decoded file contents into lines, accepting any line ending
+fn split_lines(text: &str) -> Vec<Line> {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let text = text.strip_suffix('\n').unwrap_or(&text);
+    text.split('\n').map(|line| Line::from(line.to_owned())).collect()
+}
+
+// lines as text with every line ending in \n, as diffs expect
+fn join_lines(lines: &[Line]) -> String {
+    lines.iter().flat_map(|line| [line.content.as_str(), "\n"]).collect()
+}
+
 impl From<String> for Line {
     fn from(data: String) -> Self {
         Self {
@@ -78,48 +299,68 @@ impl Line {
         }
     }
 
+    // positions past the end of the line stand for its end
     pub fn delete_range<R>(&mut self, range: R)
     where
         R: RangeBounds<usize>,
     {
-        let start = range.start_bound();
-        let new_start = match start {
-            std::ops::Bound::Included(&n) => {
-                let offset = self.content.char_indices().nth(n).unwrap_or((0, 0 as char)).0;
-                std::ops::Bound::Included(offset)
-            }
-            std::ops::Bound::Excluded(&n) => {
-                let offset = self.content.char_indices().nth(n + 1).unwrap().0;
-                std::ops::Bound::Included(offset)
-            }
-            _ => std::ops::Bound::Unbounded,
+        let start = match range.start_bound() {
+            std::ops::Bound::Included(&n) => n,
+            std::ops::Bound::Excluded(&n) => n.saturating_add(1),
+            std::ops::Bound::Unbounded => 0,
         };
-        let end = range.end_bound();
-        let new_end = match end {
-            std::ops::Bound::Included(&n) => {
-                if let Some(offset) = self.content.char_indices().nth(n + 1) {
-                    std::ops::Bound::Excluded(offset.0)
-                } else {
-                    std::ops::Bound::Unbounded
-                }
-            }
-            std::ops::Bound::Excluded(&n) => {
-                if let Some(offset) = self.content.char_indices().nth(n) {
-                    std::ops::Bound::Excluded(offset.0)
-                } else {
-                    std::ops::Bound::Unbounded
-                }
-            }
-            _ => std::ops::Bound::Unbounded,
+        let end = match range.end_bound() {
+            std::ops::Bound::Included(&n) => n.saturating_add(1),
+            std::ops::Bound::Excluded(&n) => n,
+            std::ops::Bound::Unbounded => usize::MAX,
         };
-        self.content.replace_range((new_start, new_end), "");
+        let (start, end) = (self.offset_of(start), self.offset_of(end));
+        if start < end {
+            self.content.replace_range(start..end, "");
+        }
     }
 
     pub fn len(&self) -> usize {
         self.content.chars().count()
     }
+
+    // byte offset of the char at pos, or the end of the line
+    pub fn offset_of(&self, pos: usize) -> usize {
+        self.content
+            .char_indices()
+            .nth(pos)
+            .map_or(self.content.len(), |(offset, _)| offset)
+    }
+
+    // first char of the grapheme cluster holding pos, the end of the line for positions past it
+    pub fn cluster_start(&self, pos: usize) -> usize {
+        if pos >= self.len() {
+            return self.len();
+        }
+        layout::clusters(&self.content)
+            .map(|(start, _)| start)
+            .take_while(|start| *start <= pos)
+            .last()
+            .unwrap_or(0)
+    }
+
+    // first char of the cluster count clusters after the one holding pos, before it when count
+    // is negative. stops at the start and the end of the line
+    pub fn cluster_offset(&self, pos: usize, count: isize) -> usize {
+        let starts: Vec<usize> = layout::clusters(&self.content).map(|(start, _)| start).collect();
+        let idx = starts.iter().take_while(|start| **start <= pos).count() as isize - 1;
+        let idx = match pos >= self.len() {
+            true => starts.len() as isize,
+            false => idx,
+        };
+        let target = idx.saturating_add(count).clamp(0, starts.len() as isize) as usize;
+        starts.get(target).copied().unwrap_or(self.len())
+    }
 }
 
+// bytes of a large file used to detect its charset and line endings
+const DETECT_BYTES: usize = 64 * 1024;
+
 impl Buffer {
     pub fn new() -> Self {
         Buffer {
@@ -127,44 +368,109 @@ impl Buffer {
 
             lines: Vec::from([Line::new()]),
             cursor: Cursor::new(),
-            want_cursor: 0,
+            want_x: 0.,
 
             range_start: Cursor::new(),
             range_end: Cursor::new(),
 
             file_path: String::from(""),
             is_modified: false,
+            disk_stamp: None,
+            swap_owner: SwapOwner::Unchecked,
+            swapped_revision: None,
+            revision: 0,
 
-            editor_mode: Mode::Insert,
+            syntax: None,
+            syntax_mask: None,
+            folds: Folds::new(),
+
+            indent_rules: IndentRules::new(),
+            comment_tokens: CommentTokens::for_file("", None),
+            settings: BufferSettings::from_config(),
+
+            large_file: None,
+            rows_shifted: 0,
+            row_cache: RefCell::new(None),
+
+            editor_mode: Mode::Normal,
+            advances: None,
         }
     }
 
     pub fn from_file(file_path: &str) -> Self {
+        let threshold = CONFIG.get().map_or(Config::default().general.large_file_mb, |config| config.general.large_file_mb);
+        Buffer::open(file_path, threshold * 1024 * 1024)
+    }
+
+    // files of large_bytes and more are mapped and loaded as the cursor gets to them
+    pub fn open(file_path: &str, large_bytes: u64) -> Self {
         let path = Path::new(file_path);
-        let file = match std::fs::File::open(file_path) {
-            Ok(file) => file,
-            Err(_) => std::fs::File::create(file_path).unwrap(),
+        let large_file = std::fs::metadata(file_path)
+            .is_ok_and(|meta| meta.len() >= large_bytes)
+            .then(|| LargeFile::open(file_path).ok())
+            .flatten();
+
+        let bytes = match &large_file {
+            Some(large_file) => large_file.bytes()[..large_file.bytes().len().min(DETECT_BYTES)].to_vec(),
+            None => match std::fs::read(file_path) {
+                Ok(bytes) => bytes,
+                Err(_) => {
+                    std::fs::File::create(file_path).unwrap();
+                    Vec::new()
+                }
+            },
         };
-        let reader = BufReader::new(file);
-        let lines: Vec<Line> = reader
-            .lines()
-            .map(|line| Line::from(line.expect("Failed to read line"))).collect();
 
-        Buffer {
+        // detect what we can from the file, then let .editorconfig override it
+        let mut settings = BufferSettings::for_file(file_path);
+        settings.charset = Charset::detect(&bytes);
+        settings.line_ending = LineEnding::detect(&bytes);
+        editorconfig::apply(file_path, &mut settings);
+
+        let (lines, syntax) = match large_file {
+            // large files are read as utf-8 without the features that scan the whole buffer
+            Some(_) => {
+                settings.charset = Charset::Utf8;
+                settings.fold_method = FoldMethod::Manual;
+                (vec![Line::new()], None)
+            }
+            None => (split_lines(&settings.charset.decode(&bytes)), syntax_for_file(file_path)),
+        };
+
+        let mut buf = Buffer {
             name: String::from(path.file_name().and_then(|name| name.to_str()).unwrap()),
 
             lines,
             cursor: Cursor::new(),
-            want_cursor: 0,
+            want_x: 0.,
 
             range_start: Cursor::new(),
             range_end: Cursor::new(),
 
             file_path: file_path.to_string(),
             is_modified: false,
+            disk_stamp: DiskStamp::of(file_path),
+            swap_owner: SwapOwner::Unchecked,
+            swapped_revision: None,
+            revision: 0,
 
-            editor_mode: Mode::Insert,
-        }
+            comment_tokens: CommentTokens::for_file(file_path, syntax.as_deref()),
+            syntax,
+            syntax_mask: None,
+            folds: Folds::new(),
+
+            indent_rules: IndentRules::for_file(file_path),
+            settings,
+
+            large_file,
+            rows_shifted: 0,
+            row_cache: RefCell::new(None),
+
+            editor_mode: Mode::Normal,
+            advances: None,
+        };
+        buf.load_lines_to(LOAD_AHEAD);
+        buf
     }
 
     pub fn line_count(&self) -> usize {
@@ -186,32 +492,15 @@ impl Buffer {
 
 // Line accessor methods
 impl Buffer {
+    // the line offset lines from the cursor's, none past either end of the buffer
     pub fn curr_line_offset_mut(&mut self, offset: isize) -> Option<&mut Line> {
-        let offset_adj: isize = isize::try_from(self.cursor.line_pos).unwrap() + offset - 1;
-
-        if offset_adj >= 0 {
-            Some(
-                self.lines
-                    .get_mut(offset_adj as usize)
-                    .expect("requested line out of bounds"),
-            )
-        } else {
-            None
-        }
+        let idx = (self.cursor.line_pos - 1).checked_add_signed(offset)?;
+        self.lines.get_mut(idx)
     }
 
     pub fn curr_line_offset(&self, offset: isize) -> Option<&Line> {
-        let offset_adj: isize = isize::try_from(self.cursor.line_pos).unwrap() + offset - 1;
-
-        if offset_adj >= 0 {
-            Some(
-                self.lines
-                    .get(offset_adj as usize)
-                    .expect("requested line out of bounds"),
-            )
-        } else {
-            None
-        }
+        let idx = (self.cursor.line_pos - 1).checked_add_signed(offset)?;
+        self.lines.get(idx)
     }
 
     // pub fn curr_line_offset_mut_clamped(&mut self, offset: isize) -> &mut Line {
@@ -255,26 +544,82 @@ impl Buffer {
 impl Buffer {
     pub fn insert_text(&mut self, text: &str, move_cursor: bool) {
         let old_cursor = self.cursor().clone();
-        let old_want = self.want_cursor;
+        let old_want = self.want_x;
         for (offset, to_insert) in text.split("\n").enumerate() {
             if offset != 0 {
                 self.insert_lines(1, true);
             }
             let char_pos = self.char_pos();
             self.curr_line_mut().insert(char_pos, to_insert);
-            self.move_cursor_by(to_insert.chars().count() as isize, 0, true);
+            self.move_cursor_char_to(char_pos + to_insert.chars().count());
         }
-        self.is_modified = true;
+        self.mark_modified();
         if !move_cursor {
+            // text starting with a combining mark joins the cluster before the cursor
             self.cursor = old_cursor;
-            self.want_cursor = old_want;
+            self.cursor.char_pos = self.curr_line().cluster_start(old_cursor.char_pos);
+            self.want_x = old_want;
         }
     }
 
+    // replaces the text between start (inclusive) and end (exclusive), text may span lines
+    pub fn replace_range(&mut self, start: Cursor, end: Cursor, text: &str) {
+        let (first, last) = (start.line_pos - 1, end.line_pos - 1);
+        let head = &self.lines[first].content[..self.lines[first].offset_of(start.char_pos)];
+        let tail = &self.lines[last].content[self.lines[last].offset_of(end.char_pos)..];
+        let new_lines: Vec<Line> = format!("{}{}{}", head, text, tail)
+            .split('\n')
+            .map(|line| Line::from(line.to_owned()))
+            .collect();
+        let added = new_lines.len();
+        self.lines.splice(first..=last, new_lines);
+        self.lines_changed(first + 1, added as isize - (last - first + 1) as isize);
+        self.mark_modified();
+        self.move_cursor_to(self.cursor);
+    }
+
+    // line numbers start at 1 like the cursor
+    pub fn set_line(&mut self, line_pos: usize, text: &str) {
+        let len = self.lines[line_pos - 1].len();
+        self.replace_range(Cursor { line_pos, char_pos: 0 }, Cursor { line_pos, char_pos: len }, text);
+    }
+
+    // inserts before line_pos, one past the last line appends
+    pub fn insert_line(&mut self, line_pos: usize, text: &str) {
+        if line_pos <= self.line_count() {
+            let start = Cursor { line_pos, char_pos: 0 };
+            self.replace_range(start, start, &format!("{}\n", text));
+        } else {
+            let last = self.line_count();
+            let end = Cursor { line_pos: last, char_pos: self.lines[last - 1].len() };
+            self.replace_range(end, end, &format!("\n{}", text));
+        }
+    }
+
+    // the last line left is emptied instead
+    pub fn delete_line(&mut self, line_pos: usize) {
+        let count = self.line_count();
+        if line_pos == 0 || line_pos > count {
+            return;
+        }
+        if count == 1 {
+            self.set_line(1, "");
+        } else if line_pos < count {
+            self.replace_range(Cursor { line_pos, char_pos: 0 }, Cursor { line_pos: line_pos + 1, char_pos: 0 }, "");
+        } else {
+            let start = Cursor { line_pos: line_pos - 1, char_pos: self.lines[line_pos - 2].len() };
+            let end = Cursor { line_pos, char_pos: self.lines[line_pos - 1].len() };
+            self.replace_range(start, end, "");
+        }
+    }
+
+    // deletes the grapheme cluster under the cursor
     pub fn delete_char_cursor(&mut self) {
         let char_pos = self.char_pos();
-        self.curr_line_mut().delete_char(char_pos);
-        self.is_modified = true;
+        let end = self.curr_line().cluster_offset(char_pos, 1);
+        self.curr_line_mut().delete_range(char_pos..end);
+        self.mark_modified();
+        self.move_cursor_to(self.cursor);
     }
 
     pub fn delete_text<R>(&mut self, range: R)
@@ -282,7 +627,8 @@ impl Buffer {
         R: RangeBounds<usize>,
     {
         self.curr_line_mut().delete_range(range);
-        self.is_modified = true;
+        self.mark_modified();
+        self.move_cursor_to(self.cursor);
     }
 
     pub fn insert_lines_above(&mut self, amount: usize, move_cursor: bool) {
@@ -290,6 +636,8 @@ impl Buffer {
             (self.line_pos()-1)..(self.line_pos()-1),
             iter::repeat(Line::new()).take(amount),
         );
+        self.folds.lines_inserted(self.line_pos() - 1, amount);
+        self.mark_modified();
         if !move_cursor {
             self.move_cursor_by(0, amount as isize, true);
         }
@@ -301,28 +649,52 @@ impl Buffer {
             self.line_pos()..self.line_pos(),
             iter::repeat(Line::new()).take(amount),
         );
+        self.folds.lines_inserted(self.line_pos(), amount);
+        self.mark_modified();
         if move_cursor {
             self.move_cursor_by(0, amount as isize, true);
         }
     }
 
+    // the last line left is emptied instead, a buffer always has one
     pub fn delete_line_curr(&mut self) {
+        if self.line_count() == 1 {
+            self.delete_text(0..);
+            self.move_cursor_by(0, 0, true);
+            return;
+        }
         let idx = self.line_pos() - 1;
         self.lines.remove(idx);
+        self.folds.lines_deleted(idx, 1);
+        self.mark_modified();
         self.move_cursor_by(0, 0, true); // sync cursors and clamps
     }
 
-    // clamps
+    /*
+    * deletes the 1 indexed lines in range, clamped to the buffer. deleting all of them leaves an
+    * empty line, the cursor moves onto the lines left
+    */
     pub fn delete_lines(&mut self, range: Range<usize>) {
-        let mut range_adj = range;
-        range_adj.start -= 1;
-        range_adj.end = (range_adj.end - 1).clamp(0, self.line_count());
-        self.lines.drain(range_adj);
-        self.is_modified = true;
+        let start = range.start.max(1) - 1;
+        let end = range.end.saturating_sub(1).min(self.line_count());
+        if start >= end {
+            return;
+        }
+        self.lines.drain(start..end);
+        self.folds.lines_deleted(start, end - start);
+        if self.lines.is_empty() {
+            self.lines.push(Line::new());
+        }
+        self.mark_modified();
+        self.move_cursor_to(self.cursor);
     }
 
+    // does nothing on the last line
     pub fn join_line_below(&mut self) {
         let next_idx = self.line_pos();
+        if next_idx >= self.line_count() {
+            return;
+        }
         let s: String = mem::take(&mut self.lines[next_idx].content);
         self.insert_text(&s, false);
         self.delete_lines((self.line_pos() + 1)..self.line_pos() + 2);
@@ -335,7 +707,877 @@ impl Buffer {
                 mem::take(&mut self.curr_line().content.split_at(offset.0).1.to_owned());
             self.curr_line_offset_mut(1).unwrap().content.push_str(&s);
             let range = self.char_pos()..self.curr_line().len();
-            self.curr_line_mut().delete_range(range);
+            self.delete_text(range);
+        }
+    }
+}
+
+// file io
+impl Buffer {
+    // writes the buffer to its file using the buffer's line ending and charset
+    pub fn write_file(&mut self) -> io::Result<()> {
+        if self.file_path.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "No file name"));
+        }
+        match &self.large_file {
+            Some(large_file) => {
+                // the lines after the window follow it on the next line
+                let text = self.disk_text(large_file.has_tail());
+                self.write_large(text.as_bytes())?;
+            }
+            None => {
+                let text = self.disk_text(false);
+                std::fs::write(&self.file_path, self.settings.charset.encode(&text))?;
+            }
+        }
+        self.is_modified = false;
+        self.disk_stamp = DiskStamp::of(&self.file_path);
+        Ok(())
+    }
+
+    // the lines with the buffer's line ending, ending in one with more_follows or insert_final_newline
+    fn disk_text(&self, more_follows: bool) -> String {
+        let eol = self.settings.line_ending.as_str();
+        let mut text = String::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            if i != 0 {
+                text.push_str(eol);
+            }
+            if self.settings.trim_trailing_whitespace {
+                text.push_str(line.content.trim_end_matches([' ', '\t']));
+            } else {
+                text.push_str(&line.content);
+            }
+        }
+        if more_follows || (self.settings.insert_final_newline && !text.is_empty()) {
+            text.push_str(eol);
+        }
+        text
+    }
+
+    /*
+    * a large file is streamed from its map with the window of lines in between to a file next to
+    * it, which then takes its place. the file that is mapped is never written to, the map of it
+    * is dropped before the new file is put in its place and mapped
+    */
+    fn write_large(&mut self, window: &[u8]) -> io::Result<()> {
+        let Some(large_file) = &self.large_file else {
+            return Ok(());
+        };
+        // the empty line shown before anything was read is not part of the file
+        let window = if large_file.handed_out() == 0 { &[] } else { window };
+        let path = Path::new(&self.file_path);
+        let tmp = path.with_file_name(format!(".{}.tmp", self.name));
+        let written = File::create(&tmp).and_then(|file| {
+            let mut out = BufWriter::new(file);
+            large_file.write_with(&mut out, window)?;
+            out.flush()?;
+            if let Ok(meta) = std::fs::metadata(path) {
+                std::fs::set_permissions(&tmp, meta.permissions())?;
+            }
+            Ok(())
+        });
+        if let Err(err) = written {
+            let _ = std::fs::remove_file(&tmp);
+            return Err(err);
+        }
+
+        let (first, loaded) = (large_file.first_line(), large_file.first_line() + large_file.handed_out());
+        self.large_file = None;
+        let renamed = std::fs::rename(&tmp, path);
+        let window_end = match renamed {
+            Ok(()) if loaded > first => first + self.lines.len(),
+            Ok(()) => first,
+            Err(_) => loaded,
+        };
+        let mut large_file = LargeFile::open(&self.file_path)?;
+        large_file.set_window(first, window_end);
+        self.large_file = Some(large_file);
+        renamed
+    }
+
+    // true if another program changed the file since it was read or written, a deleted file is
+    // left alone so the buffer can still be written back
+    pub fn changed_on_disk(&self) -> bool {
+        match DiskStamp::of(&self.file_path) {
+            Some(stamp) => self.disk_stamp != Some(stamp),
+            None => false,
+        }
+    }
+
+    // reads the file again, keeping the cursor where it was as far as the new text allows
+    pub fn reload(&mut self) {
+        let (cursor, mode) = (self.cursor, mem::replace(&mut self.editor_mode, Mode::Normal));
+        let (advances, swap_owner) = (self.advances.take(), self.swap_owner);
+        *self = Buffer::from_file(&self.file_path);
+        self.editor_mode = mode;
+        self.advances = advances;
+        self.swap_owner = swap_owner;
+        self.restore_cursor(cursor);
+    }
+
+    // puts the cursor back where it was as far as the current text allows
+    pub fn restore_cursor(&mut self, cursor: Cursor) {
+        self.load_lines_to(cursor.line_pos);
+        self.move_cursor_line_to(cursor.line_pos.clamp(1, self.line_count()));
+        self.move_cursor_char_to(cursor.char_pos);
+        self.move_cursor_by(0, 0, true);
+    }
+
+    // keeps the buffer as is, it stays modified so writing it replaces the changes on disk
+    pub fn keep_ours(&mut self) {
+        self.disk_stamp = DiskStamp::of(&self.file_path);
+    }
+
+    // unified diff from the buffer to the file on disk
+    pub fn disk_diff(&self) -> String {
+        let theirs = match std::fs::read(&self.file_path) {
+            Ok(bytes) => join_lines(&split_lines(&self.settings.charset.decode(&bytes))),
+            Err(_) => String::new(),
+        };
+        self.diff_with(&theirs, "disk")
+    }
+
+    // unified diff from the buffer to text with every line ending in \n
+    fn diff_with(&self, theirs: &str, name: &str) -> String {
+        let ours = join_lines(&self.lines);
+        TextDiff::from_lines(ours.as_str(), theirs)
+            .unified_diff()
+            .header("buffer", name)
+            .to_string()
+    }
+
+    // unified diff from the buffer to the lines of a swap file
+    pub fn swap_diff(&self, swap: &SwapData) -> String {
+        let theirs: String = swap.lines.iter().flat_map(|line| [line.as_str(), "\n"]).collect();
+        self.diff_with(&theirs, "swap")
+    }
+
+    // true if the swap file holds something other than the buffer
+    pub fn differs_from(&self, swap: &SwapData) -> bool {
+        self.lines.len() != swap.lines.len()
+            || self.lines.iter().zip(&swap.lines).any(|(line, swapped)| line.content != *swapped)
+    }
+
+    // takes the text and cursor of a swap file left behind by a crash
+    pub fn recover(&mut self, swap: SwapData) {
+        self.lines = swap.lines.into_iter().map(Line::from).collect();
+        self.folds = Folds::new();
+        self.mark_modified();
+        self.restore_cursor(swap.cursor);
+    }
+
+    pub fn file_path(&self) -> &str {
+        &self.file_path
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.is_modified
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    fn mark_modified(&mut self) {
+        self.is_modified = true;
+        self.revision += 1;
+    }
+
+    // moves folds after lines from 0 indexed at were added or removed
+    fn lines_changed(&mut self, at: usize, delta: isize) {
+        if delta > 0 {
+            self.folds.lines_inserted(at, delta as usize);
+        } else if delta < 0 {
+            self.folds.lines_deleted(at, (-delta) as usize);
+        }
+    }
+}
+
+// bracket matching
+impl Buffer {
+    // reparses the lines edited since the mask was last brought up to date
+    pub fn update_syntax_mask(&mut self) {
+        let Some(syntax) = &self.syntax else {
+            return;
+        };
+        match &mut self.syntax_mask {
+            Some(mask) if mask.revision == self.revision => (),
+            Some(mask) => mask.update(&self.lines, self.revision),
+            None => self.syntax_mask = Some(SyntaxMask::build(syntax, &self.lines, self.revision)),
+        }
+    }
+
+    /*
+    * the bracket pairing with the one at a position (or the next one on its line with
+    * search_line) as a 1 indexed line and char position. strings and comments are told apart
+    * with the syntax mask as it is, this runs every frame and leaves updating it to the editor
+    */
+    pub fn matching_bracket_at(&self, at: Cursor, search_line: bool) -> Option<Cursor> {
+        find_match(
+            &self.lines,
+            at.line_pos - 1,
+            at.char_pos,
+            search_line,
+            &self.settings.match_pairs,
+            self.is_code(),
+        )
+        .map(|(line, char)| Cursor {
+            line_pos: line + 1,
+            char_pos: char,
+        })
+    }
+
+    fn is_code(&self) -> impl Fn(usize, usize) -> bool + '_ {
+        let mask = self.syntax_mask.as_ref().filter(|_| self.syntax.is_some());
+        move |line, offset| mask.map_or(true, |mask| mask.is_code(line, offset))
+    }
+
+    // positions of the innermost pair enclosing the cursor
+    pub fn enclosing_pair(&mut self, pair: (char, char)) -> Option<(Cursor, Cursor)> {
+        self.update_syntax_mask();
+        let to_cursor = |(line, char): (usize, usize)| Cursor {
+            line_pos: line + 1,
+            char_pos: char,
+        };
+        find_enclosing(&self.lines, self.line_pos() - 1, self.char_pos(), pair, self.is_code())
+            .map(|(start, end)| (to_cursor(start), to_cursor(end)))
+    }
+
+    pub fn text_object(&mut self, object: &str) -> Option<TextRange> {
+        self.update_syntax_mask();
+        textobject::select(&self.lines, self.cursor, object, self.is_code())
+    }
+
+    pub fn jump_to_matching_bracket(&mut self) {
+        self.update_syntax_mask();
+        if let Some(target) = self.matching_bracket_at(self.cursor, true) {
+            self.move_cursor_to(target);
+        }
+    }
+}
+
+// auto pairs
+impl Buffer {
+    // inserts a typed char along with its closer, or steps over a closer already there.
+    // returns false if the text should be inserted normally
+    pub fn insert_with_pairs(&mut self, text: &str) -> bool {
+        let mut chars = text.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return false;
+        };
+        let prev = self.char_pos().checked_sub(1).and_then(|i| self.curr_line().content.chars().nth(i));
+        let next = self.curr_line().content.chars().nth(self.char_pos());
+
+        if next == Some(c) && self.settings.auto_pairs.iter().any(|(_, close)| *close == c) {
+            self.move_cursor_by(1, 0, true);
+            return true;
+        }
+
+        let Some(&(open, close)) = self.settings.auto_pairs.iter().find(|(open, _)| *open == c) else {
+            return false;
+        };
+        // don't pair in the middle of words, e.g. apostrophes
+        if next.is_some_and(|n| n.is_alphanumeric())
+            || (open == close && prev.is_some_and(|p| p.is_alphanumeric()))
+        {
+            return false;
+        }
+
+        self.insert_text(&format!("{}{}", open, close), false);
+        self.move_cursor_by(1, 0, true);
+        true
+    }
+
+    // deletes both halves of an empty pair around the cursor, returns false if there is none
+    pub fn delete_pair(&mut self) -> bool {
+        let Some(before) = self.char_pos().checked_sub(1) else {
+            return false;
+        };
+        let mut chars = self.curr_line().content.chars().skip(before);
+        let (Some(prev), Some(next)) = (chars.next(), chars.next()) else {
+            return false;
+        };
+        if !self.settings.auto_pairs.contains(&(prev, next)) {
+            return false;
+        }
+
+        self.delete_text(before..before + 2);
+        self.move_cursor_by(-1, 0, true);
+        true
+    }
+}
+
+// auto indentation
+impl Buffer {
+    // display width of leading whitespace, expanding tabs
+    pub fn indent_width(&self, text: &str) -> usize {
+        display_width(leading_whitespace(text), self.settings.tabstop)
+    }
+
+    // whitespace spanning width columns, using tabs unless expandtab is set
+    pub fn indent_string(&self, width: usize) -> String {
+        if self.settings.expandtab {
+            " ".repeat(width)
+        } else {
+            let tabstop = self.settings.tabstop;
+            "\t".repeat(width / tabstop) + &" ".repeat(width % tabstop)
+        }
+    }
+
+    // splits the line at the cursor, indenting the new line like the current one
+    pub fn newline_indented(&mut self) {
+        let line = &self.curr_line().content;
+        let split = self.curr_line().offset_of(self.char_pos());
+        let base = leading_whitespace(line).to_owned();
+        let before = line[..split].to_owned();
+        let after = line[split..].trim_start().to_owned();
+
+        let mut indent = base.clone();
+        let increase = self.indent_rules.increases_after(&before);
+        if increase {
+            indent = self.indent_string(self.indent_width(&base) + self.settings.shiftwidth);
+        }
+
+        // cursor between a bracket pair puts the closer on its own line
+        let closes = increase
+            && before
+                .trim_end()
+                .chars()
+                .last()
+                .and_then(closer_of)
+                .is_some_and(|closer| after.starts_with(closer));
+
+        let mut new_lines = Vec::new();
+        if closes {
+            new_lines.push(Line::from(indent.clone()));
+            new_lines.push(Line::from(format!("{}{}", base, after)));
+        } else {
+            new_lines.push(Line::from(format!("{}{}", indent, after)));
+        }
+
+        let idx = self.line_pos() - 1;
+        self.lines[idx].content = before;
+        self.folds.lines_inserted(idx + 1, new_lines.len());
+        self.lines.splice((idx + 1)..(idx + 1), new_lines);
+
+        self.move_cursor_by(0, 1, false);
+        self.move_cursor_char_to(indent.chars().count());
+        self.mark_modified();
+    }
+
+    pub fn open_line_below(&mut self) {
+        let mut indent = leading_whitespace(&self.curr_line().content).to_owned();
+        if self.indent_rules.increases_after(&self.curr_line().content) {
+            indent = self.indent_string(self.indent_width(&indent) + self.settings.shiftwidth);
+        }
+        self.insert_lines(1, true);
+        self.open_line_with(indent);
+    }
+
+    pub fn open_line_above(&mut self) {
+        let indent = leading_whitespace(&self.curr_line().content).to_owned();
+        self.insert_lines_above(1, true);
+        self.open_line_with(indent);
+    }
+
+    fn open_line_with(&mut self, indent: String) {
+        let len = indent.chars().count();
+        self.curr_line_mut().content = indent;
+        self.move_cursor_char_to(len);
+        self.mark_modified();
+    }
+
+    // dedents the current line if text is a closer typed as its first non-blank char
+    pub fn dedent_for_closer(&mut self, text: &str) {
+        let mut chars = text.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return;
+        };
+        if !self.indent_rules.decreases_on(c) {
+            return;
+        }
+
+        let split = self.curr_line().offset_of(self.char_pos());
+        let before = &self.curr_line().content[..split];
+        if before.is_empty() || !before.trim_start_matches([' ', '\t']).is_empty() {
+            return;
+        }
+
+        self.shift_line(self.line_pos() - 1, -1);
+    }
+
+    // shifts a line by amount shiftwidths, keeping the cursor on the same text
+    pub fn shift_line(&mut self, idx: usize, amount: isize) {
+        let content = &self.lines[idx].content;
+        if content.is_empty() {
+            return;
+        }
+
+        let old_len = leading_whitespace(content).chars().count();
+        let shift = amount.saturating_mul(self.settings.shiftwidth as isize);
+        let width = (self.indent_width(content) as isize).saturating_add(shift);
+        if width > MAX_INDENT as isize {
+            return;
+        }
+        let indent = self.indent_string(width.max(0) as usize);
+        let new_len = indent.chars().count();
+
+        let rest = content[leading_whitespace(content).len()..].to_owned();
+        self.lines[idx].content = indent + &rest;
+        self.mark_modified();
+
+        if idx == self.line_pos() - 1 {
+            let char_pos = self.char_pos().max(old_len) + new_len - old_len;
+            self.move_cursor_char_to(char_pos);
+        }
+    }
+
+    // inserts a tab, or spaces up to the next tab stop with expandtab
+    pub fn insert_tab(&mut self) {
+        if self.settings.expandtab {
+            let col = self.cursor_display_col();
+            let tabstop = self.settings.tabstop;
+            self.insert_text(&" ".repeat(tabstop - col % tabstop), true);
+        } else {
+            self.insert_text("\t", true);
+        }
+    }
+
+    // display column of the cursor with tabs expanded
+    pub fn cursor_display_col(&self) -> usize {
+        let line = self.curr_line();
+        display_width(&line.content[..line.offset_of(self.char_pos())], self.settings.tabstop)
+    }
+
+    // widths of text as drawn, with the font's advances when it is proportional
+    pub fn metrics(&self) -> Metrics {
+        Metrics::new(self.settings.tabstop, self.advances.clone())
+    }
+
+    // x of the cursor in columns from the start of its line, ignoring wrap
+    pub fn cursor_x(&self) -> f32 {
+        let content = &self.curr_line().content;
+        layout::x_in_segment(content, &self.metrics(), &layout::whole_line(content), self.char_pos())
+    }
+
+    // columns of the cluster under the cursor, one past the end of the line
+    pub fn cursor_width(&self) -> f32 {
+        let line = self.curr_line();
+        let (start, end) = (self.char_pos(), line.cluster_offset(self.char_pos(), 1));
+        if start >= end {
+            return 1.;
+        }
+        let cluster = &line.content[line.offset_of(start)..line.offset_of(end)];
+        self.metrics().width(cluster, self.cursor_x())
+    }
+}
+
+// comments
+impl Buffer {
+    // toggles comments on 1 indexed lines first to last inclusive
+    pub fn toggle_comment(&mut self, first: usize, last: usize) {
+        let last = last.min(self.line_count());
+        if first == 0 || first > last {
+            return;
+        }
+        if comment::toggle_lines(&mut self.lines[first - 1..last], &self.comment_tokens) {
+            self.mark_modified();
+            self.move_cursor_to(self.cursor);
+        }
+    }
+}
+
+// folding
+impl Buffer {
+    // rebuilds folds computed from the text when it changed since they were built
+    pub fn update_folds(&mut self) {
+        let method = self.settings.fold_method;
+        if method == FoldMethod::Manual || self.folds.revision == Some(self.revision) {
+            return;
+        }
+        let folds = match method {
+            FoldMethod::Indent => fold::indent_folds(&self.lines, self.settings.tabstop, self.settings.shiftwidth),
+            FoldMethod::Marker => fold::marker_folds(&self.lines),
+            FoldMethod::Syntax => {
+                self.update_syntax_mask();
+                fold::syntax_folds(&self.lines, &self.settings.match_pairs, self.is_code())
+            }
+            FoldMethod::Manual => unreachable!(),
+        };
+        self.folds.replace(folds);
+        self.folds.revision = Some(self.revision);
+    }
+
+    /*
+    * zf, folds 1 indexed lines first to last. with marker folding the markers are added to the
+    * text, folds of the other automatic methods can't be created by hand
+    */
+    pub fn create_fold(&mut self, first: usize, last: usize) {
+        let last = last.min(self.line_count());
+        if first == 0 || first >= last {
+            return;
+        }
+        if self.is_large() {
+            return;
+        }
+        match self.settings.fold_method {
+            FoldMethod::Manual => self.folds.add(first - 1, last - 1, true),
+            FoldMethod::Marker => {
+                let marker = |m: &str| match &self.comment_tokens.line {
+                    Some(token) => format!(" {} {}", token, m),
+                    None => format!(" {}", m),
+                };
+                let (open, close) = (marker(fold::OPEN_MARKER), marker(fold::CLOSE_MARKER));
+                self.lines[first - 1].content.push_str(&open);
+                self.lines[last - 1].content.push_str(&close);
+                self.mark_modified();
+                self.update_folds();
+                self.folds.close(first - 1);
+            }
+            _ => return,
+        }
+        self.move_cursor_line_to(first);
+        self.move_to_want();
+    }
+
+    pub fn open_fold(&mut self) {
+        self.update_folds();
+        self.folds.open(self.line_pos() - 1);
+    }
+
+    pub fn close_fold(&mut self) {
+        self.update_folds();
+        if self.folds.close(self.line_pos() - 1) {
+            self.move_cursor_line_to(self.line_pos());
+            self.move_to_want();
+        }
+    }
+
+    pub fn toggle_fold(&mut self) {
+        self.update_folds();
+        self.folds.toggle(self.line_pos() - 1);
+        self.move_cursor_line_to(self.line_pos());
+        self.move_to_want();
+    }
+
+    // zR and zM
+    pub fn set_all_folds(&mut self, closed: bool) {
+        self.update_folds();
+        self.folds.set_all(closed);
+        self.move_cursor_line_to(self.line_pos());
+        self.move_to_want();
+    }
+
+    // closed folds as 1 indexed first and last lines, excluding folds inside other closed folds
+    pub fn closed_folds(&self) -> Vec<(usize, usize)> {
+        self.folds
+            .hidden_ranges()
+            .into_iter()
+            .map(|(start, end)| (start + 1, end + 1))
+            .collect()
+    }
+
+    // first and last line shown on the same screen row as a 1 indexed line
+    fn visible_span(&self, line_pos: usize) -> (usize, usize) {
+        match self.folds.closed_at(line_pos - 1) {
+            Some(fold) => (fold.start + 1, fold.end + 1),
+            None => (line_pos, line_pos),
+        }
+    }
+}
+
+// large file mode
+impl Buffer {
+    pub fn is_large(&self) -> bool {
+        self.large_file.is_some()
+    }
+
+    // loads lines of a large file until there are at least count, as far as indexed
+    pub fn load_lines_to(&mut self, count: usize) {
+        let Some(large_file) = self.large_file.as_mut() else {
+            return;
+        };
+        while self.lines.len() < count {
+            let placeholder = large_file.handed_out() == 0;
+            let lines = large_file.read_lines((count - self.lines.len()).min(LOAD_CHUNK));
+            if lines.is_empty() {
+                break;
+            }
+            // the empty line shown before anything was read
+            if placeholder {
+                self.lines.clear();
+            }
+            self.lines.extend(lines);
+        }
+    }
+
+    /*
+    * loads up to count lines of a large file before the first one the buffer has. they go in
+    * front of the lines, so what is on them moves down with them
+    */
+    pub fn load_lines_before(&mut self, count: usize) {
+        let Some(large_file) = self.large_file.as_mut().filter(|large_file| large_file.handed_out() > 0) else {
+            return;
+        };
+        let lines = large_file.read_before(count.min(LOAD_CHUNK));
+        let n = lines.len();
+        if n == 0 {
+            return;
+        }
+        self.lines.splice(0..0, lines);
+        self.folds.lines_inserted(0, n);
+        for cursor in [&mut self.cursor, &mut self.range_start, &mut self.range_end] {
+            cursor.line_pos += n;
+        }
+        self.rows_shifted += n;
+    }
+
+    // rows the text moved down since the last call, for whoever draws it to follow
+    pub fn take_rows_shifted(&mut self) -> usize {
+        mem::take(&mut self.rows_shifted)
+    }
+
+    /*
+    * the 1 indexed line a G or gg jump lands on, line counts from the start of the file and is
+    * None for the first or last one. a large buffer without changes swaps its lines for the ones
+    * around the target, found with the line index. one with changes keeps its lines and loads
+    * the ones between them and the target
+    */
+    pub fn jump_target(&mut self, line: Option<usize>, last: bool) -> usize {
+        let Some(large_file) = self.large_file.as_ref() else {
+            return line.unwrap_or(if last { self.line_count() } else { 1 });
+        };
+        if last && line.is_none() {
+            large_file.wait();
+        }
+        let before = large_file.first_line();
+        let total = before + self.lines.len() + large_file.lines_after();
+        let target = line.unwrap_or(if last { total } else { 1 }).clamp(1, total);
+
+        let window = before + 1..=before + self.lines.len();
+        match target {
+            _ if window.contains(&target) => (),
+            // a selection keeps its start, so the lines it starts on stay
+            _ if !self.is_modified && !matches!(self.editor_mode, Mode::Visual | Mode::VisualLine) => {
+                let start = (target - 1).saturating_sub(LOAD_AHEAD);
+                if let Some(large_file) = self.large_file.as_mut() {
+                    large_file.restart_at(start);
+                }
+                self.lines = vec![Line::new()];
+                self.folds = Folds::new();
+                self.load_lines_to(target - start + LOAD_AHEAD);
+            }
+            _ if target > *window.end() => self.load_lines_to(target - before),
+            _ => {
+                while self.large_file.as_ref().is_some_and(|large_file| large_file.first_line() >= target) {
+                    let count = self.lines.len();
+                    self.load_lines_before(LOAD_CHUNK);
+                    if self.lines.len() == count {
+                        break;
+                    }
+                }
+                self.rows_shifted = 0;
+            }
+        }
+        let before = self.large_file.as_ref().map_or(0, |large_file| large_file.first_line());
+        target.saturating_sub(before).clamp(1, self.line_count())
+    }
+
+    // indexing progress and lines found so far, None once indexing is done
+    pub fn load_progress(&self) -> Option<(f32, usize)> {
+        let large_file = self.large_file.as_ref().filter(|large_file| !large_file.is_indexed())?;
+        Some((large_file.progress(), large_file.indexed_lines()))
+    }
+}
+
+// display lines
+impl Buffer {
+    // rows on screen for the whole buffer, with closed folds as one row
+    pub fn display_rows(&self, wrap: Option<&Wrap>) -> Rc<[DisplayRow]> {
+        self.row_cache(wrap).rows.clone()
+    }
+
+    // the rows laid out for wrap, from the cache unless something they depend on changed
+    fn row_cache(&self, wrap: Option<&Wrap>) -> Ref<'_, RowCache> {
+        let first_line = self.large_file.as_ref().map_or(0, |large_file| large_file.first_line());
+        let hidden = self.folds.hidden_ranges();
+        let fresh = self.row_cache.borrow().as_ref().is_some_and(|cache| {
+            cache.revision == self.revision
+                && cache.lines == self.lines.len()
+                && cache.first_line == first_line
+                && cache.hidden == hidden
+                && cache.wrap.as_ref() == wrap
+                && cache.tabstop == self.settings.tabstop
+                && match (&cache.advances, &self.advances) {
+                    (Some(cached), Some(advances)) => Arc::ptr_eq(cached, advances),
+                    (cached, advances) => cached.is_none() && advances.is_none(),
+                }
+        });
+        if !fresh {
+            let (rows, line_rows) = self.lay_out_rows(wrap);
+            *self.row_cache.borrow_mut() = Some(RowCache {
+                revision: self.revision,
+                lines: self.lines.len(),
+                first_line,
+                hidden,
+                wrap: wrap.cloned(),
+                tabstop: self.settings.tabstop,
+                advances: self.advances.clone(),
+                rows: rows.into(),
+                line_rows,
+            });
+        }
+        Ref::map(self.row_cache.borrow(), |cache| cache.as_ref().expect("the cache was just filled"))
+    }
+
+    fn lay_out_rows(&self, wrap: Option<&Wrap>) -> (Vec<DisplayRow>, Vec<Option<usize>>) {
+        let metrics = self.metrics();
+        let mut rows = Vec::new();
+        let mut line_rows = vec![None; self.lines.len()];
+        let mut line = 1;
+        while line <= self.lines.len() {
+            line_rows[line - 1] = Some(rows.len());
+            if let Some(fold) = self.folds.closed_at(line - 1) {
+                rows.push(DisplayRow {
+                    line,
+                    segment: Segment {
+                        start: 0,
+                        end: 0,
+                        indent: 0,
+                    },
+                    fold_end: Some(fold.end + 1),
+                });
+                line = fold.end + 2;
+                continue;
+            }
+            let content = &self.lines[line - 1].content;
+            for segment in layout::segments(content, &metrics, wrap) {
+                rows.push(DisplayRow {
+                    line,
+                    segment,
+                    fold_end: None,
+                });
+            }
+            line += 1;
+        }
+        (rows, line_rows)
+    }
+
+    // 0 indexed screen row and x in columns of a position, None if it is hidden in a closed fold
+    pub fn screen_pos(&self, wrap: Option<&Wrap>, line_pos: usize, char_pos: usize) -> Option<(usize, f32)> {
+        let content = &self.lines[line_pos - 1].content;
+        // every line is one row, no need to lay out the buffer
+        if wrap.is_none() && self.folds.hidden_ranges().is_empty() {
+            let x = layout::x_in_segment(content, &self.metrics(), &layout::whole_line(content), char_pos);
+            return Some((line_pos - 1, x));
+        }
+        let cache = self.row_cache(wrap);
+        // lines hidden in a closed fold have no row
+        let row = cache.line_rows[line_pos - 1]?;
+        let rows = &cache.rows;
+        if rows[row].fold_end.is_some() {
+            return Some((row, 0.));
+        }
+        let segments: Vec<Segment> = rows[row..]
+            .iter()
+            .take_while(|row| row.line == line_pos)
+            .map(|row| row.segment)
+            .collect();
+        let idx = layout::segment_of(&segments, char_pos);
+        let x = layout::x_in_segment(content, &self.metrics(), &segments[idx], char_pos);
+        Some((row + idx, x))
+    }
+
+    // gj and gk, moves by screen rows keeping the screen x
+    pub fn move_cursor_display_by(&mut self, wrap: Option<&Wrap>, offset: isize) {
+        self.update_folds();
+        let Some((row, x)) = self.screen_pos(wrap, self.line_pos(), self.char_pos()) else {
+            return;
+        };
+        self.move_cursor_to_screen(wrap, (row as isize + offset).max(0) as usize, x);
+    }
+
+    // moves to the char drawn over x in columns on a 0 indexed screen row, rows past the end are the last
+    pub fn move_cursor_to_screen(&mut self, wrap: Option<&Wrap>, row: usize, x: f32) {
+        self.update_folds();
+        let rows = self.display_rows(wrap);
+        let target = &rows[row.min(rows.len() - 1)];
+        if target.fold_end.is_some() {
+            self.move_cursor_line_to(target.line);
+            self.move_to_want();
+            return;
+        }
+
+        let content = &self.lines[target.line - 1].content;
+        let mut char_pos = layout::char_at_x(content, &self.metrics(), &target.segment, x);
+        // the cursor sits on a char unless it is at the end of the line in insert mode
+        let last_row = target.segment.end == self.lines[target.line - 1].len();
+        if char_pos == target.segment.end && target.segment.end > target.segment.start
+            && (!last_row || self.editor_mode != Mode::Insert)
+        {
+            char_pos -= 1;
+        }
+        self.cursor.line_pos = target.line;
+        self.move_cursor_char_to(char_pos);
+    }
+
+    // g0 and g$, moves to the first or last char of the screen row
+    pub fn move_cursor_display_edge(&mut self, wrap: Option<&Wrap>, end: bool) {
+        let content = &self.curr_line().content;
+        let segments = layout::segments(content, &self.metrics(), wrap);
+        let segment = segments[layout::segment_of(&segments, self.char_pos())];
+        let char_pos = if end {
+            let last_row = segment.end == self.curr_line().len();
+            if last_row && self.editor_mode == Mode::Insert {
+                segment.end
+            } else {
+                segment.end.max(segment.start + 1) - 1
+            }
+        } else {
+            segment.start
+        };
+        self.move_cursor_char_to(char_pos);
+    }
+}
+
+// visual selection
+impl Buffer {
+    pub fn start_selection(&mut self) {
+        self.range_start = self.cursor;
+    }
+
+    // start and end of the selection in text order, both inclusive
+    pub fn selection(&self) -> (Cursor, Cursor) {
+        let (a, b) = (self.range_start, self.cursor);
+        if (a.line_pos, a.char_pos) <= (b.line_pos, b.char_pos) {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    // first and last line the selection touches
+    pub fn selected_lines(&self) -> (usize, usize) {
+        let (start, end) = self.selection();
+        (start.line_pos, end.line_pos)
+    }
+
+    pub fn delete_selection(&mut self, linewise: bool) {
+        let (start, end) = self.selection();
+        if linewise {
+            self.delete_lines(start.line_pos..end.line_pos + 1);
+            self.move_cursor_to(Cursor {
+                line_pos: start.line_pos,
+                char_pos: 0,
+            });
+        } else {
+            let end = Cursor {
+                line_pos: end.line_pos,
+                char_pos: self.get_line(end.line_pos - 1).cluster_offset(end.char_pos, 1),
+            };
+            self.replace_range(start, end, "");
+            self.move_cursor_to(start);
         }
     }
 }
@@ -343,28 +1585,46 @@ impl Buffer {
 // cursor movement
 impl Buffer {
     pub fn move_cursor_by(&mut self, offset_char: isize, offset_line: isize, sync: bool) {
-        self.cursor.line_pos = (self.cursor.line_pos as isize + offset_line)
-            .clamp(1, self.lines.len() as isize) as usize;
+        self.update_folds();
+        // a closed fold counts as a single line
+        let mut line = self.cursor.line_pos;
+        for _ in 0..offset_line.unsigned_abs() {
+            let (first, last) = self.visible_span(line);
+            if offset_line > 0 && last < self.lines.len() {
+                line = last + 1;
+            } else if offset_line < 0 && first > 1 {
+                line = first - 1;
+            } else {
+                break;
+            }
+        }
+        self.cursor.line_pos = self.visible_span(line.clamp(1, self.lines.len())).0;
 
+        // moving sideways steps over whole grapheme clusters
         if offset_char != 0 {
-            self.want_cursor =
-                (self.char_pos() as isize + offset_char).clamp(0, isize::MAX) as usize;
+            let char_pos = self.curr_line().cluster_offset(self.char_pos(), offset_char);
+            self.place_cursor(char_pos);
+        } else {
+            self.move_to_want();
         }
 
-        self.move_to_want();
-
         if sync {
-            self.want_cursor = self.cursor.char_pos;
+            self.want_x = self.cursor_x();
         }
     }
 
+    pub fn move_cursor_to(&mut self, dest: Cursor) {
+        self.move_cursor_line_to(dest.line_pos);
+        self.move_cursor_char_to(dest.char_pos);
+    }
+
     pub fn move_cursor_line_to(&mut self, dest_line: usize) {
-        self.cursor.line_pos = dest_line.clamp(1, self.lines.len());
+        self.cursor.line_pos = self.visible_span(dest_line.clamp(1, self.lines.len())).0;
     }
 
     pub fn move_cursor_char_to(&mut self, dest_char: usize) {
-        self.want_cursor = dest_char;
-        self.move_to_want();
+        self.place_cursor(dest_char);
+        self.want_x = self.cursor_x();
     }
 
     pub fn move_cursor_char_w(&mut self) {}
@@ -373,23 +1633,35 @@ impl Buffer {
     pub fn move_cursor_char_bigb(&mut self) {}
 
     pub fn move_cursor_to_last_line(&mut self) {
-        self.cursor.line_pos = usize::MAX.clamp(0, self.lines.len());
+        self.move_cursor_line_to(self.lines.len());
         self.move_to_want();
     }
 
+    pub fn move_cursor_to_first_non_blank(&mut self) {
+        let indent = leading_whitespace(&self.curr_line().content).chars().count();
+        self.move_cursor_char_to(indent);
+    }
+
     pub fn move_cursor_to_last_char(&mut self) {
-        self.want_cursor = isize::MAX as usize;
+        self.want_x = f32::INFINITY;
         self.move_to_want();
     }
 
+    // puts the cursor on the char drawn over want_x, the one a vertical motion lands on
     fn move_to_want(&mut self) {
-        if self.editor_mode == Mode::Insert {
-            self.cursor.char_pos = self.want_cursor.clamp(0, self.curr_line().len());
+        let content = &self.curr_line().content;
+        let char_pos = layout::char_at_x(content, &self.metrics(), &layout::whole_line(content), self.want_x);
+        self.place_cursor(char_pos);
+    }
+
+    // the cursor lands on the start of a grapheme cluster
+    fn place_cursor(&mut self, char_pos: usize) {
+        let char_pos = if self.editor_mode == Mode::Insert {
+            char_pos.clamp(0, self.curr_line().len())
         } else {
-            self.cursor.char_pos = self
-                .want_cursor
-                .clamp(0, (self.curr_line().len().max(1)) - 1);
-        }
+            char_pos.clamp(0, (self.curr_line().len().max(1)) - 1)
+        };
+        self.cursor.char_pos = self.curr_line().cluster_start(char_pos);
     }
 }
 
@@ -408,6 +1680,33 @@ impl Buffers {
         }
     }
 
+    // opens every file, an empty list gives an empty buffer
+    pub fn from_files(file_paths: &[&str], current: usize) -> Self {
+        if file_paths.is_empty() {
+            return Buffers::new();
+        }
+        Buffers {
+            buffers: file_paths.iter().map(|path| Buffer::from_file(path)).collect(),
+            current_buffer: current.min(file_paths.len() - 1),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Buffer> {
+        self.buffers.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
+        self.buffers.iter_mut()
+    }
+
+    pub fn current_index(&self) -> usize {
+        self.current_buffer
+    }
+
+    pub fn get_mut(&mut self, idx: usize) -> &mut Buffer {
+        self.buffers.get_mut(idx).expect("buffer index out of bounds")
+    }
+
     pub fn curr_buffer(&self) -> &Buffer {
         let buf_idx = self.current_buffer;
         self.buffers
@@ -424,14 +1723,34 @@ impl Buffers {
 }
 
 use std::{
+    cell::{Ref, RefCell},
     fmt,
     fs::File,
-    io::{BufRead, BufReader, IsTerminal, Read, Write},
+    io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write},
     iter, mem,
     ops::{Range, RangeBounds}, path::Path,
+    rc::Rc,
+    sync::Arc,
 };
 
-use super::Mode;
+use similar::TextDiff;
+
+use crate::configuration::{Config, CONFIG};
+
+use super::{
+    brackets::{find_enclosing, find_match, parse_pairs},
+    comment::{self, CommentTokens},
+    editorconfig,
+    fold::{self, FoldMethod, Folds},
+    largefile::{LargeFile, LOAD_AHEAD, LOAD_CHUNK},
+    layout::{self, Advances, DisplayRow, Metrics, Segment, Wrap},
+    indent::{closer_of, display_width, leading_whitespace, IndentRules, MAX_INDENT},
+    syntax::{syntax_for_file, SyntaxMask},
+    textobject::{self, TextRange},
+    swap::{SwapData, SwapOwner},
+    watch::DiskStamp,
+    Mode,
+};
 
 impl fmt::Display for Buffer {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -0,0 +1,57 @@
+use super::keymap;
+
+// commands typed on the : line
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Command {
+    // write a session file, session.fim unless given
+    MkSession(Option<String>),
+    // write the current buffer, or all modified buffers
+    Write { all: bool },
+    // quit, refused while a buffer is modified unless forced
+    Quit { force: bool },
+    // write all modified buffers, or just the current one, then quit
+    WriteQuit { all: bool },
+    // map, nnoremap, ... without rhs lists the mappings
+    Map { command: String, lhs: Option<String>, rhs: Option<String> },
+    Unmap { command: String, lhs: String },
+    // run lua code, or a lua file
+    Lua(String),
+    LuaFile(String),
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let line = line.trim();
+    let (name, arg) = match line.split_once(char::is_whitespace) {
+        Some((name, arg)) => (name, Some(arg.trim().to_string()).filter(|arg| !arg.is_empty())),
+        None => (line, None),
+    };
+    match name {
+        "mks" | "mksession" => Ok(Command::MkSession(arg)),
+        "w" | "write" => Ok(Command::Write { all: false }),
+        "wa" | "wall" => Ok(Command::Write { all: true }),
+        // there is one window, so quitting it and quitting all are the same
+        "q" | "quit" | "qa" | "qall" => Ok(Command::Quit { force: false }),
+        "q!" | "quit!" | "qa!" | "qall!" => Ok(Command::Quit { force: true }),
+        "wq" => Ok(Command::WriteQuit { all: false }),
+        "wqa" | "wqall" | "xa" | "xall" => Ok(Command::WriteQuit { all: true }),
+        _ if keymap::map_command(name).is_some() => {
+            // the rhs is everything after the keys, spaces included
+            let (lhs, rhs) = match arg.as_deref().and_then(|arg| arg.split_once(char::is_whitespace)) {
+                Some((lhs, rhs)) => (Some(lhs.to_string()), Some(rhs.trim().to_string())),
+                None => (arg, None),
+            };
+            Ok(Command::Map {
+                command: name.to_string(),
+                lhs,
+                rhs,
+            })
+        }
+        _ if keymap::unmap_command(name).is_some() => Ok(Command::Unmap {
+            command: name.to_string(),
+            lhs: arg.ok_or("missing keys to unmap")?,
+        }),
+        "lua" => Ok(Command::Lua(arg.ok_or("missing lua code")?)),
+        "luafile" => Ok(Command::LuaFile(arg.ok_or("missing file name")?)),
+        _ => Err(format!("not an editor command: {}", line)),
+    }
+}
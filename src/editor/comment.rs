@@ -0,0 +1,129 @@
+use std::path::Path;
+
+use super::{
+    buffer::Line,
+    indent::leading_whitespace,
+    syntax::SYNTAX_SET,
+};
+
+// comment markers for a filetype
+#[derive(Debug, Clone)]
+pub struct CommentTokens {
+    pub line: Option<String>,
+    pub block: Option<(String, String)>,
+}
+
+impl CommentTokens {
+    /*
+    * uses the comment metadata shipped with syntect's syntax definitions, falling back to a
+    * table keyed by file extension
+    */
+    pub fn for_file(file_path: &str, syntax: Option<&str>) -> Self {
+        if let Some(syntax) = syntax.and_then(|name| SYNTAX_SET.find_syntax_by_name(name)) {
+            let metadata = SYNTAX_SET.metadata().metadata_for_scope(&[syntax.scope]);
+            let tokens = CommentTokens {
+                line: metadata.line_comment().map(|s| s.trim_end().to_owned()),
+                block: metadata
+                    .block_comment()
+                    .map(|(open, close)| (open.trim_end().to_owned(), close.trim_start().to_owned())),
+            };
+            if tokens.line.is_some() || tokens.block.is_some() {
+                return tokens;
+            }
+        }
+
+        let ext = Path::new(file_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
+        let (line, block) = match ext {
+            "c" | "h" | "cpp" | "hpp" | "cc" | "rs" | "go" | "java" | "js" | "ts" | "cs"
+            | "swift" | "kt" | "zig" | "glsl" | "vert" | "frag" => (Some("//"), Some(("/*", "*/"))),
+            "py" | "sh" | "bash" | "zsh" | "rb" | "pl" | "toml" | "yaml" | "yml" | "conf"
+            | "nim" | "r" => (Some("#"), None),
+            "lua" | "sql" | "hs" => (Some("--"), None),
+            "lisp" | "el" | "clj" | "scm" | "asm" | "ini" => (Some(";"), None),
+            "vim" => (Some("\""), None),
+            "tex" | "erl" => (Some("%"), None),
+            "css" => (None, Some(("/*", "*/"))),
+            "html" | "xml" | "md" | "svg" => (None, Some(("<!--", "-->"))),
+            _ => (Some("#"), None),
+        };
+        CommentTokens {
+            line: line.map(String::from),
+            block: block.map(|(open, close)| (open.to_owned(), close.to_owned())),
+        }
+    }
+
+    fn is_commented(&self, text: &str) -> bool {
+        let text = text.trim();
+        match (&self.line, &self.block) {
+            (Some(line), _) => text.starts_with(line.as_str()),
+            (None, Some((open, close))) => text.starts_with(open.as_str()) && text.ends_with(close.as_str()),
+            (None, None) => false,
+        }
+    }
+}
+
+/*
+* comments out lines, or uncomments them if every non-blank line is already commented. markers
+* go at the smallest indentation of the lines so the comments line up. returns whether anything
+* changed
+*/
+pub fn toggle_lines(lines: &mut [Line], tokens: &CommentTokens) -> bool {
+    if tokens.line.is_none() && tokens.block.is_none() {
+        return false;
+    }
+
+    let non_blank = || lines.iter().filter(|line| !line.content.trim().is_empty());
+    if non_blank().count() == 0 {
+        return false;
+    }
+
+    if non_blank().all(|line| tokens.is_commented(&line.content)) {
+        for line in lines.iter_mut() {
+            uncomment(line, tokens);
+        }
+        return true;
+    }
+
+    // smallest indentation in bytes, lines share the same leading whitespace up to it
+    let column = non_blank()
+        .map(|line| leading_whitespace(&line.content).len())
+        .min()
+        .unwrap_or(0);
+
+    for line in lines.iter_mut().filter(|line| !line.content.trim().is_empty()) {
+        let (indent, rest) = line.content.split_at(column);
+        line.content = match (&tokens.line, &tokens.block) {
+            (Some(marker), _) => format!("{}{} {}", indent, marker, rest),
+            (None, Some((open, close))) => format!("{}{} {} {}", indent, open, rest, close),
+            (None, None) => unreachable!(),
+        };
+    }
+    true
+}
+
+fn uncomment(line: &mut Line, tokens: &CommentTokens) {
+    let indent = leading_whitespace(&line.content).to_owned();
+    let text = &line.content[indent.len()..];
+
+    let stripped = match (&tokens.line, &tokens.block) {
+        (Some(marker), _) => text.strip_prefix(marker.as_str()).map(|rest| {
+            rest.strip_prefix(' ').unwrap_or(rest).to_owned()
+        }),
+        (None, Some((open, close))) => text
+            .trim_end()
+            .strip_prefix(open.as_str())
+            .and_then(|rest| rest.strip_suffix(close.as_str()))
+            .map(|rest| {
+                let rest = rest.strip_prefix(' ').unwrap_or(rest);
+                rest.strip_suffix(' ').unwrap_or(rest).to_owned()
+            }),
+        (None, None) => None,
+    };
+
+    if let Some(rest) = stripped {
+        line.content = indent + &rest;
+    }
+}
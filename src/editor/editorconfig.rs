@@ -0,0 +1,410 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::buffer::{BufferSettings, Charset, LineEnding};
+
+// a parsed .editorconfig file
+struct EditorConfigFile {
+    dir: PathBuf,
+    root: bool,
+    sections: Vec<Section>,
+}
+
+struct Section {
+    glob: String,
+    properties: Vec<(String, String)>,
+}
+
+/*
+* finds the .editorconfig files governing file_path and applies their properties to settings
+*/
+pub fn apply(file_path: &str, settings: &mut BufferSettings) {
+    let Ok(path) = fs::canonicalize(file_path).or_else(|_| std::path::absolute(file_path)) else {
+        return;
+    };
+
+    // nearest file last so that it overrides the ones above it
+    let mut properties: HashMap<String, String> = HashMap::new();
+    for file in discover(&path).iter().rev() {
+        for section in &file.sections {
+            if !file.matches(&section.glob, &path) {
+                continue;
+            }
+            for (key, value) in &section.properties {
+                properties.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    apply_properties(&properties, settings);
+}
+
+// collects .editorconfig files from the file's directory upward, stopping at root = true
+fn discover(path: &Path) -> Vec<EditorConfigFile> {
+    let mut files = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        let Ok(contents) = fs::read_to_string(dir.join(".editorconfig")) else {
+            continue;
+        };
+        let file = parse(dir, &contents);
+        let root = file.root;
+        files.push(file);
+        if root {
+            break;
+        }
+    }
+    files
+}
+
+fn parse(dir: &Path, contents: &str) -> EditorConfigFile {
+    let mut file = EditorConfigFile {
+        dir: dir.to_path_buf(),
+        root: false,
+        sections: Vec::new(),
+    };
+
+    for line in contents.lines().map(|line| line.trim()) {
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+
+        if line.starts_with('[') && line.ends_with(']') {
+            file.sections.push(Section {
+                glob: line[1..line.len() - 1].to_owned(),
+                properties: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let value = value.trim().to_owned();
+
+        match file.sections.last_mut() {
+            Some(section) => section.properties.push((key, value)),
+            None => {
+                if key == "root" {
+                    file.root = value.eq_ignore_ascii_case("true");
+                }
+            }
+        }
+    }
+
+    file
+}
+
+impl EditorConfigFile {
+    fn matches(&self, glob: &str, path: &Path) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.dir) else {
+            return false;
+        };
+        let relative: Vec<char> = relative.to_string_lossy().replace('\\', "/").chars().collect();
+        let name: Vec<char> = path
+            .file_name()
+            .map(|name| name.to_string_lossy().chars().collect())
+            .unwrap_or_default();
+
+        expand_braces(glob).iter().any(|alt| {
+            // globs without a slash match the file name at any depth
+            if alt.contains('/') {
+                let pattern: Vec<char> = alt.trim_start_matches('/').chars().collect();
+                glob_match(&pattern, &relative)
+            } else {
+                let pattern: Vec<char> = alt.chars().collect();
+                glob_match(&pattern, &name)
+            }
+        })
+    }
+}
+
+fn apply_properties(properties: &HashMap<String, String>, settings: &mut BufferSettings) {
+    let get = |key: &str| {
+        properties
+            .get(key)
+            .map(|value| value.to_lowercase())
+            .filter(|value| value != "unset")
+    };
+
+    match get("indent_style").as_deref() {
+        Some("tab") => settings.expandtab = false,
+        Some("space") => settings.expandtab = true,
+        _ => (),
+    }
+
+    let tab_width = get("tab_width").and_then(|value| value.parse::<usize>().ok());
+    let indent_size = get("indent_size");
+    if let Some(width) = tab_width.filter(|width| *width > 0) {
+        settings.tabstop = width;
+    }
+    match indent_size.as_deref() {
+        Some("tab") => settings.shiftwidth = settings.tabstop,
+        Some(size) => {
+            if let Some(size) = size.parse::<usize>().ok().filter(|size| *size > 0) {
+                settings.shiftwidth = size;
+                // tab_width defaults to indent_size when not given
+                if tab_width.is_none() {
+                    settings.tabstop = size;
+                }
+            }
+        }
+        None => (),
+    }
+
+    match get("end_of_line").as_deref() {
+        Some("lf") => settings.line_ending = LineEnding::Lf,
+        Some("crlf") => settings.line_ending = LineEnding::CrLf,
+        Some("cr") => settings.line_ending = LineEnding::Cr,
+        _ => (),
+    }
+
+    match get("charset").as_deref() {
+        Some("utf-8") => settings.charset = Charset::Utf8,
+        Some("utf-8-bom") => settings.charset = Charset::Utf8Bom,
+        Some("latin1") => settings.charset = Charset::Latin1,
+        Some("utf-16be") => settings.charset = Charset::Utf16Be,
+        Some("utf-16le") => settings.charset = Charset::Utf16Le,
+        _ => (),
+    }
+
+    if let Some(value) = get("trim_trailing_whitespace") {
+        settings.trim_trailing_whitespace = value == "true";
+    }
+    if let Some(value) = get("insert_final_newline") {
+        settings.insert_final_newline = value == "true";
+    }
+
+    match get("max_line_length").as_deref() {
+        Some("off") => settings.max_line_length = None,
+        Some(length) => {
+            if let Ok(length) = length.parse::<usize>() {
+                settings.max_line_length = Some(length);
+            }
+        }
+        None => (),
+    }
+}
+
+// expands {a,b} alternatives and {n1..n2} ranges into plain globs
+fn expand_braces(glob: &str) -> Vec<String> {
+    let chars: Vec<char> = glob.chars().collect();
+
+    let mut open = None;
+    let mut depth = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => {
+                if depth == 0 {
+                    open = Some(i);
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0 {
+                    let start = open.unwrap();
+                    let prefix: String = chars[..start].iter().collect();
+                    let inner: String = chars[start + 1..i].iter().collect();
+                    let suffix: String = chars[i + 1..].iter().collect();
+
+                    let Some(alternatives) = brace_alternatives(&inner) else {
+                        // not an expansion, keep the braces literally
+                        return expand_braces(&suffix)
+                            .into_iter()
+                            .map(|rest| format!("{}\\{{{}\\}}{}", prefix, inner, rest))
+                            .collect();
+                    };
+
+                    let mut result = Vec::new();
+                    for alt in alternatives {
+                        result.extend(expand_braces(&format!("{}{}{}", prefix, alt, suffix)));
+                    }
+                    return result;
+                }
+            }
+            _ => (),
+        }
+        i += 1;
+    }
+
+    Vec::from([glob.to_owned()])
+}
+
+fn brace_alternatives(inner: &str) -> Option<Vec<String>> {
+    if let Some((lo, hi)) = inner.split_once("..") {
+        let (lo, hi) = (lo.parse::<i64>().ok()?, hi.parse::<i64>().ok()?);
+        let (lo, hi) = (lo.min(hi), lo.max(hi));
+        // guard against absurd ranges
+        if hi - lo > 10000 {
+            return None;
+        }
+        return Some((lo..=hi).map(|n| n.to_string()).collect());
+    }
+
+    let mut alternatives = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut escaped = false;
+    for c in inner.chars() {
+        if escaped {
+            current.push(c);
+            escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => {
+                current.push(c);
+                escaped = true;
+            }
+            '{' => {
+                depth += 1;
+                current.push(c);
+            }
+            '}' => {
+                depth -= 1;
+                current.push(c);
+            }
+            ',' if depth == 0 => alternatives.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    alternatives.push(current);
+
+    if alternatives.len() > 1 {
+        Some(alternatives)
+    } else {
+        None
+    }
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let Some(&p) = pattern.first() else {
+        return text.is_empty();
+    };
+
+    match p {
+        '*' => {
+            if pattern.get(1) == Some(&'*') {
+                // ** crosses directory separators
+                let rest = &pattern[2..];
+                (0..=text.len()).any(|i| glob_match(rest, &text[i..]))
+            } else {
+                let rest = &pattern[1..];
+                for i in 0..=text.len() {
+                    if glob_match(rest, &text[i..]) {
+                        return true;
+                    }
+                    if text.get(i) == Some(&'/') {
+                        break;
+                    }
+                }
+                false
+            }
+        }
+        '?' => text.first().is_some_and(|c| *c != '/') && glob_match(&pattern[1..], &text[1..]),
+        '[' => match match_class(&pattern[1..], text.first().copied()) {
+            Some((matched, len)) => matched && glob_match(&pattern[1 + len..], &text[1..]),
+            // no closing bracket, treat as a literal
+            None => text.first() == Some(&'[') && glob_match(&pattern[1..], &text[1..]),
+        },
+        '\\' if pattern.len() > 1 => {
+            text.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &text[1..])
+        }
+        _ => text.first() == Some(&p) && glob_match(&pattern[1..], &text[1..]),
+    }
+}
+
+// matches c against a [...] class, returning the result and the class length including ']'
+fn match_class(class: &[char], c: Option<char>) -> Option<(bool, usize)> {
+    let close = class.iter().skip(1).position(|c| *c == ']')? + 1;
+    let Some(c) = c.filter(|c| *c != '/') else {
+        return Some((false, close + 1));
+    };
+
+    let (negate, items) = match class[0] {
+        '!' | '^' => (true, &class[1..close]),
+        _ => (false, &class[..close]),
+    };
+
+    let mut found = false;
+    let mut i = 0;
+    while i < items.len() {
+        if i + 2 < items.len() && items[i + 1] == '-' {
+            found |= items[i] <= c && c <= items[i + 2];
+            i += 3;
+        } else {
+            found |= items[i] == c;
+            i += 1;
+        }
+    }
+
+    Some((found != negate, close + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{configuration::Config, editor::testdir::TestDir};
+
+    // whether a section glob of a .editorconfig in /project picks the file at path
+    fn picks(glob: &str, path: &str) -> bool {
+        let file = EditorConfigFile {
+            dir: PathBuf::from("/project"),
+            root: true,
+            sections: Vec::new(),
+        };
+        file.matches(glob, Path::new(path))
+    }
+
+    #[test]
+    fn globs() {
+        assert!(picks("*.rs", "/project/src/main.rs"));
+        assert!(!picks("*.rs", "/project/src/main.rsx"));
+        assert!(picks("?.md", "/project/a.md"));
+        assert!(!picks("?.md", "/project/ab.md"));
+        assert!(picks("[!a]*", "/project/b.txt"));
+        assert!(!picks("[!a]*", "/project/a.txt"));
+        assert!(picks("*.{js,ts}", "/project/index.ts"));
+        assert!(!picks("*.{js,ts}", "/project/index.rs"));
+        assert!(picks("file{1..3}", "/project/file2"));
+        assert!(!picks("file{1..3}", "/project/file4"));
+    }
+
+    #[test]
+    fn globs_with_a_slash() {
+        // a bare name matches at any depth, one with a slash from the .editorconfig's directory
+        assert!(picks("main.rs", "/project/src/main.rs"));
+        assert!(!picks("/main.rs", "/project/src/main.rs"));
+        assert!(picks("src/*.rs", "/project/src/main.rs"));
+        assert!(!picks("src/*.rs", "/project/src/bin/main.rs"));
+        assert!(picks("src/**.rs", "/project/src/bin/main.rs"));
+        assert!(picks("**/main.rs", "/project/src/bin/main.rs"));
+    }
+
+    #[test]
+    fn nearest_file_wins() {
+        let dir = TestDir::new("editorconfig");
+        // not read, the one below it is the root
+        dir.file(".editorconfig", "[*]\nindent_style = tab\n");
+        dir.file("project/.editorconfig", "root = true\n[*]\nindent_size = 2\ntab_width = 8\n");
+        dir.file("project/sub/.editorconfig", "[*.rs]\nindent_size = 4\n[*.md]\nindent_size = unset\n");
+
+        let settings = |name: &str| {
+            let path = dir.file(&format!("project/sub/{}", name), "");
+            let mut settings = BufferSettings::of(&Config::default());
+            settings.shiftwidth = 3;
+            apply(path.to_str().unwrap(), &mut settings);
+            (settings.shiftwidth, settings.tabstop, settings.expandtab)
+        };
+        assert_eq!(settings("a.rs"), (4, 8, true));
+        assert_eq!(settings("b.txt"), (2, 8, true));
+        // unset takes the property back to what it was before any .editorconfig
+        assert_eq!(settings("c.md"), (3, 8, true));
+    }
+}
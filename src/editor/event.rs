@@ -0,0 +1,28 @@
+use super::Mode;
+
+// things that happen in the editor that scripts and plugins can react to
+#[derive(Debug, Clone)]
+pub enum EditorEvent {
+    // init.lua has run
+    Startup,
+    // a key as typed, before mappings, in keymap notation
+    Key(String),
+    ModeChanged(Mode),
+    // the current buffer was edited, reported once a frame at most
+    BufferChanged,
+    // a buffer was written to the path
+    Write(String),
+}
+
+impl EditorEvent {
+    // what scripts subscribe to with fim.on
+    pub fn name(&self) -> &'static str {
+        match self {
+            EditorEvent::Startup => "startup",
+            EditorEvent::Key(_) => "key",
+            EditorEvent::ModeChanged(_) => "mode",
+            EditorEvent::BufferChanged => "change",
+            EditorEvent::Write(_) => "write",
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use super::{buffer::Line, indent::display_width, indent::leading_whitespace};
+
+pub const OPEN_MARKER: &str = "{{{";
+pub const CLOSE_MARKER: &str = "}}}";
+
+// how folds are created for a buffer
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FoldMethod {
+    Manual,
+    Indent,
+    Marker,
+    Syntax,
+}
+
+impl FoldMethod {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "manual" => Some(FoldMethod::Manual),
+            "indent" => Some(FoldMethod::Indent),
+            "marker" => Some(FoldMethod::Marker),
+            "syntax" => Some(FoldMethod::Syntax),
+            _ => None,
+        }
+    }
+}
+
+// lines start to end inclusive, 0 indexed
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+    pub closed: bool,
+}
+
+impl Fold {
+    fn new(start: usize, end: usize) -> Self {
+        Fold {
+            start,
+            end,
+            closed: false,
+        }
+    }
+
+    fn contains(&self, line: usize) -> bool {
+        self.start <= line && line <= self.end
+    }
+}
+
+/*
+* folds of a buffer, sorted by start with enclosing folds before the folds they contain.
+* revision is the buffer revision computed folds were built from
+*/
+#[derive(Debug, Clone)]
+pub struct Folds {
+    folds: Vec<Fold>,
+    pub revision: Option<u64>,
+}
+
+impl Folds {
+    pub fn new() -> Self {
+        Folds {
+            folds: Vec::new(),
+            revision: None,
+        }
+    }
+
+    fn sort(&mut self) {
+        self.folds.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+        // folds that became equal after an edit merge, staying closed if either was
+        self.folds.dedup_by(|a, b| {
+            let same = a.start == b.start && a.end == b.end;
+            if same {
+                b.closed |= a.closed;
+            }
+            same
+        });
+    }
+
+    // swaps in recomputed folds, keeping folds that start on the same line closed
+    pub fn replace(&mut self, folds: Vec<Fold>) {
+        let closed: Vec<usize> = self.folds.iter().filter(|f| f.closed).map(|f| f.start).collect();
+        self.folds = folds;
+        for fold in &mut self.folds {
+            fold.closed = closed.contains(&fold.start);
+        }
+        self.sort();
+    }
+
+    pub fn add(&mut self, start: usize, end: usize, closed: bool) {
+        self.folds.push(Fold { start, end, closed });
+        self.sort();
+    }
+
+    // outermost closed fold containing line, which is the one shown in its place
+    pub fn closed_at(&self, line: usize) -> Option<Fold> {
+        self.folds.iter().find(|f| f.closed && f.contains(line)).copied()
+    }
+
+    // closed folds that are not inside another closed fold
+    pub fn hidden_ranges(&self) -> Vec<(usize, usize)> {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for fold in self.folds.iter().filter(|f| f.closed) {
+            if ranges.last().map_or(true, |(_, end)| fold.start > *end) {
+                ranges.push((fold.start, fold.end));
+            }
+        }
+        ranges
+    }
+
+    // zo, opens the closed fold shown at line
+    pub fn open(&mut self, line: usize) -> bool {
+        match self.folds.iter_mut().find(|f| f.closed && f.contains(line)) {
+            Some(fold) => {
+                fold.closed = false;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // zc, closes the innermost open fold around line, or the one around an already closed fold
+    pub fn close(&mut self, line: usize) -> bool {
+        let outer = self.closed_at(line);
+        let target = self
+            .folds
+            .iter_mut()
+            .rev()
+            .filter(|f| !f.closed && f.contains(line))
+            .find(|f| outer.map_or(true, |outer| f.start <= outer.start && outer.end <= f.end));
+        match target {
+            Some(fold) => {
+                fold.closed = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // za
+    pub fn toggle(&mut self, line: usize) -> bool {
+        self.open(line) || self.close(line)
+    }
+
+    // zR and zM
+    pub fn set_all(&mut self, closed: bool) {
+        for fold in &mut self.folds {
+            fold.closed = closed;
+        }
+    }
+
+    // keeps manual folds on the same text when count lines are inserted before line at
+    pub fn lines_inserted(&mut self, at: usize, count: usize) {
+        for fold in &mut self.folds {
+            if fold.start >= at {
+                fold.start += count;
+            }
+            if fold.end >= at {
+                fold.end += count;
+            }
+        }
+    }
+
+    // shrinks folds over deleted lines, dropping folds that were deleted entirely
+    pub fn lines_deleted(&mut self, at: usize, count: usize) {
+        for fold in &mut self.folds {
+            if fold.start >= at + count {
+                fold.start -= count;
+            } else if fold.start > at {
+                fold.start = at;
+            }
+            if fold.end >= at + count {
+                fold.end -= count;
+            } else if fold.end >= at {
+                // the fold now ends on the line before the deleted ones
+                fold.end = at.wrapping_sub(1);
+            }
+        }
+        self.folds.retain(|f| f.end != usize::MAX && f.start < f.end);
+        self.sort();
+    }
+}
+
+// runs of lines indented by at least n shiftwidths, blank lines take the lower level around them
+pub fn indent_folds(lines: &[Line], tabstop: usize, shiftwidth: usize) -> Vec<Fold> {
+    let levels: Vec<Option<usize>> = lines
+        .iter()
+        .map(|line| {
+            if line.content.trim().is_empty() {
+                None
+            } else {
+                Some(display_width(leading_whitespace(&line.content), tabstop) / shiftwidth.max(1))
+            }
+        })
+        .collect();
+
+    let mut resolved = vec![0; levels.len()];
+    let mut prev = 0;
+    for i in 0..levels.len() {
+        resolved[i] = match levels[i] {
+            Some(level) => level,
+            None => {
+                let next = levels[i..].iter().flatten().next().copied().unwrap_or(0);
+                prev.min(next)
+            }
+        };
+        prev = resolved[i];
+    }
+
+    nest_levels(&resolved)
+}
+
+// folds between `{{{` and `}}}` markers, an unclosed marker runs to the end of the buffer
+pub fn marker_folds(lines: &[Line]) -> Vec<Fold> {
+    let mut folds = Vec::new();
+    let mut stack = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        for _ in line.content.matches(OPEN_MARKER) {
+            stack.push(i);
+        }
+        for _ in line.content.matches(CLOSE_MARKER) {
+            if let Some(start) = stack.pop() {
+                folds.push(Fold::new(start, i));
+            }
+        }
+    }
+    let last = lines.len().saturating_sub(1);
+    folds.extend(stack.into_iter().map(|start| Fold::new(start, last)));
+    folds.retain(|f| f.start < f.end);
+    folds
+}
+
+// folds over brackets in code that open and close on different lines
+pub fn syntax_folds(lines: &[Line], pairs: &[(char, char)], is_code: impl Fn(usize, usize) -> bool) -> Vec<Fold> {
+    let mut folds: Vec<Fold> = Vec::new();
+    let mut stack: Vec<(char, usize)> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        for (offset, c) in line.content.char_indices() {
+            if !pairs.iter().any(|(open, close)| c == *open || c == *close) || !is_code(i, offset) {
+                continue;
+            }
+            if let Some((_, close)) = pairs.iter().find(|(open, _)| c == *open) {
+                stack.push((*close, i));
+            } else if let Some(pos) = stack.iter().rposition(|(close, _)| c == *close) {
+                let (_, start) = stack[pos];
+                stack.truncate(pos);
+                // several brackets opening on one line only make one fold
+                if start < i && !folds.iter().any(|f| f.start == start && f.end >= i) {
+                    folds.retain(|f| f.start != start);
+                    folds.push(Fold::new(start, i));
+                }
+            }
+        }
+    }
+    folds
+}
+
+// turns a fold level per line into nested folds
+fn nest_levels(levels: &[usize]) -> Vec<Fold> {
+    let mut folds = Vec::new();
+    let mut stack: Vec<usize> = Vec::new();
+    for (i, &level) in levels.iter().enumerate() {
+        while stack.len() > level {
+            let start = stack.pop().unwrap();
+            folds.push(Fold::new(start, i - 1));
+        }
+        while stack.len() < level {
+            stack.push(i);
+        }
+    }
+    let last = levels.len().saturating_sub(1);
+    folds.extend(stack.into_iter().map(|start| Fold::new(start, last)));
+    folds.retain(|f| f.start < f.end);
+    folds
+}
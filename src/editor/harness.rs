@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use crate::configuration::Config;
+
+use super::{
+    buffer::{BufferSettings, Buffers, Line},
+    input::InputEvent,
+    keymap, Editor, Mode,
+};
+
+/*
+* drives an editor without a window. keys are given in vim notation and go through the same
+* input path as the sdl frontend: the key event first, then the text it types
+*/
+pub struct Harness {
+    pub editor: Editor,
+}
+
+impl Harness {
+    // the cursor starts on the first char like after opening a file
+    pub fn new(text: &str) -> Self {
+        Harness::with_config(text, &Config::default())
+    }
+
+    // the config file and init script on disk are never read, only the config given
+    pub fn with_config(text: &str, config: &Config) -> Self {
+        let mut buffers = Buffers::new();
+        let buf = buffers.curr_buffer_mut();
+        buf.settings = BufferSettings::of(config);
+        buf.lines = text.split('\n').map(|line| Line::from(line.to_owned())).collect();
+        Harness {
+            editor: Editor::detached(buffers, config),
+        }
+    }
+
+    // runs lua like init.lua would, before any key
+    pub fn with_script(text: &str, lua: &str) -> Self {
+        let mut harness = Harness::new(text);
+        harness.editor.run_lua(lua);
+        assert_eq!(harness.message(), None, "running {:?}", lua);
+        harness
+    }
+
+    // a detached editor with files open, the first one current
+    pub fn open(paths: &[&Path], config: &Config) -> Self {
+        let paths: Vec<&str> = paths.iter().map(|path| path.to_str().expect("temp paths are utf-8")).collect();
+        Harness {
+            editor: Editor::detached(Buffers::from_files(&paths, 0), config),
+        }
+    }
+
+    pub fn keys(&mut self, keys: &str) -> &mut Self {
+        let tokens = keymap::parse_keys(keys, &self.editor.keymap.leader).expect("keys should be valid notation");
+        for token in tokens {
+            let event = keymap::token_key(&token).expect("every parsed token is a key");
+            self.editor.handle_input(InputEvent::Key(event));
+            if let Some(text) = keymap::token_text(&token) {
+                self.editor.handle_input(InputEvent::Text(text));
+            }
+        }
+        self
+    }
+
+    // what waiting out the mapping timeout does
+    pub fn timeout(&mut self) -> &mut Self {
+        self.editor.resolve_typed(None);
+        self
+    }
+
+    pub fn text(&self) -> String {
+        self.editor.get_text()
+    }
+
+    // 1 indexed line and 0 indexed char, like the buffer's cursor
+    pub fn cursor(&self) -> (usize, usize) {
+        let cursor = self.editor.buffers.curr_buffer().cursor();
+        (cursor.line_pos, cursor.char_pos)
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.editor.mode
+    }
+
+    // only the unnamed register " exists so far
+    pub fn register(&self, name: char) -> Option<&str> {
+        (name == '"').then_some(self.editor.yank_buffer.as_str())
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.editor.get_message()
+    }
+}
+
+#[track_caller]
+pub fn assert_keys(before: &str, keys: &str, after: &str) {
+    let mut harness = Harness::new(before);
+    harness.keys(keys);
+    assert_eq!(harness.text(), after, "typing {:?} into {:?}", keys, before);
+}
+
+#[track_caller]
+pub fn assert_cursor(before: &str, keys: &str, cursor: (usize, usize)) {
+    let mut harness = Harness::new(before);
+    harness.keys(keys);
+    assert_eq!(harness.cursor(), cursor, "typing {:?} into {:?}", keys, before);
+}
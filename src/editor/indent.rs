@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use super::layout;
+
+// widest indentation a shift makes, like vim it leaves lines alone rather than go past it
+pub const MAX_INDENT: usize = 32 * 1024;
+
+// Per-filetype rules used when opening new lines and typing closing brackets
+#[derive(Debug, Clone)]
+pub struct IndentRules {
+    // a line ending (ignoring trailing whitespace) in one of these indents the next line
+    pub increase_after: Vec<String>,
+    // typing one of these as the first non-blank char of a line dedents it
+    pub decrease_on: Vec<char>,
+}
+
+impl IndentRules {
+    pub fn new() -> Self {
+        IndentRules {
+            increase_after: Vec::from(["{".into(), "[".into(), "(".into()]),
+            decrease_on: Vec::from(['}', ']', ')']),
+        }
+    }
+
+    // picks rules by file extension, falling back to bracket-only rules
+    pub fn for_file(file_path: &str) -> Self {
+        let ext = Path::new(file_path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("");
+
+        let mut rules = IndentRules::new();
+        match ext {
+            "py" | "pyw" | "nim" => {
+                rules.increase_after.push(":".into());
+            }
+            "lua" => {
+                rules.increase_after.extend(["then".into(), "do".into(), "function()".into()]);
+            }
+            "sh" | "bash" | "zsh" => {
+                rules.increase_after.extend(["then".into(), "do".into()]);
+            }
+            "rb" => {
+                rules.increase_after.push("do".into());
+            }
+            "md" | "txt" | "" => {
+                rules.increase_after.clear();
+                rules.decrease_on.clear();
+            }
+            _ => (),
+        }
+        rules
+    }
+
+    pub fn increases_after(&self, text: &str) -> bool {
+        let text = text.trim_end();
+        self.increase_after.iter().any(|pat| text.ends_with(pat.as_str()))
+    }
+
+    pub fn decreases_on(&self, c: char) -> bool {
+        self.decrease_on.contains(&c)
+    }
+}
+
+// returns the leading whitespace of a line
+pub fn leading_whitespace(text: &str) -> &str {
+    let end = text.len() - text.trim_start_matches([' ', '\t']).len();
+    &text[..end]
+}
+
+// width of text in columns, a column per grapheme cluster and tabs to the next tab stop
+pub fn display_width(text: &str, tabstop: usize) -> usize {
+    layout::clusters(text).fold(0, |col, (_, cluster)| col + layout::cluster_width(cluster, col, tabstop))
+}
+
+// the closing bracket that pairs with an opener, if any
+pub fn closer_of(c: char) -> Option<char> {
+    match c {
+        '{' => Some('}'),
+        '[' => Some(']'),
+        '(' => Some(')'),
+        _ => None,
+    }
+}
@@ -0,0 +1,69 @@
+// keys the editor reacts to, whatever window system or terminal they came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Key {
+    // a key that types a char, as typed: shift and caps lock are already applied
+    Char(char),
+    Escape,
+    Return,
+    Backspace,
+    Tab,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub mods: Modifiers,
+}
+
+impl KeyEvent {
+    pub fn new(key: Key) -> Self {
+        KeyEvent {
+            key,
+            mods: Modifiers::default(),
+        }
+    }
+
+    // c with only ctrl held, like <C-r>
+    pub fn ctrl(c: char) -> Self {
+        KeyEvent {
+            key: Key::Char(c),
+            mods: Modifiers {
+                ctrl: true,
+                ..Modifiers::default()
+            },
+        }
+    }
+
+    // the char the key types, None when ctrl or alt turn it into a command
+    pub fn char(&self) -> Option<char> {
+        match self.key {
+            Key::Char(c) if !self.mods.ctrl && !self.mods.alt => Some(c),
+            _ => None,
+        }
+    }
+}
+
+/*
+* what a frontend feeds the editor. a key that types something is followed by the text it typed,
+* the editor drops that text when the key already did something else, like `i` entering insert
+* mode. frontends without separate text events send the text right after the key
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputEvent {
+    Key(KeyEvent),
+    Text(String),
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+
+use super::input::{Key, KeyEvent, Modifiers};
+
+// how deep a recursive mapping may expand before it is considered a loop
+pub const MAX_MAP_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum MapMode {
+    Normal,
+    Insert,
+    Visual,
+    Command,
+}
+
+#[derive(Debug, Clone)]
+pub struct Mapping {
+    pub lhs: Vec<String>,
+    pub rhs: Vec<String>,
+    // rhs keys run as they are instead of going through the mappings again
+    pub noremap: bool,
+}
+
+// how the keys typed so far relate to the mappings of a mode
+pub enum Lookup<'a> {
+    None,
+    // the start of at least one longer mapping
+    Prefix,
+    Exact(&'a Mapping),
+    // a mapping, but also the start of a longer one, resolved by more keys or the timeout
+    ExactAndPrefix(&'a Mapping),
+}
+
+/*
+* per mode tables of key mappings. keys are kept as tokens in vim notation, a printable char or
+* a name in angle brackets like <Esc> or <C-s>
+*/
+pub struct Keymap {
+    maps: HashMap<MapMode, Vec<Mapping>>,
+    // what <leader> means in mappings defined from now on
+    pub leader: String,
+}
+
+impl Keymap {
+    pub fn new(leader: &str) -> Self {
+        Keymap {
+            maps: HashMap::new(),
+            leader: leader.to_string(),
+        }
+    }
+
+    pub fn lookup(&self, mode: MapMode, keys: &[String]) -> Lookup<'_> {
+        let Some(maps) = self.maps.get(&mode) else {
+            return Lookup::None;
+        };
+        let exact = maps.iter().find(|map| map.lhs == keys);
+        let longer = maps.iter().any(|map| map.lhs.len() > keys.len() && map.lhs.starts_with(keys));
+        match (exact, longer) {
+            (Some(map), true) => Lookup::ExactAndPrefix(map),
+            (Some(map), false) => Lookup::Exact(map),
+            (None, true) => Lookup::Prefix,
+            (None, false) => Lookup::None,
+        }
+    }
+
+    // the longest mapping keys start with, used when expanding the rhs of recursive mappings
+    pub fn longest_match(&self, mode: MapMode, keys: &[String]) -> Option<&Mapping> {
+        self.maps
+            .get(&mode)?
+            .iter()
+            .filter(|map| keys.starts_with(&map.lhs))
+            .max_by_key(|map| map.lhs.len())
+    }
+
+    /*
+    * runs a map command such as `nnoremap <leader>w :w<CR>`. without rhs the mappings starting
+    * with lhs (or all of them without lhs) are listed instead
+    */
+    pub fn map(&mut self, command: &str, lhs: Option<&str>, rhs: Option<&str>) -> Result<Option<String>, String> {
+        let (modes, noremap) = map_command(command).ok_or_else(|| format!("not a map command: {}", command))?;
+        let lhs = lhs.map(|lhs| parse_keys(lhs, &self.leader)).transpose()?;
+
+        let Some(rhs) = rhs else {
+            return Ok(Some(self.list(&modes, lhs.as_deref().unwrap_or(&[]))));
+        };
+        let lhs = lhs.filter(|lhs| !lhs.is_empty()).ok_or("missing keys to map")?;
+        let rhs = parse_keys(rhs, &self.leader)?;
+        for mode in modes {
+            let maps = self.maps.entry(mode).or_default();
+            maps.retain(|map| map.lhs != lhs);
+            maps.push(Mapping {
+                lhs: lhs.clone(),
+                rhs: rhs.clone(),
+                noremap,
+            });
+        }
+        Ok(None)
+    }
+
+    // runs an unmap command such as `iunmap jk`
+    pub fn unmap(&mut self, command: &str, lhs: &str) -> Result<(), String> {
+        let (modes, _) = unmap_command(command).ok_or_else(|| format!("not an unmap command: {}", command))?;
+        let lhs = parse_keys(lhs, &self.leader)?;
+        let mut found = false;
+        for mode in modes {
+            if let Some(maps) = self.maps.get_mut(&mode) {
+                let before = maps.len();
+                maps.retain(|map| map.lhs != lhs);
+                found |= maps.len() != before;
+            }
+        }
+        if found {
+            Ok(())
+        } else {
+            Err(format!("no such mapping: {}", lhs.concat()))
+        }
+    }
+
+    fn list(&self, modes: &[MapMode], prefix: &[String]) -> String {
+        let mut lines = Vec::new();
+        for mode in modes {
+            let Some(maps) = self.maps.get(mode) else {
+                continue;
+            };
+            for map in maps.iter().filter(|map| map.lhs.starts_with(prefix)) {
+                let star = if map.noremap { "*" } else { " " };
+                lines.push(format!("{}  {:<12} {}{}", mode_letter(*mode), map.lhs.concat(), star, map.rhs.concat()));
+            }
+        }
+        if lines.is_empty() {
+            String::from("no mappings found")
+        } else {
+            lines.join("\n")
+        }
+    }
+}
+
+fn mode_letter(mode: MapMode) -> char {
+    match mode {
+        MapMode::Normal => 'n',
+        MapMode::Insert => 'i',
+        MapMode::Visual => 'v',
+        MapMode::Command => 'c',
+    }
+}
+
+// modes a map command defines mappings for and whether they are noremap
+pub fn map_command(command: &str) -> Option<(Vec<MapMode>, bool)> {
+    let (prefix, noremap) = match command.strip_suffix("noremap") {
+        Some(prefix) => (prefix, true),
+        None => (command.strip_suffix("map")?, false),
+    };
+    command_modes(prefix).map(|modes| (modes, noremap))
+}
+
+pub fn unmap_command(command: &str) -> Option<(Vec<MapMode>, bool)> {
+    command_modes(command.strip_suffix("unmap")?).map(|modes| (modes, false))
+}
+
+// the letter in front of map commands, none maps normal and visual mode like vim
+fn command_modes(prefix: &str) -> Option<Vec<MapMode>> {
+    match prefix {
+        "" => Some(vec![MapMode::Normal, MapMode::Visual]),
+        "n" => Some(vec![MapMode::Normal]),
+        "i" => Some(vec![MapMode::Insert]),
+        "v" | "x" => Some(vec![MapMode::Visual]),
+        "c" => Some(vec![MapMode::Command]),
+        _ => None,
+    }
+}
+
+/*
+* splits vim key notation into tokens, `<leader>` becomes the leader key and names in angle
+* brackets are normalized so <esc> and <Esc> are the same key
+*/
+pub fn parse_keys(notation: &str, leader: &str) -> Result<Vec<String>, String> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+    while let Some(c) = rest.chars().next() {
+        let special = (c == '<').then(|| rest.find('>')).flatten();
+        let Some(end) = special else {
+            keys.push(match c {
+                ' ' => String::from("<Space>"),
+                '<' => String::from("<lt>"),
+                c => c.to_string(),
+            });
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+
+        let name = &rest[1..end];
+        rest = &rest[end + 1..];
+        if name.eq_ignore_ascii_case("leader") {
+            keys.extend(parse_keys(leader, "\\")?);
+            continue;
+        }
+        keys.push(normalize(name).ok_or_else(|| format!("unknown key <{}>", name))?);
+    }
+    Ok(keys)
+}
+
+const SPECIAL_KEYS: [(&str, Key); 12] = [
+    ("Esc", Key::Escape),
+    ("CR", Key::Return),
+    ("BS", Key::Backspace),
+    ("Tab", Key::Tab),
+    ("Space", Key::Char(' ')),
+    ("Del", Key::Delete),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Home", Key::Home),
+    ("End", Key::End),
+];
+
+// the canonical token for a name in angle brackets
+fn normalize(name: &str) -> Option<String> {
+    let lower = name.to_ascii_lowercase();
+    let alias = match lower.as_str() {
+        "lt" => return Some(String::from("<lt>")),
+        "enter" | "return" => "CR",
+        "escape" => "Esc",
+        "backspace" => "BS",
+        "delete" => "Del",
+        _ => name,
+    };
+    if let Some((named, _)) = SPECIAL_KEYS.iter().find(|(named, _)| named.eq_ignore_ascii_case(alias)) {
+        return Some(format!("<{}>", named));
+    }
+
+    // <C-x> and <A-x>, or with a special key like <C-Space>
+    let (modifier, key) = name.split_once('-')?;
+    let modifier = match modifier.to_ascii_uppercase().as_str() {
+        "C" => 'C',
+        "A" | "M" => 'A',
+        _ => return None,
+    };
+    let mut chars = key.chars();
+    let key = match (chars.next(), chars.next()) {
+        (Some(c), None) if c.is_ascii_graphic() => c.to_ascii_lowercase().to_string(),
+        _ => normalize(key)?.trim_matches(['<', '>']).to_string(),
+    };
+    Some(format!("<{}-{}>", modifier, key))
+}
+
+// the token for a key press
+pub fn key_token(event: KeyEvent) -> String {
+    if let Some((name, _)) = SPECIAL_KEYS.iter().find(|(_, key)| *key == event.key) {
+        return modified(event.mods, name.to_string());
+    }
+    match event.key {
+        Key::Char(c) if event.char().is_some() => match c {
+            '<' => String::from("<lt>"),
+            c => c.to_string(),
+        },
+        // ctrl and alt combos of printable keys
+        Key::Char(c) => modified(event.mods, c.to_ascii_lowercase().to_string()),
+        _ => unreachable!("every other key is in SPECIAL_KEYS"),
+    }
+}
+
+fn modified(mods: Modifiers, key: String) -> String {
+    if mods.ctrl {
+        format!("<C-{}>", key)
+    } else if mods.alt {
+        format!("<A-{}>", key)
+    } else if key.chars().count() > 1 {
+        format!("<{}>", key)
+    } else {
+        key
+    }
+}
+
+// the key press a token stands for, so mapped keys can be replayed
+pub fn token_key(token: &str) -> Option<KeyEvent> {
+    let name = token.strip_prefix('<').and_then(|token| token.strip_suffix('>'));
+    let Some(name) = name.filter(|name| *name != "lt") else {
+        let c = if token == "<lt>" { '<' } else { token.chars().next()? };
+        return Some(KeyEvent::new(Key::Char(c)));
+    };
+    if let Some((_, key)) = SPECIAL_KEYS.iter().find(|(named, _)| *named == name) {
+        return Some(KeyEvent::new(*key));
+    }
+    let (modifier, key) = name.split_once('-')?;
+    let key = match SPECIAL_KEYS.iter().find(|(named, _)| *named == key) {
+        Some((_, key)) => *key,
+        None => Key::Char(key.chars().next()?),
+    };
+    let mods = Modifiers {
+        ctrl: modifier == "C",
+        alt: modifier == "A",
+        ..Modifiers::default()
+    };
+    Some(KeyEvent { key, mods })
+}
+
+// text a replayed key types, as the text input event following it would
+pub fn token_text(token: &str) -> Option<String> {
+    match token {
+        "<lt>" => Some(String::from("<")),
+        "<Space>" => Some(String::from(" ")),
+        _ if token.starts_with('<') && token.len() > 1 => None,
+        _ => Some(token.to_string()),
+    }
+}
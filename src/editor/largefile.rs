@@ -0,0 +1,299 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+};
+
+use memmap2::Mmap;
+
+use super::buffer::Line;
+
+// bytes scanned for newlines before the shared index is updated
+const INDEX_CHUNK: usize = 4 << 20;
+
+// lines kept loaded past the cursor
+pub const LOAD_AHEAD: usize = 10_000;
+// most lines turned into Lines at once
+pub const LOAD_CHUNK: usize = 100_000;
+
+/*
+* a memory mapped file whose line starts are indexed on a background thread. lines are only
+* turned into Lines when the buffer asks for them, the buffer holds a window of the file's lines
+* and the lines before and after it stay in the file
+*/
+pub struct LargeFile {
+    map: Arc<Mmap>,
+    // byte offsets of the start of every line after the first
+    index: Arc<Mutex<Vec<usize>>>,
+    scanned: Arc<AtomicUsize>,
+    done: Arc<AtomicBool>,
+    // set when the file is dropped, the indexing thread stops at the next chunk
+    cancelled: Arc<AtomicBool>,
+    // notified after every chunk and with the index locked once done is set
+    indexed: Arc<Condvar>,
+    // the lines first..loaded of the file are the ones handed out
+    first: usize,
+    loaded: usize,
+}
+
+impl LargeFile {
+    pub fn open(file_path: &str) -> io::Result<Self> {
+        let file = File::open(file_path)?;
+        // SAFETY: the map is only read, a file truncated by another program shows up as garbage
+        // or a bus error, the usual tradeoff of mapping files
+        let map = Arc::new(unsafe { Mmap::map(&file)? });
+        let index = Arc::new(Mutex::new(Vec::new()));
+        let scanned = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicBool::new(false));
+        let indexed = Arc::new(Condvar::new());
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let (t_map, t_index, t_scanned) = (map.clone(), index.clone(), scanned.clone());
+        let (t_done, t_indexed, t_cancelled) = (done.clone(), indexed.clone(), cancelled.clone());
+        thread::spawn(move || {
+            for (chunk_idx, chunk) in t_map.chunks(INDEX_CHUNK).enumerate() {
+                // nobody waits on a dropped file, so done is never set
+                if t_cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                let base = chunk_idx * INDEX_CHUNK;
+                let starts: Vec<usize> = memchr::memchr_iter(b'\n', chunk).map(|i| base + i + 1).collect();
+                t_index.lock().unwrap().extend(starts);
+                t_scanned.store(base + chunk.len(), Ordering::Release);
+                t_indexed.notify_all();
+            }
+            // set with the lock held so a waiter can not miss it between checking and waiting
+            let _index = t_index.lock().unwrap();
+            t_done.store(true, Ordering::Release);
+            t_indexed.notify_all();
+        });
+
+        Ok(LargeFile {
+            map,
+            index,
+            scanned,
+            done,
+            cancelled,
+            indexed,
+            first: 0,
+            loaded: 0,
+        })
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        self.done.load(Ordering::Acquire)
+    }
+
+    // fraction of the file indexed so far
+    pub fn progress(&self) -> f32 {
+        match self.map.len() {
+            0 => 1.,
+            len => self.scanned.load(Ordering::Acquire) as f32 / len as f32,
+        }
+    }
+
+    // lines found so far, the last one may still be growing while indexing
+    pub fn indexed_lines(&self) -> usize {
+        let found = self.index.lock().unwrap().len();
+        self.line_count(found)
+    }
+
+    // a newline ending the file does not start another line
+    fn line_count(&self, found: usize) -> usize {
+        if self.map.ends_with(b"\n") && self.is_indexed() {
+            found
+        } else {
+            found + 1
+        }
+    }
+
+    // file lines before the first one handed out
+    pub fn first_line(&self) -> usize {
+        self.first
+    }
+
+    // file lines after the last one handed out, as far as indexed
+    pub fn lines_after(&self) -> usize {
+        self.indexed_lines().saturating_sub(self.loaded)
+    }
+
+    pub fn handed_out(&self) -> usize {
+        self.loaded - self.first
+    }
+
+    pub fn is_fully_loaded(&self) -> bool {
+        self.first == 0 && self.is_indexed() && self.loaded == self.indexed_lines()
+    }
+
+    // lines that are known to be complete, the last one may still grow while indexing
+    fn complete_lines(&self, index: &[usize]) -> usize {
+        if self.is_indexed() {
+            self.line_count(index.len())
+        } else {
+            index.len()
+        }
+    }
+
+    // byte offset of the start of a line, the end of the file for the line after the last
+    fn line_start(&self, index: &[usize], line: usize) -> usize {
+        match line {
+            0 => 0,
+            line => index.get(line - 1).copied().unwrap_or(self.map.len()),
+        }
+    }
+
+    fn line(&self, index: &[usize], line: usize) -> Line {
+        let start = self.line_start(index, line);
+        let end = index.get(line).map_or(self.map.len(), |next| next - 1);
+        let bytes = &self.map[start..end];
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        Line::from(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    // the next lines after the ones handed out, at most max of them and only complete ones
+    pub fn read_lines(&mut self, max: usize) -> Vec<Line> {
+        let index = self.index.lock().unwrap();
+        let end_line = self.complete_lines(&index).min(self.loaded.saturating_add(max));
+        let lines = (self.loaded..end_line).map(|line| self.line(&index, line)).collect();
+        drop(index);
+        self.loaded = end_line.max(self.loaded);
+        lines
+    }
+
+    // the lines right before the ones handed out, at most max of them
+    pub fn read_before(&mut self, max: usize) -> Vec<Line> {
+        let index = self.index_to(self.first);
+        let start_line = self.first.saturating_sub(max);
+        let lines = (start_line..self.first).map(|line| self.line(&index, line)).collect();
+        drop(index);
+        self.first = start_line;
+        lines
+    }
+
+    // hands out lines from the 0 indexed line of the file on, as far as it is indexed
+    pub fn restart_at(&mut self, line: usize) {
+        let index = self.index.lock().unwrap();
+        self.first = line.min(self.complete_lines(&index));
+        self.loaded = self.first;
+    }
+
+    // the lines first..loaded are what the buffer has of the file, after writing it out again
+    pub fn set_window(&mut self, first: usize, loaded: usize) {
+        self.first = first;
+        self.loaded = loaded;
+    }
+
+    /*
+    * writes the file with the lines handed out replaced by window, the lines before and after
+    * them are copied from the map as they are
+    */
+    pub fn write_with(&self, out: &mut impl Write, window: &[u8]) -> io::Result<()> {
+        let index = self.index_to(self.loaded);
+        let (start, end) = (self.line_start(&index, self.first), self.line_start(&index, self.loaded));
+        out.write_all(&self.map[..start])?;
+        out.write_all(window)?;
+        out.write_all(&self.map[end..])
+    }
+
+    // true if lines of the file come after the ones handed out
+    pub fn has_tail(&self) -> bool {
+        let index = self.index_to(self.loaded);
+        self.line_start(&index, self.loaded) < self.map.len()
+    }
+
+    // blocks until the whole file is indexed
+    pub fn wait(&self) {
+        drop(self.index_to(usize::MAX));
+    }
+
+    // the index once it has the starts of the lines before line, which a reopened file may not
+    fn index_to(&self, line: usize) -> MutexGuard<'_, Vec<usize>> {
+        let index = self.index.lock().unwrap();
+        self.indexed.wait_while(index, |index| !self.is_indexed() && index.len() < line).unwrap()
+    }
+}
+
+impl Drop for LargeFile {
+    // a file reopened after every write would otherwise leave a thread indexing each old map
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::editor::{buffer::Buffer, testdir::TestDir};
+
+    #[test]
+    fn reading_and_writing_a_window() {
+        let dir = TestDir::new("large_window");
+        let path = dir.file("window", "a\nb\r\nc\nd");
+        let mut file = LargeFile::open(path.to_str().unwrap()).unwrap();
+        file.wait();
+        assert_eq!(file.indexed_lines(), 4);
+
+        // lines are handed out from anywhere in the file, line ends are not part of them
+        let content = |lines: Vec<Line>| -> Vec<String> { lines.into_iter().map(|line| line.content).collect() };
+        file.restart_at(1);
+        assert_eq!(content(file.read_lines(2)), ["b", "c"]);
+        assert_eq!(content(file.read_before(5)), ["a"]);
+        assert_eq!((file.first_line(), file.handed_out(), file.lines_after()), (0, 3, 1));
+        assert!(file.has_tail());
+
+        // writing replaces the lines handed out and copies the rest
+        let mut out = Vec::new();
+        file.write_with(&mut out, b"x\n").unwrap();
+        assert_eq!(out, b"x\nd");
+    }
+
+    #[test]
+    fn opening_through_a_buffer() {
+        let numbers = |lines: std::ops::RangeInclusive<usize>| lines.map(|i| format!("{}\n", i)).collect::<String>();
+        let dir = TestDir::new("large");
+        let path = dir.file("large", &numbers(1..=30_000));
+        let file_path = path.to_str().unwrap();
+        let current = |buf: &Buffer| buf.get_line(buf.line_pos() - 1).content.clone();
+
+        // G finds the last line with the index and only reads the lines around it
+        let mut buf = Buffer::open(file_path, 0);
+        let target = buf.jump_target(None, true);
+        buf.move_cursor_line_to(target);
+        assert_eq!(current(&buf), "30000");
+        assert!(buf.line_count() < 30_000);
+
+        // the lines outside the window are streamed from the file when it is written
+        buf.delete_line_curr();
+        buf.write_file().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), numbers(1..=29_999));
+
+        // with changes gg loads the lines between, they go in front of the ones there
+        buf.delete_line_curr();
+        let target = buf.jump_target(None, false);
+        buf.move_cursor_line_to(target);
+        assert_eq!(current(&buf), "1");
+        assert_eq!(buf.line_count(), 29_998);
+
+        // without changes gg swaps the lines for the ones at the start, the rest of the file is
+        // written after them
+        let mut buf = Buffer::open(file_path, 0);
+        buf.jump_target(None, true);
+        let target = buf.jump_target(None, false);
+        buf.move_cursor_line_to(target);
+        assert_eq!(current(&buf), "1");
+        assert!(buf.line_count() < 29_999);
+        buf.delete_line_curr();
+        buf.write_file().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), numbers(2..=29_999));
+    }
+}
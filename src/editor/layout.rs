@@ -0,0 +1,436 @@
+use std::{collections::HashMap, sync::Arc};
+
+use unicode_bidi::{bidi_class, BidiClass, ParagraphBidiInfo};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::indent::{display_width, leading_whitespace};
+
+// soft wrap options, width is in columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wrap {
+    pub width: usize,
+    // break at whitespace instead of the last column that fits
+    pub linebreak: bool,
+    // continuation rows keep the indentation of the line
+    pub breakindent: bool,
+    // drawn at the start of continuation rows
+    pub showbreak: String,
+}
+
+/*
+* visible columns when lines are not wrapped. precedes and extends are drawn at the edges of
+* lines that continue off screen
+*/
+#[derive(Debug, Clone)]
+pub struct Viewport {
+    pub left: usize,
+    pub width: usize,
+    // columns to scroll at once when the cursor leaves the view, 0 recenters on the cursor
+    pub sidescroll: usize,
+    // columns kept visible around the cursor
+    pub sidescrolloff: usize,
+    pub precedes: Option<char>,
+    pub extends: Option<char>,
+}
+
+impl Viewport {
+    // scrolls so that the width columns from col are at least sidescrolloff columns from either edge
+    pub fn scroll_to(&mut self, col: usize, width: usize) {
+        if self.width == 0 {
+            return;
+        }
+        let margin = self.sidescrolloff.min(self.width.saturating_sub(1) / 2);
+
+        if col < self.left + margin {
+            let needed = col.saturating_sub(margin);
+            self.left = match self.sidescroll {
+                0 => col.saturating_sub(self.width / 2),
+                step => needed.min(self.left.saturating_sub(step)),
+            };
+        } else if col + width + margin > self.left + self.width {
+            let needed = col + width + margin - self.width;
+            self.left = match self.sidescroll {
+                0 => col.saturating_sub(self.width / 2),
+                step => needed.max(self.left + step),
+            };
+        }
+    }
+
+    pub fn right(&self) -> usize {
+        self.left + self.width
+    }
+}
+
+// chars start..end of a line shown on one screen row, drawn after indent columns
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Segment {
+    pub start: usize,
+    pub end: usize,
+    pub indent: usize,
+}
+
+// a row on screen, either part of a 1 indexed line or a closed fold ending at fold_end
+#[derive(Debug, Clone, Copy)]
+pub struct DisplayRow {
+    pub line: usize,
+    pub segment: Segment,
+    pub fold_end: Option<usize>,
+}
+
+/*
+* the extended grapheme clusters of text with the index of their first char. a cluster is what
+* shows as one character, like e with a combining accent, a flag or an emoji joined with zwj, so
+* the cursor steps over it and it takes a column as a whole
+*/
+pub fn clusters(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.graphemes(true).scan(0, |pos, cluster| {
+        let start = *pos;
+        *pos += cluster.chars().count();
+        Some((start, cluster))
+    })
+}
+
+/*
+* columns a cluster takes when drawn at col. east asian wide chars and emoji take two, clusters
+* without a width of their own like a lone combining mark still get one for the cursor
+*/
+pub fn cluster_width(cluster: &str, col: usize, tabstop: usize) -> usize {
+    if cluster.starts_with('\t') {
+        tabstop - col % tabstop
+    } else {
+        cluster.width().clamp(1, 2)
+    }
+}
+
+/*
+* the chars of a cluster that get drawn and shaped. outline fonts rarely have a glyph for a whole
+* emoji sequence, so those show their first emoji
+*/
+pub fn drawn_chars(cluster: &str) -> impl Iterator<Item = char> + '_ {
+    let emoji = cluster.contains('\u{200d}') || cluster.starts_with(|c| c >= '\u{1f000}');
+    cluster.chars().take(if emoji { 1 } else { usize::MAX })
+}
+
+/*
+* advances of a proportional font's glyphs in columns, the advance of a space, and the kerning
+* the shaper put between pairs of chars. whoever draws the text hands them over, without them
+* every cluster takes whole columns
+*/
+pub type Advances = Arc<FontAdvances>;
+
+#[derive(Debug, Clone, Default)]
+pub struct FontAdvances {
+    pub chars: HashMap<char, f32>,
+    pub kerning: HashMap<(char, char), f32>,
+}
+
+// how wide text is drawn, in columns that are fractional with a proportional font
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    pub tabstop: usize,
+    pub advances: Option<Advances>,
+}
+
+impl Metrics {
+    pub fn new(tabstop: usize, advances: Option<Advances>) -> Self {
+        Metrics { tabstop, advances }
+    }
+
+    pub fn monospace(tabstop: usize) -> Self {
+        Metrics { tabstop, advances: None }
+    }
+
+    // columns a cluster takes when drawn at x, one that has no glyph loaded yet takes its cells
+    pub fn width(&self, cluster: &str, x: f32) -> f32 {
+        if cluster.starts_with('\t') {
+            let tabstop = self.tabstop as f32;
+            return tabstop - x % tabstop;
+        }
+        let cells = cluster_width(cluster, 0, self.tabstop) as f32;
+        let Some(advances) = &self.advances else {
+            return cells;
+        };
+        drawn_chars(cluster)
+            .map(|c| advances.chars.get(&c).copied())
+            .sum::<Option<f32>>()
+            .filter(|width| *width > 0.)
+            .unwrap_or(cells)
+    }
+
+    // columns added between a cluster and the one drawn after it
+    pub fn kerning(&self, cluster: &str, next: &str) -> f32 {
+        let (Some(advances), Some(last), Some(first)) = (&self.advances, drawn_chars(cluster).last(), next.chars().next())
+        else {
+            return 0.;
+        };
+        advances.kerning.get(&(last, first)).copied().unwrap_or(0.)
+    }
+
+    // columns text takes from the start of a row
+    pub fn text_width(&self, text: &str) -> f32 {
+        let mut clusters = clusters(text).peekable();
+        let mut x = 0.;
+        while let Some((_, cluster)) = clusters.next() {
+            x += self.width(cluster, x) + clusters.peek().map_or(0., |(_, next)| self.kerning(cluster, next));
+        }
+        x
+    }
+}
+
+/*
+* splits a line into the parts shown on each screen row. without wrap, or when the window is
+* too narrow to wrap sensibly, the whole line is one segment
+*/
+pub fn segments(text: &str, metrics: &Metrics, wrap: Option<&Wrap>) -> Vec<Segment> {
+    let whole = whole_line(text);
+    let Some(wrap) = wrap.filter(|wrap| wrap.width > 1) else {
+        return vec![whole];
+    };
+
+    let showbreak = wrap.showbreak.chars().count();
+    let mut continued_indent = showbreak;
+    if wrap.breakindent {
+        continued_indent += display_width(leading_whitespace(text), metrics.tabstop);
+    }
+    // always leave room for some text on continuation rows
+    if continued_indent >= wrap.width / 2 {
+        continued_indent = showbreak.min(wrap.width / 2);
+    }
+
+    // the line's own indentation is not a place to break
+    let indent_chars = leading_whitespace(text).chars().count();
+
+    // rows only break between clusters
+    let clusters: Vec<(usize, &str)> = clusters(text).collect();
+    let mut segments = Vec::new();
+    let mut first = 0;
+    let mut indent = 0;
+    loop {
+        let mut x = indent as f32;
+        let mut next = first;
+        let mut last_break = None;
+        while next < clusters.len() {
+            let kerning = clusters.get(next + 1).map_or(0., |(_, after)| metrics.kerning(clusters[next].1, after));
+            let width = metrics.width(clusters[next].1, x) + kerning;
+            if x + width > wrap.width as f32 && next > first {
+                break;
+            }
+            x += width;
+            next += 1;
+            let (start, cluster) = clusters[next - 1];
+            if wrap.linebreak && start >= indent_chars && (cluster == " " || cluster == "\t") {
+                last_break = Some(next);
+            }
+        }
+
+        let start = clusters.get(first).map_or(whole.end, |(start, _)| *start);
+        if next >= clusters.len() {
+            segments.push(Segment { start, end: whole.end, indent });
+            return segments;
+        }
+
+        next = last_break.unwrap_or(next);
+        segments.push(Segment {
+            start,
+            end: clusters[next].0,
+            indent,
+        });
+        first = next;
+        indent = continued_indent;
+    }
+}
+
+// a line as a single segment, as it is laid out without wrap
+pub fn whole_line(text: &str) -> Segment {
+    Segment {
+        start: 0,
+        end: text.chars().count(),
+        indent: 0,
+    }
+}
+
+// a cluster of a segment and where it is drawn, x in columns from the left edge of the row
+#[derive(Debug, Clone, Copy)]
+pub struct Placed<'a> {
+    pub start: usize,
+    pub cluster: &'a str,
+    pub x: f32,
+    pub width: f32,
+    // part of a right to left run
+    pub rtl: bool,
+}
+
+/*
+* the clusters of a segment in the order they are drawn, left to right. lines with right to left
+* text are reordered with the unicode bidi algorithm while the buffer stays in logical order, so
+* the cursor still steps through the clusters as they are stored and is drawn where its cluster is
+*/
+pub fn placed<'a>(text: &'a str, metrics: &Metrics, segment: &Segment) -> Vec<Placed<'a>> {
+    placed_between(text, metrics, segment, f32::NEG_INFINITY, f32::INFINITY)
+}
+
+/*
+* the clusters of placed that reach into the columns left..right, the first one sticking out past
+* right included so it is known whether the row goes on. left to right lines are only laid out
+* up to there, right to left ones have to be laid out as a whole before they can be reordered
+*/
+pub fn placed_between<'a>(text: &'a str, metrics: &Metrics, segment: &Segment, left: f32, right: f32) -> Vec<Placed<'a>> {
+    let has_rtl = |c| matches!(bidi_class(c), BidiClass::R | BidiClass::AL | BidiClass::RLE | BidiClass::RLO | BidiClass::RLI);
+    let reorder = text.chars().any(has_rtl);
+
+    // widths are measured in logical order, tabs reach the same tab stops either way. a cluster's
+    // width takes in the kerning up to the next one, only on lines that are not reordered
+    let mut placed = Vec::new();
+    let mut offsets = Vec::new();
+    let mut x = segment.indent as f32;
+    let mut offset = 0;
+    let mut clusters = clusters(text).peekable();
+    while let Some((start, cluster)) = clusters.next() {
+        if start >= segment.end {
+            break;
+        }
+        if start >= segment.start {
+            let kerning = match clusters.peek() {
+                Some((next_start, next)) if !reorder && *next_start < segment.end => metrics.kerning(cluster, next),
+                _ => 0.,
+            };
+            let width = metrics.width(cluster, x) + kerning;
+            if reorder {
+                placed.push(Placed { start, cluster, x, width, rtl: false });
+                offsets.push(offset);
+            } else if x + width > left {
+                placed.push(Placed { start, cluster, x, width, rtl: false });
+                if x + width > right {
+                    break;
+                }
+            }
+            x += width;
+        }
+        offset += cluster.len();
+    }
+    if placed.is_empty() || !reorder {
+        return placed;
+    }
+
+    // each line is a paragraph, its direction is the one of its first strong char
+    let bidi = ParagraphBidiInfo::new(text, None);
+    let levels = bidi.reordered_levels(offsets[0]..offset);
+    let levels: Vec<_> = offsets.iter().map(|offset| levels[*offset]).collect();
+    let mut x = segment.indent as f32;
+    let mut visual = Vec::new();
+    for i in ParagraphBidiInfo::reorder_visual(&levels) {
+        let at = x;
+        x += placed[i].width;
+        if x <= left {
+            continue;
+        }
+        visual.push(Placed { x: at, rtl: levels[i].is_rtl(), ..placed[i] });
+        if x > right {
+            break;
+        }
+    }
+    visual
+}
+
+/*
+* x of char_pos within its segment, in columns from the left edge of the row. the end of a
+* segment is after its last char, which is on the left of it in a right to left run
+*/
+pub fn x_in_segment(text: &str, metrics: &Metrics, segment: &Segment, char_pos: usize) -> f32 {
+    x_in_placed(&placed(text, metrics, segment), segment, char_pos)
+}
+
+// x_in_segment for a segment that is already placed
+pub fn x_in_placed(placed: &[Placed], segment: &Segment, char_pos: usize) -> f32 {
+    let Some(at) = placed.iter().filter(|p| p.start <= char_pos).max_by_key(|p| p.start) else {
+        return segment.indent as f32;
+    };
+    match char_pos < segment.end {
+        true => at.x,
+        false if at.rtl => at.x,
+        false => at.x + at.width,
+    }
+}
+
+// the x ranges on the row of a segment covered by the clusters starting in first..last, left to right
+pub fn spans(text: &str, metrics: &Metrics, segment: &Segment, first: usize, last: usize) -> Vec<(f32, f32)> {
+    let mut spans: Vec<(f32, f32)> = Vec::new();
+    for p in placed(text, metrics, segment).iter().filter(|p| (first..last).contains(&p.start)) {
+        match spans.last_mut() {
+            Some(span) if span.1 == p.x => span.1 += p.width,
+            _ => spans.push((p.x, p.x + p.width)),
+        }
+    }
+    spans
+}
+
+// index of the segment showing char_pos, the position after the last char belongs to the last
+pub fn segment_of(segments: &[Segment], char_pos: usize) -> usize {
+    segments
+        .iter()
+        .position(|segment| char_pos < segment.end)
+        .unwrap_or(segments.len() - 1)
+}
+
+// the first char of the cluster of a segment drawn over x, or the end of the segment past it
+pub fn char_at_x(text: &str, metrics: &Metrics, segment: &Segment, x: f32) -> usize {
+    placed(text, metrics, segment)
+        .iter()
+        .find(|p| x < p.x + p.width)
+        .map_or(segment.end, |p| p.start)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a made up proportional font, i is narrow and m is wide, an a after m is pulled closer
+    fn advances() -> Option<Advances> {
+        Some(Arc::new(FontAdvances {
+            chars: HashMap::from([('i', 0.5), ('m', 1.5)]),
+            kerning: HashMap::from([(('m', 'a'), -0.25)]),
+        }))
+    }
+
+    #[test]
+    fn proportional_fonts() {
+        let metrics = Metrics::new(4, advances());
+        let line = whole_line("imim");
+        assert_eq!(x_in_segment("imim", &metrics, &line, 2), 2.);
+        assert_eq!(char_at_x("imim", &metrics, &line, 1.9), 1);
+        assert_eq!(char_at_x("imim", &metrics, &line, 2.1), 2);
+        // the kerning the shaper puts between two chars moves the ones after them
+        let line = whole_line("mam");
+        assert_eq!(x_in_segment("mam", &metrics, &line, 1), 1.25);
+        assert_eq!(x_in_segment("mam", &metrics, &line, 3), 3.75);
+        assert_eq!(metrics.text_width("mam"), 3.75);
+        // chars the font has no glyph for yet take their cells
+        assert_eq!(x_in_segment("ai", &metrics, &whole_line("ai"), 2), 1.5);
+
+        // rows break where the advances no longer fit
+        let wrap = Wrap {
+            width: 4,
+            linebreak: false,
+            breakindent: false,
+            showbreak: String::new(),
+        };
+        let rows = segments("mmmiiii", &metrics, Some(&wrap));
+        assert_eq!(rows, vec![Segment { start: 0, end: 2, indent: 0 }, Segment { start: 2, end: 7, indent: 0 }]);
+    }
+
+    #[test]
+    fn laying_out_the_view() {
+        // only the columns in view are laid out, with the first cluster past them to tell the row goes on
+        let metrics = Metrics::monospace(4);
+        let starts = |text: &str| -> Vec<usize> {
+            let placed = placed_between(text, &metrics, &whole_line(text), 2., 4.);
+            placed.iter().map(|p| p.start).collect()
+        };
+        assert_eq!(starts("abcdefgh"), vec![2, 3, 4]);
+        assert_eq!(starts("ab"), Vec::<usize>::new());
+        assert_eq!(starts("a\tb"), vec![1, 2]);
+        // right to left lines are reordered before they are cut
+        assert_eq!(starts("אבגדהו"), vec![3, 2, 1]);
+    }
+}
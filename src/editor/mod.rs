@@ -1,12 +1,56 @@
-use std::{env, path::PathBuf};
+use std::{
+    env, fs, mem,
+    path::{Path, PathBuf},
+    process,
+    time::{Duration, Instant},
+};
 
-use sdl2::keyboard::{Keycode, Mod};
 
+pub mod brackets;
 pub mod buffer;
+pub mod command;
+pub mod comment;
+pub mod editorconfig;
+pub mod event;
+pub mod fold;
+pub mod indent;
+pub mod input;
+pub mod keymap;
+pub mod largefile;
+pub mod layout;
+pub mod motion;
+pub mod plugin;
+pub mod script;
+pub mod session;
+pub mod surround;
+pub mod swap;
+pub mod syntax;
+pub mod textobject;
+pub mod watch;
 
-use buffer::Buffers;
+#[cfg(test)]
+mod behaviour;
+#[cfg(test)]
+mod harness;
+#[cfg(test)]
+mod testdir;
 
-#[derive(Eq, PartialEq)]
+use buffer::{Buffer, Buffers, Cursor};
+use command::Command;
+use event::EditorEvent;
+use input::{InputEvent, Key, KeyEvent};
+use keymap::{Keymap, Lookup, MapMode, Mapping, MAX_MAP_DEPTH};
+use largefile::LOAD_AHEAD;
+use plugin::EditorPlugin;
+use script::Script;
+use session::{BufferState, Session, ViewState, DEFAULT_SESSION};
+use swap::{SwapData, SwapOwner};
+use watch::{same_file, FileWatcher};
+use layout::{DisplayRow, Segment, Viewport, Wrap};
+
+use crate::configuration::{Config, CONFIG};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Mode {
     Normal,
     Insert,
@@ -16,6 +60,19 @@ pub enum Mode {
     Command,
 }
 
+impl Mode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "normal",
+            Mode::Insert => "insert",
+            Mode::Visual => "visual",
+            Mode::VisualLine => "visual_line",
+            Mode::Replace => "replace",
+            Mode::Command => "command",
+        }
+    }
+}
+
 pub struct Editor {
     working_dir: PathBuf,
 
@@ -24,6 +81,64 @@ pub struct Editor {
 
     yank_buffer: String,
     insert_buffer: String,
+
+    // keys of an incomplete normal mode sequence such as `>>`
+    pending_keys: String,
+
+    // text typed after : and earlier commands, browsed with up and down
+    command_line: String,
+    command_history: Vec<String>,
+    history_pos: Option<usize>,
+
+    // soft wrap, width follows the window unless set in the config
+    wrap: Option<Wrap>,
+    wrap_at_window: bool,
+    // visible columns when not wrapping
+    viewport: Viewport,
+
+    watcher: FileWatcher,
+    // files the watcher reported that wait for the open prompt to be answered
+    changed_files: Vec<PathBuf>,
+    // a question the next keys answer
+    prompt: Option<Prompt>,
+    // shown until the next key
+    message: Option<String>,
+
+    // swap files of modified buffers are written every swap_interval
+    last_swap: Instant,
+    swap_interval: Option<Duration>,
+
+    // camera and window as last reported by main, saved with sessions
+    view: Option<ViewState>,
+
+    // main leaves its loop once set
+    quit: bool,
+
+    keymap: Keymap,
+    // keys typed so far that may still become a mapping, waiting up to map_timeout
+    typed_keys: Vec<String>,
+    typed_at: Instant,
+    map_timeout: Duration,
+    // the key just pressed did something, the text it typed is not for the buffer
+    swallow_text: bool,
+
+    // None while a script runs
+    script: Option<Script>,
+    // extensions compiled in, main registers them
+    plugins: Vec<Box<dyn EditorPlugin>>,
+    // what scripts last heard about, to tell them when it changes
+    seen_mode: Mode,
+    seen_revision: (usize, u64),
+}
+
+// questions waiting for a key, the message shows the choices
+enum Prompt {
+    // the file of a modified buffer changed on disk, by index into the buffers
+    Reload(usize),
+    // a swap file left behind by a crash holds changes the file of a buffer does not
+    Recover(usize, SwapData),
+    // the window was closed with modified buffers
+    Quit,
 }
 
 impl Editor {
@@ -34,6 +149,72 @@ impl Editor {
         } else {
             buffers = Buffers::from_file(file_path);
         }
+        Editor::with_buffers(buffers)
+    }
+
+    // reopens the buffers of a session with their cursors, registers and history
+    pub fn from_session(session: &Session) -> Self {
+        let paths: Vec<&str> = session.buffers.iter().map(|buf| buf.path.as_str()).collect();
+        let mut editor = Editor::with_buffers(Buffers::from_files(&paths, session.current));
+        editor.restore(session);
+        editor
+    }
+
+    // puts back what a session has besides its files, the buffers are the session's in order
+    pub fn restore(&mut self, session: &Session) {
+        for (buf, state) in self.buffers.iter_mut().zip(&session.buffers) {
+            buf.restore_cursor(state.cursor);
+        }
+        for (name, content) in &session.registers {
+            if *name == '"' {
+                self.yank_buffer = content.clone();
+            }
+        }
+        self.command_history = session.command_history.clone();
+        self.view = session.view;
+    }
+
+    fn with_buffers(buffers: Buffers) -> Self {
+        let default;
+        let config = match CONFIG.get() {
+            Some(config) => config,
+            None => {
+                default = Config::default();
+                &default
+            }
+        };
+        let watcher = match config.general.poll_files {
+            true => FileWatcher::polling(),
+            false => FileWatcher::new(),
+        };
+        let mut editor = Editor::build(buffers, config, FileWatcher::none());
+        editor.set_watcher(watcher);
+        editor.open_swaps();
+        editor.load_init_script();
+        editor.emit(EditorEvent::Startup);
+        editor
+    }
+
+    /*
+    * an editor that only knows the config it is given. no init script runs, no files are
+    * watched and no swap file is looked for, so nothing outside of it changes what it does
+    */
+    pub fn detached(buffers: Buffers, config: &Config) -> Self {
+        Editor::build(buffers, config, FileWatcher::none())
+    }
+
+    // watches the files of every buffer with watcher from now on
+    pub fn set_watcher(&mut self, watcher: FileWatcher) {
+        self.watcher = watcher;
+        for buf in self.buffers.iter() {
+            self.watcher.watch(buf.file_path());
+        }
+    }
+
+    fn build(buffers: Buffers, config: &Config, watcher: FileWatcher) -> Self {
+        let swap_interval = config.general.swap_interval_ms;
+        let (keymap, map_timeout) = keymap_from_config(config);
+        let seen_revision = (buffers.current_index(), buffers.curr_buffer().revision());
         Editor {
             // TODO handle possible error
             working_dir: env::current_dir().unwrap(),
@@ -43,97 +224,213 @@ impl Editor {
 
             yank_buffer: String::from(""),
             insert_buffer: String::from(""),
+
+            pending_keys: String::new(),
+
+            command_line: String::new(),
+            command_history: Vec::new(),
+            history_pos: None,
+
+            wrap: config.wrap.enabled.then(|| wrap_from_config(config)),
+            wrap_at_window: config.wrap.width == 0,
+            viewport: viewport_from_config(config),
+
+            watcher,
+            changed_files: Vec::new(),
+            prompt: None,
+            message: None,
+
+            last_swap: Instant::now(),
+            swap_interval: (swap_interval > 0).then(|| Duration::from_millis(swap_interval)),
+
+            view: None,
+
+            quit: false,
+
+            keymap,
+            typed_keys: Vec::new(),
+            typed_at: Instant::now(),
+            map_timeout,
+            swallow_text: false,
+
+            script: Some(Script::new()),
+            plugins: Vec::new(),
+            seen_mode: Mode::Normal,
+            seen_revision,
         }
     }
 
-    fn handle_keypress_normal(&mut self, keycode: Keycode, keymod: Mod, skip_events: &mut bool) {
+    /*
+    * runs the pending normal mode sequence once it is complete. returns false when the keys
+    * are not a sequence, so a key typed after a count still runs as a plain command
+    */
+    fn handle_pending_normal(&mut self) -> bool {
         let buf = self.buffers.curr_buffer_mut();
-        match keycode {
-            Keycode::Return => {
-                buf.insert_lines(1, true);
+        let wrap = active_wrap(&self.wrap, buf);
+        let (count, keys) = motion::split_count(&self.pending_keys);
+        // counts past isize::MAX would turn negative as offsets
+        let n = count.unwrap_or(1).min(isize::MAX as usize);
+        match keys {
+            // only a count so far
+            "" => return true,
+            "dd" => {
+                // there is nothing left to delete after the line count
+                for _ in 0..n.min(buf.line_count()) {
+                    buf.delete_line_curr();
+                }
             }
-            Keycode::Up => {
-                buf.move_cursor_by(0, -1, false);
+            ">>" | "<<" => {
+                let amount = if keys == ">>" { 1 } else { -1 };
+                let first = buf.line_pos() - 1;
+                for idx in first..first.saturating_add(n).min(buf.line_count()) {
+                    buf.shift_line(idx, amount);
+                }
+                buf.move_cursor_to_first_non_blank();
             }
-            Keycode::Down => {
-                buf.move_cursor_by(0, 1, false);
+            "h" => buf.move_cursor_by(-(n as isize), 0, true),
+            "l" => buf.move_cursor_by(n as isize, 0, true),
+            "j" => buf.move_cursor_by(0, n as isize, false),
+            "k" => buf.move_cursor_by(0, -(n as isize), false),
+            "x" => {
+                // a count reaching past the end of the line stops there
+                let pos = buf.char_pos();
+                let end = buf.curr_line().cluster_offset(pos, n as isize);
+                buf.delete_text(pos..end);
+                buf.move_cursor_by(0, 0, true);
             }
-            Keycode::Right => {
-                buf.move_cursor_by(1, 0, true);
+            "gj" => buf.move_cursor_display_by(wrap, n as isize),
+            "gk" => buf.move_cursor_display_by(wrap, -(n as isize)),
+            "g0" => buf.move_cursor_display_edge(wrap, false),
+            "g$" => buf.move_cursor_display_edge(wrap, true),
+            "G" | "gg" => {
+                let target = buf.jump_target(count, keys == "G");
+                buf.move_cursor_line_to(target);
+                buf.move_cursor_to_first_non_blank();
             }
-            Keycode::Left => {
-                buf.move_cursor_by(-1, 0, true);
+            _ if keys.starts_with("gc") => match motion::line_range(buf, &keys[2..], 'c', n) {
+                Ok((first, last)) => {
+                    buf.toggle_comment(first, last);
+                    buf.move_cursor_line_to(first);
+                    buf.move_cursor_by(0, 0, false);
+                }
+                Err(motion::Status::Pending) => return true,
+                Err(_) => (),
+            },
+            "zo" => buf.open_fold(),
+            "zc" => buf.close_fold(),
+            "za" => buf.toggle_fold(),
+            "zR" => buf.set_all_folds(false),
+            "zM" => buf.set_all_folds(true),
+            "zF" => buf.create_fold(buf.line_pos(), buf.line_pos().saturating_add(n) - 1),
+            _ if keys.starts_with("zf") => match motion::line_range(buf, &keys[2..], 'f', n) {
+                Ok((first, last)) => buf.create_fold(first, last),
+                Err(motion::Status::Pending) => return true,
+                Err(_) => (),
+            },
+            _ if keys.starts_with("ys") || keys.starts_with("cs") || keys.starts_with("ds") => {
+                if surround::apply(buf, keys) == motion::Status::Pending {
+                    return true;
+                }
             }
-            Keycode::A => {
-                self.mode = Mode::Insert;
-                buf.editor_mode = Mode::Insert;
-                *skip_events = true;
-                buf.move_cursor_by(1, 0, true);
+            // wait for the rest of the sequence
+            ">" | "<" | "d" | "y" | "c" | "g" | "z" => return true,
+            _ if count.is_some() && keys.chars().count() == 1 => {
+                self.pending_keys.clear();
+                return false;
             }
-            Keycode::D => {
-                if buf.line_count() == 1 {
-                    buf.delete_text(0..);
-                    buf.move_cursor_by(0, 0, true);
-                } else {
-                    buf.delete_line_curr();
+            _ => (),
+        }
+        self.pending_keys.clear();
+        true
+    }
+
+    fn handle_keypress_normal(&mut self, event: KeyEvent) {
+        if event.key == Key::Escape {
+            self.pending_keys.clear();
+            return;
+        }
+        if let Some(c) = event.char() {
+            let starts_count = c.is_ascii_digit() && c != '0';
+            if !self.pending_keys.is_empty() || SEQUENCE_KEYS.contains(c) || starts_count {
+                self.pending_keys.push(c);
+                if self.handle_pending_normal() {
+                    return;
                 }
             }
-            Keycode::H => {
-                buf.move_cursor_by(-1, 0, true);
+        }
+
+        let buf = self.buffers.curr_buffer_mut();
+        let Some(c) = event.char() else {
+            match event.key {
+                Key::Return => buf.insert_lines(1, true),
+                Key::Up => buf.move_cursor_by(0, -1, false),
+                Key::Down => buf.move_cursor_by(0, 1, false),
+                Key::Right => buf.move_cursor_by(1, 0, true),
+                Key::Left => buf.move_cursor_by(-1, 0, true),
+                _ => (),
+            }
+            return;
+        };
+        match c {
+            ':' => {
+                self.mode = Mode::Command;
+                self.command_line.clear();
+                self.history_pos = None;
+                self.swallow_text = true;
             }
-            Keycode::I => {
+            'a' => {
                 self.mode = Mode::Insert;
                 buf.editor_mode = Mode::Insert;
-                *skip_events = true;
-            }
-            Keycode::J => {
-                buf.move_cursor_by(0, 1, false);
-            }
-            Keycode::K => {
-                buf.move_cursor_by(0, -1, false);
-            }
-            Keycode::L => {
+                self.swallow_text = true;
                 buf.move_cursor_by(1, 0, true);
             }
-            Keycode::O => {
-                if is_upper(keymod) {
-                    self.mode = Mode::Insert;
-                    buf.editor_mode = Mode::Insert;
-                    *skip_events = true;
-                    buf.insert_lines_above(1, true);
-                }
-                if is_lower(keymod) {
-                    self.mode = Mode::Insert;
-                    buf.editor_mode = Mode::Insert;
-                    *skip_events = true;
-                    buf.insert_lines(1, true);
+            'h' => buf.move_cursor_by(-1, 0, true),
+            'i' => {
+                self.mode = Mode::Insert;
+                buf.editor_mode = Mode::Insert;
+                self.swallow_text = true;
+            }
+            'j' => buf.move_cursor_by(0, 1, false),
+            'k' => buf.move_cursor_by(0, -1, false),
+            'l' => buf.move_cursor_by(1, 0, true),
+            'O' | 'o' => {
+                self.mode = Mode::Insert;
+                buf.editor_mode = Mode::Insert;
+                self.swallow_text = true;
+                if c == 'O' {
+                    buf.open_line_above();
+                } else {
+                    buf.open_line_below();
                 }
             }
-            Keycode::X => {
+            'x' => {
                 buf.delete_char_cursor();
                 buf.move_cursor_by(0, 0, true); // update cursor keep in bounds
             }
-            Keycode::Num0 => {
-                buf.move_cursor_char_to(0);
-            }
-            Keycode::Num4 => {
-                if (keymod & (Mod::LSHIFTMOD | Mod::RSHIFTMOD)).bits() != 0 {
-                    buf.move_cursor_to_last_char();
-                }
+            'v' | 'V' => {
+                self.mode = if c == 'V' { Mode::VisualLine } else { Mode::Visual };
+                buf.editor_mode = if c == 'V' { Mode::VisualLine } else { Mode::Visual };
+                buf.start_selection();
             }
+            '0' => buf.move_cursor_char_to(0),
+            '$' => buf.move_cursor_to_last_char(),
+            '%' => buf.jump_to_matching_bracket(),
             _ => (),
         }
     }
 
-    fn handle_keypress_insert(&mut self, keycode: Keycode, keymod: Mod, skip_events: &mut bool) {
+    fn handle_keypress_insert(&mut self, event: KeyEvent) {
         let buf = self.buffers.curr_buffer_mut();
-        match keycode {
-            Keycode::Escape => {
+        match event.key {
+            Key::Escape => {
                 self.mode = Mode::Normal;
                 buf.editor_mode = Mode::Normal;
                 buf.move_cursor_by(-1, 0, true);
             }
-            Keycode::Backspace => {
+            Key::Backspace => {
+                if buf.delete_pair() {
+                    return;
+                }
                 if buf.char_pos() == 0 {
                     if buf.curr_line_offset_mut(-1).is_some() {
                         buf.move_cursor_by(0, -1, true);
@@ -146,41 +443,435 @@ impl Editor {
                     buf.delete_char_cursor();
                 }
             }
-            Keycode::Return => {
-                buf.split_line_below();
-                buf.move_cursor_by(0, 1, true);
-                buf.move_cursor_char_to(0);
+            Key::Return => buf.newline_indented(),
+            Key::Up => buf.move_cursor_by(0, -1, false),
+            Key::Down => buf.move_cursor_by(0, 1, false),
+            Key::Right => buf.move_cursor_by(1, 0, true),
+            Key::Left => buf.move_cursor_by(-1, 0, true),
+            Key::Tab => buf.insert_tab(),
+            _ if event == KeyEvent::ctrl('t') => buf.shift_line(buf.line_pos() - 1, 1),
+            _ if event == KeyEvent::ctrl('d') => buf.shift_line(buf.line_pos() - 1, -1),
+            _ => (),
+        }
+    }
+
+    fn exit_visual(&mut self) {
+        self.mode = Mode::Normal;
+        let buf = self.buffers.curr_buffer_mut();
+        buf.editor_mode = Mode::Normal;
+        buf.move_cursor_by(0, 0, true);
+        self.pending_keys.clear();
+    }
+
+    fn handle_keypress_visual(&mut self, event: KeyEvent) {
+        let linewise = self.mode == Mode::VisualLine;
+        let buf = self.buffers.curr_buffer_mut();
+        let wrap = active_wrap(&self.wrap, buf);
+        match event.key {
+            Key::Escape => return self.exit_visual(),
+            Key::Up => return buf.move_cursor_by(0, -1, false),
+            Key::Down => return buf.move_cursor_by(0, 1, false),
+            Key::Right => return buf.move_cursor_by(1, 0, true),
+            Key::Left => return buf.move_cursor_by(-1, 0, true),
+            _ => (),
+        }
+        let Some(c) = event.char() else {
+            return;
+        };
+
+        self.pending_keys.push(c);
+        let (count, keys) = motion::split_count(&self.pending_keys);
+        // counts past isize::MAX would turn negative
+        let n = count.unwrap_or(1).min(isize::MAX as usize) as isize;
+        match keys {
+            "" | "g" | "z" => return,
+            "h" => buf.move_cursor_by(-n, 0, true),
+            "l" => buf.move_cursor_by(n, 0, true),
+            "j" => buf.move_cursor_by(0, n, false),
+            "k" => buf.move_cursor_by(0, -n, false),
+            "0" => buf.move_cursor_char_to(0),
+            "$" => buf.move_cursor_to_last_char(),
+            "gj" => buf.move_cursor_display_by(wrap, n),
+            "gk" => buf.move_cursor_display_by(wrap, -n),
+            "g0" => buf.move_cursor_display_edge(wrap, false),
+            "g$" => buf.move_cursor_display_edge(wrap, true),
+            "G" | "gg" => {
+                let target = buf.jump_target(count, keys == "G");
+                buf.move_cursor_line_to(target);
+                buf.move_cursor_by(0, 0, false);
             }
-            Keycode::Up => {
-                buf.move_cursor_by(0, -1, false);
+            "gc" => {
+                let (first, last) = buf.selected_lines();
+                buf.toggle_comment(first, last);
+                buf.move_cursor_line_to(first);
+                return self.exit_visual();
             }
-            Keycode::Down => {
-                buf.move_cursor_by(0, 1, false);
+            "zf" => {
+                let (first, last) = buf.selected_lines();
+                buf.create_fold(first, last);
+                return self.exit_visual();
             }
-            Keycode::Right => {
-                buf.move_cursor_by(1, 0, true);
+            ">" | "<" => {
+                let (first, last) = buf.selected_lines();
+                for idx in first - 1..last {
+                    buf.shift_line(idx, if keys == ">" { n } else { -n });
+                }
+                buf.move_cursor_line_to(first);
+                buf.move_cursor_to_first_non_blank();
+                return self.exit_visual();
             }
-            Keycode::Left => {
-                buf.move_cursor_by(-1, 0, true);
+            "d" | "x" => {
+                buf.delete_selection(linewise);
+                return self.exit_visual();
+            }
+            "v" | "V" => {
+                let mode = if keys == "V" { Mode::VisualLine } else { Mode::Visual };
+                if self.mode == mode {
+                    return self.exit_visual();
+                }
+                buf.editor_mode = if keys == "V" { Mode::VisualLine } else { Mode::Visual };
+                self.mode = mode;
+            }
+            _ => (),
+        }
+        self.pending_keys.clear();
+    }
+
+    fn handle_keypress_command(&mut self, key: Key) {
+        match key {
+            Key::Escape => self.mode = Mode::Normal,
+            Key::Return => {
+                self.mode = Mode::Normal;
+                let line = mem::take(&mut self.command_line);
+                if line.trim().is_empty() {
+                    return;
+                }
+                self.command_history.retain(|old| *old != line);
+                self.command_history.push(line.clone());
+                self.run_command_line(&line);
             }
-            Keycode::Tab => {
-                buf.insert_text("    ", true);
+            Key::Backspace => {
+                // backspace on an empty line leaves command mode like in vim
+                if self.command_line.pop().is_none() {
+                    self.mode = Mode::Normal;
+                }
+            }
+            Key::Up | Key::Down => {
+                let len = self.command_history.len();
+                self.history_pos = match (key, self.history_pos) {
+                    (Key::Up, None) => len.checked_sub(1),
+                    (Key::Up, Some(pos)) => Some(pos.saturating_sub(1)),
+                    (_, Some(pos)) if pos + 1 < len => Some(pos + 1),
+                    _ => None,
+                };
+                self.command_line = self.history_pos.map_or(String::new(), |pos| self.command_history[pos].clone());
             }
             _ => (),
         }
     }
 
-    pub fn handle_keypress(&mut self, keycode: Keycode, keymod: Mod, skip_events: &mut bool) {
+    // commands of plugins and then scripts come after the built in ones
+    fn run_command_line(&mut self, line: &str) {
+        let err = match command::parse(line) {
+            Ok(command) => return self.execute(command),
+            Err(err) => err,
+        };
+        let line = line.trim();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if !self.with_plugins(|plugin, editor| plugin.on_command(editor, name, args.trim()))
+            && !self.run_script_command(line)
+        {
+            self.message = Some(err);
+        }
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::MkSession(path) => {
+                let path = path.unwrap_or(DEFAULT_SESSION.to_string());
+                self.message = Some(match self.session().save(&path) {
+                    Ok(()) => format!("session written to {}", path),
+                    Err(err) => format!("could not write session {}: {}", path, err),
+                });
+            }
+            Command::Write { all } => {
+                if let Err(err) = self.write_buffers(all) {
+                    self.message = Some(err);
+                }
+            }
+            Command::Quit { force } => {
+                let modified = self.modified_buffers();
+                if force || modified.is_empty() {
+                    self.quit = true;
+                } else {
+                    self.message = Some(format!("no write since last change for {} (add ! to override)", modified.join(", ")));
+                }
+            }
+            Command::WriteQuit { all } => match self.write_buffers(all) {
+                Ok(()) => self.execute(Command::Quit { force: false }),
+                Err(err) => self.message = Some(err),
+            },
+            Command::Map { command, lhs, rhs } => {
+                self.message = match self.keymap.map(&command, lhs.as_deref(), rhs.as_deref()) {
+                    Ok(listing) => listing,
+                    Err(err) => Some(err),
+                };
+            }
+            Command::Unmap { command, lhs } => {
+                if let Err(err) = self.keymap.unmap(&command, &lhs) {
+                    self.message = Some(err);
+                }
+            }
+            Command::Lua(code) => self.run_lua(&code),
+            Command::LuaFile(path) => self.run_lua_file(Path::new(&path)),
+        }
+    }
+
+    // writes the current buffer, or every modified one with all
+    fn write_buffers(&mut self, all: bool) -> Result<(), String> {
+        let current = self.buffers.current_index();
+        let mut written = Vec::new();
+        let mut result = Ok(());
+        for (idx, buf) in self.buffers.iter_mut().enumerate() {
+            let wanted = if all { buf.is_modified() } else { idx == current };
+            if !wanted {
+                continue;
+            }
+            if let Err(err) = buf.write_file() {
+                result = Err(format!("could not write \"{}\": {}", buf.file_path(), err));
+                break;
+            }
+            written.push(buf.file_path().to_string());
+        }
+        for path in written {
+            self.emit(EditorEvent::Write(path));
+        }
+        result
+    }
+
+    // names of the buffers with unwritten changes
+    fn modified_buffers(&self) -> Vec<String> {
+        self.buffers
+            .iter()
+            .filter(|buf| buf.is_modified())
+            .map(|buf| match buf.file_path() {
+                "" => String::from("[No Name]"),
+                path => format!("\"{}\"", path),
+            })
+            .collect()
+    }
+
+    // closing the window asks first when there are unwritten changes
+    pub fn request_quit(&mut self) {
+        let modified = self.modified_buffers();
+        if modified.is_empty() {
+            self.quit = true;
+            return;
+        }
+        self.prompt = Some(Prompt::Quit);
+        self.message = Some(format!("{} {}", modified.join(", "), QUIT_PROMPT));
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.quit
+    }
+
+    // answers to the open prompt, any other key leaves the question open
+    fn handle_prompt(&mut self, event: KeyEvent) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match (prompt, event.char()) {
+            (Prompt::Reload(idx), Some('r')) => {
+                let buf = self.buffers.get_mut(idx);
+                buf.reload();
+                self.message = Some(format!("\"{}\" reloaded", buf.file_path()));
+            }
+            (Prompt::Reload(idx), Some('k')) => {
+                self.buffers.get_mut(idx).keep_ours();
+                self.message = None;
+            }
+            (Prompt::Reload(idx), Some('d')) => {
+                let buf = self.buffers.get_mut(idx);
+                self.message = Some(format!("\"{}\" {}\n{}", buf.file_path(), RELOAD_PROMPT, buf.disk_diff()));
+                self.prompt = Some(Prompt::Reload(idx));
+            }
+            (Prompt::Recover(idx, swap), Some('r')) => {
+                let buf = self.buffers.get_mut(idx);
+                buf.recover(swap);
+                self.message = Some(format!("\"{}\" recovered from swap, write it to keep the changes", buf.file_path()));
+                self.write_swap(idx);
+                self.open_swaps();
+            }
+            (Prompt::Recover(idx, _), Some('e')) => {
+                self.message = None;
+                self.write_swap(idx);
+                self.open_swaps();
+            }
+            (Prompt::Recover(idx, swap), Some('d')) => {
+                let buf = self.buffers.get_mut(idx);
+                self.message = Some(format!("\"{}\" {}\n{}", buf.file_path(), RECOVER_PROMPT, buf.swap_diff(&swap)));
+                self.prompt = Some(Prompt::Recover(idx, swap));
+            }
+            (Prompt::Quit, Some('w')) => {
+                self.message = None;
+                self.execute(Command::WriteQuit { all: true });
+            }
+            (Prompt::Quit, Some('d')) => self.quit = true,
+            (Prompt::Quit, _) if event.key == Key::Escape => self.message = None,
+            (Prompt::Quit, Some('c')) => self.message = None,
+            (prompt, _) => self.prompt = Some(prompt),
+        }
+    }
+
+    pub fn handle_input(&mut self, input: InputEvent) {
+        match input {
+            InputEvent::Key(event) => self.handle_keypress(event),
+            // the text of a key that did something else
+            InputEvent::Text(_) if self.swallow_text => self.swallow_text = false,
+            InputEvent::Text(text) => self.handle_text_input(&text),
+        }
+    }
+
+    pub fn handle_keypress(&mut self, event: KeyEvent) {
+        self.swallow_text = false;
+        if self.prompt.is_some() {
+            // the text input of the answer should not reach the buffer
+            self.swallow_text = true;
+            return self.handle_prompt(event);
+        }
+        self.message = None;
+        let token = keymap::key_token(event);
+        if self.with_plugins(|plugin, editor| plugin.on_key(editor, &token)) {
+            self.swallow_text = true;
+            return;
+        }
+        self.emit(EditorEvent::Key(token.clone()));
+        match self.map_mode() {
+            Some(_) => {
+                self.typed_keys.push(token);
+                self.resolve_typed(Some(event));
+            }
+            None => self.dispatch_key(event),
+        }
+    }
+
+    // mappings of the current mode, normal mode ones do not apply in the middle of a sequence
+    fn map_mode(&self) -> Option<MapMode> {
+        match self.mode {
+            Mode::Normal if motion::split_count(&self.pending_keys).1.is_empty() => Some(MapMode::Normal),
+            Mode::Insert => Some(MapMode::Insert),
+            Mode::Visual | Mode::VisualLine => Some(MapMode::Visual),
+            Mode::Command => Some(MapMode::Command),
+            _ => None,
+        }
+    }
+
+    /*
+    * matches the typed keys against the mappings. live is the key being pressed right now, it
+    * waits for more keys while it may start a mapping. keys that turn out not to be mapped run
+    * as typed, after a timeout (live is None) nothing waits any longer
+    */
+    fn resolve_typed(&mut self, live: Option<KeyEvent>) {
+        while !self.typed_keys.is_empty() {
+            let lookup = match self.map_mode() {
+                Some(mode) => self.keymap.lookup(mode, &self.typed_keys),
+                None => Lookup::None,
+            };
+            match lookup {
+                Lookup::Prefix | Lookup::ExactAndPrefix(_) if live.is_some() => {
+                    // the text input of a key that may be mapped waits with it
+                    self.swallow_text = true;
+                    self.typed_at = Instant::now();
+                    return;
+                }
+                Lookup::Exact(map) | Lookup::ExactAndPrefix(map) => {
+                    let map = map.clone();
+                    self.typed_keys.clear();
+                    self.run_mapping(&map, 0);
+                    self.swallow_text = live.is_some();
+                    return;
+                }
+                Lookup::Prefix | Lookup::None => {
+                    // the first key is not mapped, the ones after it may still start a mapping
+                    let key = self.typed_keys.remove(0);
+                    match live {
+                        Some(event) if self.typed_keys.is_empty() => self.dispatch_key(event),
+                        _ => self.send_key(&key),
+                    }
+                }
+            }
+        }
+    }
+
+    // runs the rhs of a mapping, through the other mappings unless it is noremap
+    fn run_mapping(&mut self, map: &Mapping, depth: usize) {
+        if map.noremap {
+            for key in &map.rhs {
+                self.send_key(key);
+            }
+            return;
+        }
+        if depth >= MAX_MAP_DEPTH {
+            self.message = Some(format!("recursive mapping: {}", map.lhs.concat()));
+            return;
+        }
+
+        let mut idx = 0;
+        while idx < map.rhs.len() {
+            // like vim an rhs starting with its own lhs does not map that first key again, so
+            // `nmap j jzz` is not a loop
+            let own_lhs = idx == 0 && map.rhs.starts_with(&map.lhs);
+            let found = match self.map_mode() {
+                Some(mode) if !own_lhs => self.keymap.longest_match(mode, &map.rhs[idx..]).cloned(),
+                _ => None,
+            };
+            match found {
+                Some(inner) => {
+                    idx += inner.lhs.len();
+                    self.run_mapping(&inner, depth + 1);
+                }
+                None => {
+                    self.send_key(&map.rhs[idx]);
+                    idx += 1;
+                }
+            }
+        }
+    }
+
+    // presses a key as if typed, followed by its text input unless the key swallowed it
+    fn send_key(&mut self, token: &str) {
+        let Some(event) = keymap::token_key(token) else {
+            return;
+        };
+        let swallowed = mem::replace(&mut self.swallow_text, false);
+        self.dispatch_key(event);
+        if let Some(text) = keymap::token_text(token).filter(|_| !self.swallow_text) {
+            self.handle_text_input(&text);
+        }
+        self.swallow_text = swallowed;
+    }
+
+    fn dispatch_key(&mut self, event: KeyEvent) {
         match self.mode {
-            Mode::Normal => self.handle_keypress_normal(keycode, keymod, skip_events),
-            Mode::Insert => self.handle_keypress_insert(keycode, keymod, skip_events),
+            Mode::Normal => self.handle_keypress_normal(event),
+            Mode::Insert => self.handle_keypress_insert(event),
+            Mode::Visual | Mode::VisualLine => self.handle_keypress_visual(event),
+            Mode::Command => self.handle_keypress_command(event.key),
             _ => (),
         }
     }
 
     pub fn handle_text_input(&mut self, text: &str) {
         if self.mode == Mode::Insert {
-            self.buffers.curr_buffer_mut().insert_text(text, true);
+            let buf = self.buffers.curr_buffer_mut();
+            buf.dedent_for_closer(text);
+            if !buf.insert_with_pairs(text) {
+                buf.insert_text(text, true);
+            }
+        }
+        if self.mode == Mode::Command {
+            self.command_line.push_str(text);
         }
         // if self.mode == Mode::Replace {
         //     self.buffers.curr_buffer_mut().replace_text(text);
@@ -201,37 +892,424 @@ impl Editor {
         let c = self.buffers.curr_buffer().cursor();
         (c.line_pos as u32, c.char_pos as u32)
     }
-}
 
-// false does not mean uppercase
-fn is_lower(keymod: Mod) -> bool {
-    ((keymod.intersects(Mod::CAPSMOD)) && is_shift(keymod - Mod::CAPSMOD)) || keymod == Mod::NOMOD
+    // screen row, x and width in columns of the bracket matching the one under the cursor
+    pub fn get_matching_bracket(&self) -> Option<(u32, f32, f32)> {
+        let buf = self.buffers.curr_buffer();
+        let pos = *buf.cursor();
+        let target = buf.matching_bracket_at(pos, false).or_else(|| {
+            // in insert mode the bracket just typed sits before the cursor
+            let before = pos.char_pos.checked_sub(1).filter(|_| self.mode == Mode::Insert)?;
+            buf.matching_bracket_at(Cursor { char_pos: before, ..pos }, false)
+        })?;
+        let (row, x) = buf.screen_pos(active_wrap(&self.wrap, buf), target.line_pos, target.char_pos)?;
+        let line = buf.get_line(target.line_pos - 1);
+        let bracket = line.content[line.offset_of(target.char_pos)..].chars().next().unwrap_or(' ');
+        Some((row as u32, x, buf.metrics().width(&bracket.to_string(), x)))
+    }
+
+    // selected spans as (screen row, first x, last x exclusive) in columns
+    pub fn get_selection(&self) -> Vec<(u32, f32, f32)> {
+        if self.mode != Mode::Visual && self.mode != Mode::VisualLine {
+            return Vec::new();
+        }
+        let buf = self.buffers.curr_buffer();
+        let metrics = buf.metrics();
+        let (start, end) = buf.selection();
+
+        // a large buffer has one row per line, no need to lay out all of it
+        let rows = match buf.is_large() {
+            true => (start.line_pos..=end.line_pos)
+                .map(|line| DisplayRow {
+                    line,
+                    segment: Segment {
+                        start: 0,
+                        end: buf.get_line(line - 1).len(),
+                        indent: 0,
+                    },
+                    fold_end: None,
+                })
+                .collect(),
+            false => buf.display_rows(self.wrap.as_ref()),
+        };
+        let first_row = if buf.is_large() { start.line_pos - 1 } else { 0 };
+
+        let mut spans = Vec::new();
+        for (row, display) in rows.iter().enumerate() {
+            let row = first_row + row;
+            let line_pos = display.line;
+            if line_pos < start.line_pos || line_pos > end.line_pos || display.fold_end.is_some() {
+                continue;
+            }
+            let content = &buf.get_line(line_pos - 1).content;
+            let len = buf.get_line(line_pos - 1).len();
+            let (first, last) = if self.mode == Mode::VisualLine {
+                (0, len)
+            } else {
+                let first = if line_pos == start.line_pos { start.char_pos } else { 0 };
+                let last = if line_pos == end.line_pos { end.char_pos + 1 } else { len };
+                (first, last.min(len))
+            };
+
+            let segment = &display.segment;
+            let (first, last) = (first.max(segment.start), last.min(segment.end));
+            if first >= last && !(len == 0 || first == segment.start) {
+                continue;
+            }
+            // right to left text can split the selection of a row into several spans
+            let mut row_spans = layout::spans(content, &metrics, segment, first, last);
+            if row_spans.is_empty() {
+                let x = layout::x_in_segment(content, &metrics, segment, first);
+                row_spans.push((x, x));
+            }
+            spans.extend(row_spans.into_iter().map(|(first_x, last_x)| (row as u32, first_x, last_x.max(first_x + 1.))));
+        }
+        spans
+    }
+
+    // closed folds as first and last line
+    pub fn get_closed_folds(&mut self) -> Vec<(u32, u32)> {
+        let buf = self.buffers.curr_buffer_mut();
+        buf.update_folds();
+        buf.closed_folds()
+            .into_iter()
+            .map(|(first, last)| (first as u32, last as u32))
+            .collect()
+    }
+
+    /*
+    * puts the cursor on the char drawn at a 0 indexed screen row and x in columns, where a mouse
+    * click landed. with a proportional font x is measured with the glyph advances
+    */
+    pub fn click(&mut self, row: usize, x: f32) {
+        if self.prompt.is_some() || self.mode == Mode::Command {
+            return;
+        }
+        self.pending_keys.clear();
+        let buf = self.buffers.curr_buffer_mut();
+        let wrap = active_wrap(&self.wrap, buf);
+        buf.move_cursor_to_screen(wrap, row, x);
+    }
+
+    // row the start of a line is drawn on, None if it is hidden in a closed fold
+    pub fn get_screen_row(&self, line: u32) -> Option<u32> {
+        let buf = self.buffers.curr_buffer();
+        buf.screen_pos(active_wrap(&self.wrap, buf), line as usize, 0).map(|(row, _)| row as u32)
+    }
+
+    pub fn get_wrap(&self) -> Option<&Wrap> {
+        active_wrap(&self.wrap, self.buffers.curr_buffer())
+    }
+
+    // glyph advances of the font the text is drawn with, none for a monospace font
+    pub fn set_advances(&mut self, advances: Option<layout::Advances>) {
+        for buf in self.buffers.iter_mut() {
+            buf.advances = advances.clone();
+        }
+    }
+
+    /*
+    * tells the editor how many columns fit in the window. wrapped lines break at the window
+    * width unless the config sets one, otherwise the view scrolls to keep the cursor visible
+    */
+    pub fn set_window_columns(&mut self, columns: usize) {
+        let large = self.buffers.curr_buffer().is_large();
+        match self.wrap.as_mut().filter(|_| !large) {
+            Some(wrap) if self.wrap_at_window => wrap.width = columns,
+            Some(_) => (),
+            None => {
+                self.viewport.width = columns;
+                // the whole cluster under the cursor is kept in view
+                let buf = self.buffers.curr_buffer();
+                let (x, width) = (buf.cursor_x(), buf.cursor_width());
+                self.viewport.scroll_to(x as usize, (x + width).ceil() as usize - x as usize);
+            }
+        }
+    }
+
+    // visible columns, None when lines wrap
+    pub fn get_viewport(&self) -> Option<&Viewport> {
+        match self.get_wrap() {
+            Some(_) => None,
+            None => Some(&self.viewport),
+        }
+    }
+
+    /*
+    * first line and text of the lines worth drawing. large buffers only hand out the lines
+    * around the cursor, everything else is drawn whole
+    */
+    pub fn get_text_window(&self) -> (u32, String) {
+        let buf = self.buffers.curr_buffer();
+        if !buf.is_large() {
+            return (1, self.get_text());
+        }
+        let first = buf.line_pos().saturating_sub(TEXT_WINDOW / 2).max(1);
+        let last = (first + TEXT_WINDOW).min(buf.line_count());
+        let text = (first..=last)
+            .map(|line| buf.get_line(line - 1).content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        (first as u32, text)
+    }
+
+    /*
+    * called every frame. keeps lines of a large buffer loaded ahead of the cursor, writes the
+    * swap file every so often and picks up changes other programs made to the file, reloading it
+    * unless the buffer has edits of its own
+    */
+    pub fn poll(&mut self) {
+        // a key that could have started a mapping ran out of time
+        if !self.typed_keys.is_empty() && self.typed_at.elapsed() >= self.map_timeout {
+            self.resolve_typed(None);
+        }
+
+        for path in self.watcher.changed() {
+            if !self.changed_files.contains(&path) {
+                self.changed_files.push(path);
+            }
+        }
+        let buf = self.buffers.curr_buffer_mut();
+        if buf.is_large() {
+            buf.load_lines_to(buf.line_pos() + LOAD_AHEAD);
+            buf.load_lines_before(LOAD_AHEAD.saturating_sub(buf.line_pos() - 1));
+        }
+        // only the lines edited since the last frame are parsed again
+        buf.update_syntax_mask();
+
+        if self.swap_interval.is_some_and(|interval| self.last_swap.elapsed() >= interval) {
+            for idx in 0..self.buffers.iter().count() {
+                let buf = self.buffers.get_mut(idx);
+                if buf.is_modified() && buf.swapped_revision != Some(buf.revision()) {
+                    self.write_swap(idx);
+                }
+            }
+            self.last_swap = Instant::now();
+        }
+
+        self.emit_changes();
+
+        // unmodified buffers reload right away, the first modified one asks and the rest wait for it
+        while self.prompt.is_none() && !self.changed_files.is_empty() {
+            let path = self.changed_files.remove(0);
+            for (idx, buf) in self.buffers.iter_mut().enumerate() {
+                if !same_file(&path, buf.file_path()) || !buf.changed_on_disk() {
+                    continue;
+                }
+                if buf.is_modified() {
+                    self.prompt = Some(Prompt::Reload(idx));
+                    self.message = Some(format!("\"{}\" {}", buf.file_path(), RELOAD_PROMPT));
+                } else {
+                    buf.reload();
+                    self.message = Some(format!("\"{}\" reloaded", buf.file_path()));
+                }
+            }
+        }
+    }
+
+    // lets scripts and plugins know about mode switches and edits since the last frame
+    fn emit_changes(&mut self) {
+        if self.mode != self.seen_mode {
+            self.seen_mode = self.mode;
+            self.emit(EditorEvent::ModeChanged(self.mode));
+        }
+        let revision = (self.buffers.current_index(), self.buffers.curr_buffer().revision());
+        if revision != self.seen_revision {
+            // switching buffers is not an edit
+            let edited = revision.0 == self.seen_revision.0;
+            self.seen_revision = revision;
+            if edited {
+                self.emit(EditorEvent::BufferChanged);
+            }
+        }
+    }
+
+    fn emit(&mut self, event: EditorEvent) {
+        if let EditorEvent::BufferChanged = event {
+            self.with_plugins(|plugin, editor| {
+                plugin.on_buffer_changed(editor);
+                false
+            });
+        }
+        self.emit_script(&event);
+    }
+
+    /*
+    * looks for the swap files of the buffers before they get their own. a swap of a running fim
+    * means someone else is editing the file, one left by a crash is offered for recovery, one
+    * buffer at a time. the editor does this on start, a detached one when told to
+    */
+    pub fn open_swaps(&mut self) {
+        while self.prompt.is_none() {
+            let Some(idx) = self.buffers.iter().position(|buf| buf.swap_owner == SwapOwner::Unchecked) else {
+                return;
+            };
+            let buf = self.buffers.get_mut(idx);
+            // large files would take too long to write out every few seconds
+            if self.swap_interval.is_none() || buf.file_path().is_empty() || buf.is_large() {
+                buf.swap_owner = SwapOwner::Skipped;
+                continue;
+            }
+            match swap::read(buf.file_path()) {
+                Some(swap) if swap.pid != process::id() && swap::is_running(swap.pid) => {
+                    buf.swap_owner = SwapOwner::Skipped;
+                    self.message = Some(format!("another fim (pid {}) is editing \"{}\", changes are not swapped", swap.pid, buf.file_path()));
+                }
+                Some(swap) if buf.differs_from(&swap) => {
+                    buf.swap_owner = SwapOwner::Ours;
+                    self.message = Some(format!("\"{}\" {}", buf.file_path(), RECOVER_PROMPT));
+                    self.prompt = Some(Prompt::Recover(idx, swap));
+                }
+                _ => {
+                    buf.swap_owner = SwapOwner::Ours;
+                    self.write_swap(idx);
+                }
+            }
+        }
+    }
+
+    fn write_swap(&mut self, idx: usize) {
+        // the swap file of a crash stays until the user decided what to do with it
+        if matches!(self.prompt, Some(Prompt::Recover(recovering, _)) if recovering == idx) {
+            return;
+        }
+        let buf = self.buffers.get_mut(idx);
+        if buf.swap_owner != SwapOwner::Ours {
+            return;
+        }
+        // a failed write is tried again on the next poll
+        match swap::write(buf) {
+            Ok(()) => buf.swapped_revision = Some(buf.revision()),
+            Err(err) => self.message = Some(format!("could not write swap file: {}", err)),
+        }
+    }
+
+    // removes the swap files on a clean exit
+    pub fn close(&mut self) {
+        for (idx, buf) in self.buffers.iter().enumerate() {
+            let recovering = matches!(self.prompt, Some(Prompt::Recover(recovering, _)) if recovering == idx);
+            if buf.swap_owner == SwapOwner::Ours && !recovering {
+                swap::remove(buf.file_path());
+            }
+        }
+    }
+
+    // camera and window of main, remembered for :mksession
+    pub fn set_view_state(&mut self, view: ViewState) {
+        self.view = Some(view);
+    }
+
+    // the open buffers and editor state as a session, paths made absolute
+    pub fn session(&self) -> Session {
+        let named = |buf: &&Buffer| !buf.file_path().is_empty();
+        let buffers = self
+            .buffers
+            .iter()
+            .filter(named)
+            .map(|buf| BufferState {
+                path: fs::canonicalize(buf.file_path())
+                    .map_or(buf.file_path().to_string(), |path| path.to_string_lossy().into_owned()),
+                cursor: *buf.cursor(),
+            })
+            .collect();
+        // buffers without a file are left out, the current one counts among the rest
+        let current = self.buffers.iter().take(self.buffers.current_index()).filter(named).count();
+        Session {
+            buffers,
+            current,
+            registers: vec![('"', self.yank_buffer.clone())],
+            command_history: self.command_history.clone(),
+            view: self.view,
+        }
+    }
+
+    // the : line while typing a command
+    pub fn get_command_line(&self) -> Option<String> {
+        (self.mode == Mode::Command).then(|| format!(":{}", self.command_line))
+    }
+
+    // text for the message area, several lines when showing a diff
+    pub fn set_message(&mut self, message: &str) {
+        self.message = Some(message.to_string());
+    }
+
+    pub fn get_message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    // rows the text of a large buffer moved down as lines before it were loaded, since last asked
+    pub fn take_rows_shifted(&mut self) -> usize {
+        self.buffers.curr_buffer_mut().take_rows_shifted()
+    }
+
+    // fraction of a large file indexed and lines found so far, None once indexing is done
+    pub fn get_load_progress(&self) -> Option<(f32, usize)> {
+        self.buffers.curr_buffer().load_progress()
+    }
+
+    pub fn get_line(&self, line: u32) -> &str {
+        &self.buffers.curr_buffer().get_line(line as usize - 1).content
+    }
+
+    pub fn get_mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn get_tabstop(&self) -> usize {
+        self.buffers.curr_buffer().settings.tabstop
+    }
 }
 
-// false does not mean lowercase
-fn is_upper(keymod: Mod) -> bool {
-    is_shift(keymod) || keymod == Mod::CAPSMOD
+const RELOAD_PROMPT: &str = "changed on disk: [r]eload, [k]eep ours, [d]iff";
+const QUIT_PROMPT: &str = "not written: [w]rite all and quit, [d]iscard and quit, [c]ancel";
+const RECOVER_PROMPT: &str = "has a swap file, fim may have crashed: [r]ecover, [e]dit anyway, [d]iff";
+
+// lines of a large buffer drawn around the cursor
+const TEXT_WINDOW: usize = 200;
+
+// large buffers are never wrapped, laying them out would mean reading all of them
+fn active_wrap<'a>(wrap: &'a Option<Wrap>, buf: &Buffer) -> Option<&'a Wrap> {
+    wrap.as_ref().filter(|_| !buf.is_large())
 }
 
-// only shift
-fn is_shift(keymod: Mod) -> bool {
-    keymod == Mod::LSHIFTMOD
-        || keymod == Mod::RSHIFTMOD
-        || keymod == (Mod::RSHIFTMOD | Mod::LSHIFTMOD)
+// the leader, the mappings of the config and how long to wait for a mapping to complete
+fn keymap_from_config(config: &Config) -> (Keymap, Duration) {
+    let mut keymap = Keymap::new(&config.keymap.leader);
+    for (command, lhs, rhs) in &config.keymap.maps {
+        if let Err(err) = keymap.map(command, Some(lhs), Some(rhs)) {
+            println!("Could not map {} {}: {}", command, lhs, err);
+        }
+    }
+    (keymap, Duration::from_millis(config.keymap.timeout_ms))
 }
 
-// only ctrl
-fn is_ctrl(keymod: Mod) -> bool {
-    keymod == Mod::LCTRLMOD || keymod == Mod::RCTRLMOD || keymod == (Mod::RCTRLMOD & Mod::LCTRLMOD)
+// how lines wrap once wrapping is on
+fn wrap_settings() -> Wrap {
+    match CONFIG.get() {
+        Some(config) => wrap_from_config(config),
+        None => wrap_from_config(&Config::default()),
+    }
 }
 
-// only alt
-fn is_alt(keymod: Mod) -> bool {
-    keymod == Mod::LALTMOD || keymod == Mod::RALTMOD || keymod == (Mod::RALTMOD & Mod::LALTMOD)
+fn wrap_from_config(config: &Config) -> Wrap {
+    Wrap {
+        width: config.wrap.width,
+        linebreak: config.wrap.linebreak,
+        breakindent: config.wrap.breakindent,
+        showbreak: config.wrap.showbreak.clone(),
+    }
 }
 
-// shift + ctrl only (no super alt or whatev)
-fn is_shift_ctrl(keymod: Mod) -> bool {
-    is_shift(keymod & !(Mod::LCTRLMOD | Mod::RCTRLMOD))
-        || is_ctrl(keymod & !(Mod::LSHIFTMOD | Mod::RSHIFTMOD))
+fn viewport_from_config(config: &Config) -> Viewport {
+    Viewport {
+        left: 0,
+        width: 0,
+        sidescroll: config.wrap.sidescroll,
+        sidescrolloff: config.wrap.sidescrolloff,
+        precedes: config.wrap.precedes.chars().next(),
+        extends: config.wrap.extends.chars().next(),
+    }
 }
+
+// keys that start a multi-key normal mode sequence
+const SEQUENCE_KEYS: &str = "<>dycgGz";
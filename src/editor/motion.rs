@@ -0,0 +1,59 @@
+use super::buffer::Buffer;
+
+// outcome of interpreting a partially typed key sequence
+#[derive(Debug, Eq, PartialEq)]
+pub enum Status {
+    Done,
+    Pending,
+    Invalid,
+}
+
+// splits a leading count off keys, e.g. "3gcc" -> (Some(3), "gcc")
+pub fn split_count(keys: &str) -> (Option<usize>, &str) {
+    let digits = keys.len() - keys.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    // a lone 0 is a motion, not a count
+    if digits == 0 || keys.starts_with('0') {
+        return (None, keys);
+    }
+    (keys[..digits].parse().ok(), &keys[digits..])
+}
+
+/*
+* resolves a linewise motion or text object typed after an operator, e.g. the "3j" of "gc3j".
+* the operator char itself repeated ("gcc") selects count lines. returns 1 indexed inclusive
+* first and last lines
+*/
+pub fn line_range(buf: &mut Buffer, keys: &str, operator: char, count: usize) -> Result<(usize, usize), Status> {
+    let (inner_count, keys) = split_count(keys);
+    let count = count.saturating_mul(inner_count.unwrap_or(1));
+    let line = buf.line_pos();
+    let last = buf.line_count();
+
+    let mut chars = keys.chars();
+    let range = match chars.next() {
+        None => return Err(Status::Pending),
+        Some(c) if c == operator => (line, (line.saturating_add(count) - 1).min(last)),
+        Some('j') => (line, line.saturating_add(count).min(last)),
+        Some('k') => (line.saturating_sub(count).max(1), line),
+        Some('G') => match inner_count {
+            Some(target) => (line.min(target.min(last)), line.max(target.min(last))),
+            None => (line, last),
+        },
+        Some('g') => match chars.next() {
+            None => return Err(Status::Pending),
+            Some('g') => (1, line),
+            Some(_) => return Err(Status::Invalid),
+        },
+        Some(c @ ('i' | 'a')) => {
+            let Some(object) = chars.next() else {
+                return Err(Status::Pending);
+            };
+            let range = buf
+                .text_object(&format!("{}{}", c, object))
+                .ok_or(Status::Invalid)?;
+            (range.start.line_pos, range.end.line_pos)
+        }
+        Some(_) => return Err(Status::Invalid),
+    };
+    Ok(range)
+}
@@ -0,0 +1,82 @@
+use std::mem;
+
+use glam::{Vec2, Vec3};
+
+use super::Editor;
+
+/*
+* an extension compiled into fim. every hook has a default that does nothing, so a plugin only
+* implements the ones it needs. hooks get the editor itself, the plugins are taken out of it
+* while they run
+*/
+pub trait EditorPlugin {
+    fn name(&self) -> &str;
+
+    // called once, when the plugin is registered
+    fn on_startup(&mut self, _editor: &mut Editor) {}
+
+    // a key in keymap notation before mappings see it, true if the plugin used it up
+    fn on_key(&mut self, _editor: &mut Editor, _key: &str) -> bool {
+        false
+    }
+
+    // the current buffer was edited, once a frame at most
+    fn on_buffer_changed(&mut self, _editor: &mut Editor) {}
+
+    // a : command the editor does not know, true if the plugin ran it
+    fn on_command(&mut self, _editor: &mut Editor, _name: &str, _args: &str) -> bool {
+        false
+    }
+
+    // called every frame before the renderers flush
+    fn draw(&mut self, _editor: &Editor, _canvas: &mut dyn Canvas) {}
+}
+
+/*
+* what plugins draw on. coordinates are those of the text, line 1 starts at y 0 and every row is
+* a row height lower. main draws it with its renderers
+*/
+pub trait Canvas {
+    // width of a column and height of a row
+    fn cell_size(&self) -> Vec2;
+
+    fn draw_line(&mut self, from: Vec2, to: Vec2, color: Vec3);
+
+    fn draw_quad(&mut self, corners: &[Vec2; 4], color: Vec3);
+
+    // a line of text with its baseline at y
+    fn draw_text(&mut self, x: f32, y: f32, text: &str);
+}
+
+impl Editor {
+    pub fn register_plugin(&mut self, mut plugin: Box<dyn EditorPlugin>) {
+        if self.plugins.iter().any(|other| other.name() == plugin.name()) {
+            println!("Plugin {} is already registered. Skipping!", plugin.name());
+            return;
+        }
+        plugin.on_startup(self);
+        self.plugins.push(plugin);
+    }
+
+    pub fn draw_plugins(&mut self, canvas: &mut dyn Canvas) {
+        let mut plugins = mem::take(&mut self.plugins);
+        for plugin in &mut plugins {
+            plugin.draw(self, canvas);
+        }
+        self.restore_plugins(plugins);
+    }
+
+    // asks each plugin in turn until one returns true
+    pub(super) fn with_plugins(&mut self, mut f: impl FnMut(&mut dyn EditorPlugin, &mut Editor) -> bool) -> bool {
+        let mut plugins = mem::take(&mut self.plugins);
+        let handled = plugins.iter_mut().any(|plugin| f(plugin.as_mut(), self));
+        self.restore_plugins(plugins);
+        handled
+    }
+
+    // plugins registered by a hook come after the ones that were running
+    fn restore_plugins(&mut self, mut plugins: Vec<Box<dyn EditorPlugin>>) {
+        plugins.append(&mut self.plugins);
+        self.plugins = plugins;
+    }
+}
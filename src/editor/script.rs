@@ -0,0 +1,290 @@
+use std::{cell::RefCell, fs, path::Path, time::Duration};
+
+use mlua::{FromLua, Function, IntoLua, Lua, Scope, Table, Value};
+
+use super::{event::EditorEvent, keymap, wrap_settings, Editor};
+use crate::configuration::config_dir;
+
+pub const INIT_SCRIPT: &str = "init.lua";
+
+/*
+* the parts of the fim table that outlive a call into lua. commands and event handlers are plain
+* lua tables the editor looks into, everything touching the editor is bound per call
+*/
+const PRELUDE: &str = r#"
+fim = { commands = {}, handlers = {} }
+
+function fim.command(name, fn)
+    fim.commands[name] = fn
+end
+
+function fim.on(event, fn)
+    fim.handlers[event] = fim.handlers[event] or {}
+    table.insert(fim.handlers[event], fn)
+end
+"#;
+
+/*
+* the lua runtime behind init.lua and :lua. scripts see a global fim table:
+*   fim.line_count(), fim.get_line(n), fim.set_line(n, text), fim.insert_line(n, text),
+*   fim.delete_line(n)           lines of the current buffer, numbered from 1
+*   fim.get_cursor(), fim.set_cursor(line, char)
+*   fim.mode(), fim.message(text), fim.exec(command line), fim.feed(keys)
+*   fim.map(map command, lhs, rhs), fim.unmap(unmap command, lhs)    like :nnoremap and :nunmap
+*   fim.get_option(name), fim.set_option(name, value)
+*   fim.command(name, fn)        fn gets what follows the name on the : line
+*   fim.on(event, fn)            startup, key, mode, change and write
+*/
+pub struct Script {
+    lua: Lua,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        let lua = Lua::new();
+        lua.load(PRELUDE).set_name("prelude").exec().expect("prelude is valid lua");
+        Script { lua }
+    }
+
+    fn has(&self, table: &str, key: &str) -> bool {
+        fim(&self.lua)
+            .and_then(|fim| fim.get::<_, Table>(table))
+            .and_then(|table| table.contains_key(key))
+            .unwrap_or(false)
+    }
+}
+
+fn fim(lua: &Lua) -> mlua::Result<Table<'_>> {
+    lua.globals().get("fim")
+}
+
+impl Editor {
+    // runs init.lua from the config directory, if there is one
+    pub(super) fn load_init_script(&mut self) {
+        let path = config_dir().join(INIT_SCRIPT);
+        if path.exists() {
+            self.run_lua_file(&path);
+        }
+    }
+
+    pub(super) fn run_lua(&mut self, code: &str) {
+        self.with_api(|lua| lua.load(code).set_name(":lua").exec());
+    }
+
+    pub(super) fn run_lua_file(&mut self, path: &Path) {
+        match fs::read_to_string(path) {
+            Ok(code) => self.with_api(|lua| lua.load(&code).set_name(path.display().to_string()).exec()),
+            Err(err) => self.message = Some(format!("could not read {}: {}", path.display(), err)),
+        }
+    }
+
+    // runs a command defined with fim.command, false if no script defines one by that name
+    pub(super) fn run_script_command(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (name, args) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if !self.script.as_ref().is_some_and(|script| script.has("commands", name)) {
+            return false;
+        }
+        self.with_api(|lua| {
+            let command: Function = fim(lua)?.get::<_, Table>("commands")?.get(name)?;
+            command.call::<_, ()>(args.trim())
+        });
+        true
+    }
+
+    // calls the handlers scripts registered for the event
+    pub(super) fn emit_script(&mut self, event: &EditorEvent) {
+        if !self.script.as_ref().is_some_and(|script| script.has("handlers", event.name())) {
+            return;
+        }
+        self.with_api(|lua| {
+            let handlers: Table = fim(lua)?.get::<_, Table>("handlers")?.get(event.name())?;
+            for handler in handlers.sequence_values::<Function>() {
+                match event {
+                    EditorEvent::Key(key) | EditorEvent::Write(key) => handler?.call(key.as_str())?,
+                    EditorEvent::ModeChanged(mode) => handler?.call(mode.name())?,
+                    EditorEvent::Startup | EditorEvent::BufferChanged => handler?.call(())?,
+                }
+            }
+            Ok(())
+        });
+    }
+
+    /*
+    * binds the editor functions of the fim table for the length of f. the runtime is taken out
+    * of the editor meanwhile, so a script that reaches back into scripts (fim.exec of a script
+    * command, events it causes) finds none and those calls are skipped instead of nesting
+    */
+    fn with_api<R: Default>(&mut self, f: impl FnOnce(&Lua) -> mlua::Result<R>) -> R {
+        let Some(script) = self.script.take() else {
+            return R::default();
+        };
+        let editor = RefCell::new(&mut *self);
+        let result = script.lua.scope(|scope| {
+            bind_api(&script.lua, scope, &editor)?;
+            f(&script.lua)
+        });
+        self.script = Some(script);
+        result.unwrap_or_else(|err| {
+            self.message = Some(format!("lua: {}", err));
+            R::default()
+        })
+    }
+}
+
+fn bind_api<'lua, 'scope>(
+    lua: &'lua Lua,
+    scope: &Scope<'lua, 'scope>,
+    editor: &'scope RefCell<&mut Editor>,
+) -> mlua::Result<()> {
+    let api = fim(lua)?;
+
+    api.set("line_count", scope.create_function(|_, ()| Ok(editor.borrow().buffers.curr_buffer().line_count()))?)?;
+    api.set(
+        "get_line",
+        scope.create_function(|_, line: usize| {
+            let editor = editor.borrow();
+            let buf = editor.buffers.curr_buffer();
+            Ok((1..=buf.line_count()).contains(&line).then(|| buf.get_line(line - 1).content.clone()))
+        })?,
+    )?;
+    api.set(
+        "set_line",
+        scope.create_function(|_, (line, text): (usize, String)| {
+            let mut editor = editor.borrow_mut();
+            let buf = editor.buffers.curr_buffer_mut();
+            check_line(line, buf.line_count())?;
+            buf.set_line(line, &text);
+            Ok(())
+        })?,
+    )?;
+    api.set(
+        "insert_line",
+        scope.create_function(|_, (line, text): (usize, String)| {
+            let mut editor = editor.borrow_mut();
+            let buf = editor.buffers.curr_buffer_mut();
+            check_line(line, buf.line_count() + 1)?;
+            buf.insert_line(line, &text);
+            Ok(())
+        })?,
+    )?;
+    api.set(
+        "delete_line",
+        scope.create_function(|_, line: usize| {
+            let mut editor = editor.borrow_mut();
+            let buf = editor.buffers.curr_buffer_mut();
+            check_line(line, buf.line_count())?;
+            buf.delete_line(line);
+            Ok(())
+        })?,
+    )?;
+
+    api.set(
+        "get_cursor",
+        scope.create_function(|_, ()| {
+            let editor = editor.borrow();
+            let cursor = editor.buffers.curr_buffer().cursor();
+            Ok((cursor.line_pos, cursor.char_pos))
+        })?,
+    )?;
+    api.set(
+        "set_cursor",
+        scope.create_function(|_, (line, char): (usize, Option<usize>)| {
+            let mut editor = editor.borrow_mut();
+            let buf = editor.buffers.curr_buffer_mut();
+            buf.move_cursor_line_to(line);
+            buf.move_cursor_char_to(char.unwrap_or(0));
+            Ok(())
+        })?,
+    )?;
+
+    api.set("mode", scope.create_function(|_, ()| Ok(editor.borrow().mode.name()))?)?;
+    api.set(
+        "message",
+        scope.create_function(|_, text: String| {
+            editor.borrow_mut().message = Some(text);
+            Ok(())
+        })?,
+    )?;
+    api.set(
+        "exec",
+        scope.create_function(|_, line: String| {
+            editor.borrow_mut().run_command_line(&line);
+            Ok(())
+        })?,
+    )?;
+    api.set(
+        "feed",
+        scope.create_function(|_, keys: String| {
+            let mut editor = editor.borrow_mut();
+            let keys = keymap::parse_keys(&keys, &editor.keymap.leader).map_err(mlua::Error::runtime)?;
+            editor.typed_keys.extend(keys);
+            editor.resolve_typed(None);
+            Ok(())
+        })?,
+    )?;
+
+    api.set(
+        "map",
+        scope.create_function(|_, (command, lhs, rhs): (String, String, String)| {
+            let mut editor = editor.borrow_mut();
+            editor.keymap.map(&command, Some(&lhs), Some(&rhs)).map_err(mlua::Error::runtime)?;
+            Ok(())
+        })?,
+    )?;
+    api.set(
+        "unmap",
+        scope.create_function(|_, (command, lhs): (String, String)| {
+            editor.borrow_mut().keymap.unmap(&command, &lhs).map_err(mlua::Error::runtime)
+        })?,
+    )?;
+
+    api.set("get_option", scope.create_function(|lua, name: String| get_option(&editor.borrow(), lua, &name))?)?;
+    api.set(
+        "set_option",
+        scope.create_function(|lua, (name, value): (String, Value)| {
+            set_option(&mut editor.borrow_mut(), lua, &name, value)
+        })?,
+    )?;
+    Ok(())
+}
+
+fn check_line(line: usize, last: usize) -> mlua::Result<()> {
+    match (1..=last).contains(&line) {
+        true => Ok(()),
+        false => Err(mlua::Error::runtime(format!("line {} out of range 1 to {}", line, last))),
+    }
+}
+
+// options scripts can change while fim runs, the config sets where they start
+fn get_option<'lua>(editor: &Editor, lua: &'lua Lua, name: &str) -> mlua::Result<Value<'lua>> {
+    let settings = &editor.buffers.curr_buffer().settings;
+    match name {
+        "tabstop" => settings.tabstop.into_lua(lua),
+        "shiftwidth" => settings.shiftwidth.into_lua(lua),
+        "expandtab" => settings.expandtab.into_lua(lua),
+        "wrap" => editor.wrap.is_some().into_lua(lua),
+        "leader" => editor.keymap.leader.as_str().into_lua(lua),
+        "timeout_ms" => (editor.map_timeout.as_millis() as u64).into_lua(lua),
+        _ => Err(mlua::Error::runtime(format!("unknown option: {}", name))),
+    }
+}
+
+fn set_option<'lua>(editor: &mut Editor, lua: &'lua Lua, name: &str, value: Value<'lua>) -> mlua::Result<()> {
+    let settings = &mut editor.buffers.curr_buffer_mut().settings;
+    match name {
+        "tabstop" => settings.tabstop = usize::from_lua(value, lua)?.max(1),
+        "shiftwidth" => settings.shiftwidth = usize::from_lua(value, lua)?.max(1),
+        "expandtab" => settings.expandtab = bool::from_lua(value, lua)?,
+        "wrap" => editor.wrap = bool::from_lua(value, lua)?.then(wrap_settings),
+        // like in vim, mappings already defined keep the leader they were made with
+        "leader" => {
+            let leader = String::from_lua(value, lua)?;
+            keymap::parse_keys(&leader, "").map_err(mlua::Error::runtime)?;
+            editor.keymap.leader = leader;
+        }
+        "timeout_ms" => editor.map_timeout = Duration::from_millis(u64::from_lua(value, lua)?),
+        _ => return Err(mlua::Error::runtime(format!("unknown option: {}", name))),
+    }
+    Ok(())
+}
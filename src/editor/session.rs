@@ -0,0 +1,213 @@
+use std::{fs, io};
+
+use super::buffer::Cursor;
+
+// bumped when a change would make older fims misread a session, they refuse to load it. keys
+// they do not know are skipped, so adding some does not need a new version
+pub const SESSION_VERSION: u32 = 1;
+const HEADER: &str = "fim session";
+
+pub const DEFAULT_SESSION: &str = "session.fim";
+
+// an open file and where its cursor was
+#[derive(Debug, Clone)]
+pub struct BufferState {
+    pub path: String,
+    pub cursor: Cursor,
+}
+
+// what the window and camera looked like, owned by main rather than the editor
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewState {
+    pub camera_pos: [f32; 3],
+    pub camera_to: [f32; 3],
+    pub zoom: f32,
+    pub window: (u32, u32),
+}
+
+/*
+* the open files with their cursors, which one is current, the unnamed register (the only one
+* fim has), the : history and the camera and window. fim has no marks, search history or split
+* windows for it to keep. stored as one `key values` entry per line after a
+* `fim session <version>` header, text that could hold a newline is escaped
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    pub buffers: Vec<BufferState>,
+    pub current: usize,
+    pub registers: Vec<(char, String)>,
+    pub command_history: Vec<String>,
+    pub view: Option<ViewState>,
+}
+
+impl Session {
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let mut text = format!("{} {}\n", HEADER, SESSION_VERSION);
+        for buf in &self.buffers {
+            text.push_str(&format!("buffer {} {} {}\n", buf.cursor.line_pos, buf.cursor.char_pos, escape(&buf.path)));
+        }
+        text.push_str(&format!("current {}\n", self.current));
+        for (name, content) in &self.registers {
+            text.push_str(&format!("register {} {}\n", name, escape(content)));
+        }
+        for command in &self.command_history {
+            text.push_str(&format!("history {}\n", escape(command)));
+        }
+        if let Some(view) = &self.view {
+            let [px, py, pz] = view.camera_pos;
+            let [tx, ty, tz] = view.camera_to;
+            text.push_str(&format!("camera {} {} {} {} {} {} {}\n", px, py, pz, tx, ty, tz, view.zoom));
+            text.push_str(&format!("window {} {}\n", view.window.0, view.window.1));
+        }
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut lines = text.lines();
+        let version = lines
+            .next()
+            .and_then(|header| header.strip_prefix(HEADER))
+            .and_then(|version| version.trim().parse::<u32>().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a fim session"))?;
+        if version > SESSION_VERSION {
+            let message = format!("session version {} is newer than this fim's {}", version, SESSION_VERSION);
+            return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+        }
+
+        let mut session = Session::default();
+        let mut camera = None;
+        let mut window = None;
+        for (num, line) in lines.enumerate() {
+            let (key, values) = line.split_once(' ').unwrap_or((line, ""));
+            let parsed = match key {
+                "buffer" => parse_buffer(values).map(|buf| session.buffers.push(buf)),
+                "current" => values.parse().ok().map(|current| session.current = current),
+                "register" => values.split_once(' ').and_then(|(name, content)| {
+                    let name = name.chars().next()?;
+                    session.registers.push((name, unescape(content)));
+                    Some(())
+                }),
+                "history" => {
+                    session.command_history.push(unescape(values));
+                    Some(())
+                }
+                "camera" => parse_floats::<7>(values).map(|floats| camera = Some(floats)),
+                "window" => parse_floats::<2>(values).map(|size| window = Some((size[0] as u32, size[1] as u32))),
+                // written by a newer fim
+                _ => Some(()),
+            };
+            if parsed.is_none() {
+                println!("Could not parse line {} of session {}: {}", num + 2, path, line);
+            }
+        }
+
+        if let (Some(camera), Some(window)) = (camera, window) {
+            session.view = Some(ViewState {
+                camera_pos: [camera[0], camera[1], camera[2]],
+                camera_to: [camera[3], camera[4], camera[5]],
+                zoom: camera[6],
+                window,
+            });
+        }
+        session.current = session.current.min(session.buffers.len().saturating_sub(1));
+        Ok(session)
+    }
+}
+
+fn parse_buffer(values: &str) -> Option<BufferState> {
+    let mut parts = values.splitn(3, ' ');
+    let line_pos = parts.next()?.parse().ok()?;
+    let char_pos = parts.next()?.parse().ok()?;
+    let path = unescape(parts.next()?);
+    Some(BufferState {
+        path,
+        cursor: Cursor { line_pos, char_pos },
+    })
+}
+
+fn parse_floats<const N: usize>(values: &str) -> Option<[f32; N]> {
+    let floats: Vec<f32> = values.split(' ').map(|value| value.parse().ok()).collect::<Option<_>>()?;
+    floats.try_into().ok()
+}
+
+// keeps multi line text and paths with newlines on one line of the session
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::testdir::TestDir;
+
+    #[test]
+    fn saving_and_loading() {
+        let dir = TestDir::new("session");
+        let path = dir.path().join("session.fim");
+        let path = path.to_str().unwrap();
+
+        // paths and text with newlines and backslashes come back as they were
+        let session = Session {
+            buffers: vec![
+                BufferState {
+                    path: "/a".to_owned(),
+                    cursor: Cursor { line_pos: 2, char_pos: 1 },
+                },
+                BufferState {
+                    path: "/b\nc\\".to_owned(),
+                    cursor: Cursor { line_pos: 1, char_pos: 0 },
+                },
+            ],
+            current: 1,
+            registers: vec![('"', "two\nlines".to_owned())],
+            command_history: vec!["w".to_owned(), "mksession".to_owned()],
+            view: Some(ViewState {
+                camera_pos: [0., 1., 2.5],
+                camera_to: [0., 0., 0.],
+                zoom: 1.5,
+                window: (800, 600),
+            }),
+        };
+        session.save(path).unwrap();
+        let loaded = Session::load(path).unwrap();
+        let buffers: Vec<_> = loaded
+            .buffers
+            .iter()
+            .map(|buf| (buf.path.as_str(), buf.cursor.line_pos, buf.cursor.char_pos))
+            .collect();
+        assert_eq!(buffers, [("/a", 2, 1), ("/b\nc\\", 1, 0)]);
+        assert_eq!(loaded.current, 1);
+        assert_eq!(loaded.registers, session.registers);
+        assert_eq!(loaded.command_history, session.command_history);
+        assert_eq!(loaded.view, session.view);
+
+        // keys from a newer fim are skipped, a current past the buffers is kept in range
+        fs::write(path, format!("{} {}\nbuffer 1 0 /a\nfolds 1 2\ncurrent 5\n", HEADER, SESSION_VERSION)).unwrap();
+        let loaded = Session::load(path).unwrap();
+        assert_eq!((loaded.buffers.len(), loaded.current), (1, 0));
+
+        // a session from a newer version is refused rather than misread
+        fs::write(path, format!("{} {}\nbuffer 1 0 /a\n", HEADER, SESSION_VERSION + 1)).unwrap();
+        assert!(Session::load(path).is_err());
+        fs::write(path, "not a session\n").unwrap();
+        assert!(Session::load(path).is_err());
+    }
+}
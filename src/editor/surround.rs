@@ -0,0 +1,194 @@
+use super::{
+    buffer::{Buffer, Cursor},
+    motion::Status,
+    textobject::{alias_pair, find_quotes, find_tag, is_quote, word_end, TextRange},
+};
+
+// delimiters to insert around text
+struct Surrounding {
+    open: String,
+    close: String,
+}
+
+/*
+* runs a surround command (`ys{motion}{char}`, `cs{old}{new}` or `ds{char}`) given the keys typed
+* so far, reporting whether more keys are needed
+*/
+pub fn apply(buf: &mut Buffer, keys: &str) -> Status {
+    let Some(rest) = keys.get(2..) else {
+        return Status::Pending;
+    };
+    let mut chars = rest.chars();
+
+    match &keys[..2] {
+        "ds" => {
+            let Some(target) = chars.next() else {
+                return Status::Pending;
+            };
+            match locate(buf, target) {
+                Some((open, close)) => {
+                    buf.replace_range(close.start, close.end, "");
+                    buf.replace_range(open.start, open.end, "");
+                    buf.move_cursor_to(open.start);
+                    Status::Done
+                }
+                None => Status::Invalid,
+            }
+        }
+        "cs" => {
+            let Some(target) = chars.next() else {
+                return Status::Pending;
+            };
+            let new = match parse_surrounding(chars.as_str()) {
+                Ok(new) => new,
+                Err(status) => return status,
+            };
+            match locate(buf, target) {
+                Some((open, close)) => {
+                    buf.replace_range(close.start, close.end, &new.close);
+                    buf.replace_range(open.start, open.end, &new.open);
+                    buf.move_cursor_to(open.start);
+                    Status::Done
+                }
+                None => Status::Invalid,
+            }
+        }
+        "ys" => {
+            let (range, used) = match motion_range(buf, rest) {
+                Ok(found) => found,
+                Err(status) => return status,
+            };
+            let new = match parse_surrounding(&rest[used..]) {
+                Ok(new) => new,
+                Err(status) => return status,
+            };
+            buf.replace_range(range.end, range.end, &new.close);
+            buf.replace_range(range.start, range.start, &new.open);
+            buf.move_cursor_to(range.start);
+            Status::Done
+        }
+        _ => Status::Invalid,
+    }
+}
+
+// the range a `ys` motion covers and how many chars of keys it used
+fn motion_range(buf: &mut Buffer, keys: &str) -> Result<(TextRange, usize), Status> {
+    let mut chars = keys.chars();
+    let cursor = *buf.cursor();
+    let line = &buf.curr_line().content;
+    let at = |char_pos| Cursor {
+        line_pos: cursor.line_pos,
+        char_pos,
+    };
+
+    match chars.next() {
+        None => Err(Status::Pending),
+        // whole line, without its indentation
+        Some('s') => {
+            let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+            let len = line.trim_end().chars().count();
+            Ok((TextRange { start: at(indent), end: at(len.max(indent)) }, 1))
+        }
+        Some('$') => Ok((TextRange { start: cursor, end: at(buf.curr_line().len()) }, 1)),
+        Some(c @ ('w' | 'W' | 'e' | 'E')) => {
+            let end = word_end(line, cursor.char_pos, c.is_ascii_uppercase());
+            Ok((TextRange { start: cursor, end: at(end) }, 1))
+        }
+        Some(c @ ('i' | 'a')) => {
+            let Some(object) = chars.next() else {
+                return Err(Status::Pending);
+            };
+            buf.text_object(&format!("{}{}", c, object))
+                .map(|range| (range, 1 + object.len_utf8()))
+                .ok_or(Status::Invalid)
+        }
+        Some(_) => Err(Status::Invalid),
+    }
+}
+
+// the delimiters to add, typing an opening bracket pads with spaces and `<` or `t` reads a tag
+fn parse_surrounding(keys: &str) -> Result<Surrounding, Status> {
+    let mut chars = keys.chars();
+    let Some(c) = chars.next() else {
+        return Err(Status::Pending);
+    };
+
+    if c == '<' || c == 't' {
+        let rest = chars.as_str();
+        let rest = rest.strip_prefix('<').filter(|_| c == 't').unwrap_or(rest);
+        let Some(end) = rest.find('>') else {
+            return Err(Status::Pending);
+        };
+        let tag = rest[..end].trim();
+        let name = tag.split_whitespace().next().ok_or(Status::Invalid)?;
+        return Ok(Surrounding {
+            open: format!("<{}>", tag),
+            close: format!("</{}>", name),
+        });
+    }
+
+    Ok(match alias_pair(c) {
+        Some((open, close)) if c == open => Surrounding {
+            open: format!("{} ", open),
+            close: format!(" {}", close),
+        },
+        Some((open, close)) => Surrounding {
+            open: open.to_string(),
+            close: close.to_string(),
+        },
+        None => Surrounding {
+            open: c.to_string(),
+            close: c.to_string(),
+        },
+    })
+}
+
+// ranges of the opening and closing delimiters named by target around the cursor
+fn locate(buf: &mut Buffer, target: char) -> Option<(TextRange, TextRange)> {
+    let cursor = *buf.cursor();
+
+    if target == 't' {
+        let tags = find_tag(&buf.lines, cursor)?;
+        return Some((
+            TextRange { start: tags.open_start, end: tags.open_end },
+            TextRange { start: tags.close_start, end: tags.close_end },
+        ));
+    }
+
+    // an opening bracket also takes the space padding it inside, like the one ysiw( adds
+    let padded = alias_pair(target).is_some_and(|(open, _)| open == target);
+    let (start, end) = match alias_pair(target) {
+        Some(pair) => buf.enclosing_pair(pair)?,
+        None if is_quote(target) || !target.is_alphanumeric() => {
+            let (start, end) = find_quotes(&buf.curr_line().content, cursor.char_pos, target)?;
+            let line = cursor.line_pos;
+            (
+                Cursor { line_pos: line, char_pos: start },
+                Cursor { line_pos: line, char_pos: end },
+            )
+        }
+        None => return None,
+    };
+
+    let single = |at: Cursor| TextRange {
+        start: at,
+        end: Cursor {
+            line_pos: at.line_pos,
+            char_pos: at.char_pos + 1,
+        },
+    };
+    let (mut open, mut close) = (single(start), single(end));
+    if padded {
+        let char_at = |at: Cursor| buf.get_line(at.line_pos - 1).content.chars().nth(at.char_pos);
+        let before_close = close.start.char_pos.checked_sub(1).map(|char_pos| Cursor { char_pos, ..close.start });
+        // a single space between the brackets only pads one side
+        let before = |a: Cursor, b: Cursor| (a.line_pos, a.char_pos) < (b.line_pos, b.char_pos);
+        if char_at(open.end) == Some(' ') && before(open.end, close.start) {
+            open.end.char_pos += 1;
+        }
+        if let Some(at) = before_close.filter(|at| char_at(*at) == Some(' ') && !before(*at, open.end)) {
+            close.start = at;
+        }
+    }
+    Some((open, close))
+}
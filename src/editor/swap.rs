@@ -0,0 +1,144 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+use super::buffer::{Buffer, Cursor};
+
+// first line of every swap file, bumped when the format changes
+const HEADER: &str = "fim swap 1";
+
+/*
+* the contents of a swap file, written next to the file being edited so work survives a crash.
+* the process that wrote it is kept to tell a crash from another instance editing the file.
+* only the text and cursor are kept, fim has no undo history to carry over
+*/
+pub struct SwapData {
+    pub pid: u32,
+    pub cursor: Cursor,
+    pub lines: Vec<String>,
+}
+
+// what the swap file of a buffer is to this instance
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SwapOwner {
+    // not looked for yet
+    Unchecked,
+    // ours to write
+    Ours,
+    // another instance's, or the buffer gets none
+    Skipped,
+}
+
+// .name.swp next to the file
+pub fn swap_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    path.with_file_name(format!(".{}.swp", name))
+}
+
+/*
+* format is a header, the pid of the writer, the cursor and then the lines of the buffer. written
+* to a temporary file first so a crash while writing leaves the last swap intact
+*/
+pub fn write(buf: &Buffer) -> io::Result<()> {
+    let cursor = buf.cursor();
+    let mut text = format!("{}\n{}\n{} {}\n", HEADER, process::id(), cursor.line_pos, cursor.char_pos);
+    for line in &buf.lines {
+        text.push_str(&line.content);
+        text.push('\n');
+    }
+
+    let path = swap_path(buf.file_path());
+    let tmp = path.with_extension("swp.tmp");
+    fs::write(&tmp, text)?;
+    fs::rename(tmp, path)
+}
+
+pub fn read(file_path: &str) -> Option<SwapData> {
+    let text = fs::read_to_string(swap_path(file_path)).ok()?;
+    let mut lines = text.split('\n');
+    if lines.next()? != HEADER {
+        return None;
+    }
+    let pid = lines.next()?.parse().ok()?;
+    let (line_pos, char_pos) = lines.next()?.split_once(' ')?;
+    let cursor = Cursor {
+        line_pos: line_pos.parse().ok()?,
+        char_pos: char_pos.parse().ok()?,
+    };
+
+    let mut lines: Vec<String> = lines.map(str::to_owned).collect();
+    // every line ends in \n, the split leaves an empty string after the last
+    lines.pop();
+    if lines.is_empty() {
+        lines.push(String::new());
+    }
+    Some(SwapData { pid, cursor, lines })
+}
+
+pub fn remove(file_path: &str) {
+    let _ = fs::remove_file(swap_path(file_path));
+}
+
+// true if the process that wrote a swap file is still running
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    // 0 and pids past i32::MAX come from a corrupt swap file, kill would take them for a
+    // process group
+    if pid == 0 || pid > i32::MAX as u32 {
+        return false;
+    }
+    if pid == process::id() {
+        return true;
+    }
+    // SAFETY: signal 0 sends nothing to the positive pid, kill only checks that the process
+    // exists. EPERM means it belongs to another user
+    let exists = unsafe { libc::kill(pid as i32, 0) == 0 };
+    exists || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+pub fn is_running(pid: u32) -> bool {
+    pid == process::id()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::testdir::TestDir;
+
+    #[test]
+    fn writing_and_reading() {
+        let dir = TestDir::new("swap");
+        let path = dir.file("file.txt", "a\nb\n");
+        let file_path = path.to_str().unwrap();
+        assert_eq!(swap_path(file_path), dir.path().join(".file.txt.swp"));
+        assert!(read(file_path).is_none());
+
+        // the text, cursor and writer come back, an emptied buffer still has its one line
+        let mut buf = Buffer::open(file_path, u64::MAX);
+        buf.delete_line_curr();
+        write(&buf).unwrap();
+        let swap = read(file_path).unwrap();
+        assert_eq!((swap.pid, swap.lines), (process::id(), vec!["b".to_owned()]));
+        buf.delete_line_curr();
+        write(&buf).unwrap();
+        assert_eq!(read(file_path).unwrap().lines, [""]);
+
+        // another header is some other program's file
+        fs::write(swap_path(file_path), "vim swap\n").unwrap();
+        assert!(read(file_path).is_none());
+        remove(file_path);
+        assert!(!swap_path(file_path).exists());
+    }
+
+    #[test]
+    fn running_writers() {
+        assert!(is_running(process::id()));
+        // what a corrupt swap file could hold, none of it is a process
+        assert!(!is_running(0));
+        assert!(!is_running(u32::MAX));
+    }
+}
@@ -0,0 +1,168 @@
+use std::{ops::Range, sync::LazyLock};
+
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxSet};
+
+use super::buffer::Line;
+
+pub static SYNTAX_SET: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+static STRING_SCOPE: LazyLock<Scope> = LazyLock::new(|| Scope::new("string").unwrap());
+static COMMENT_SCOPE: LazyLock<Scope> = LazyLock::new(|| Scope::new("comment").unwrap());
+
+// name of the syntect syntax for a file, None for plain text
+pub fn syntax_for_file(file_path: &str) -> Option<String> {
+    SYNTAX_SET
+        .find_syntax_for_file(file_path)
+        .ok()
+        .flatten()
+        .filter(|syntax| syntax.name != SYNTAX_SET.find_syntax_plain_text().name)
+        .map(|syntax| syntax.name.clone())
+}
+
+/*
+* byte ranges of each line that lie inside strings or comments. the parser state after every line
+* is kept, so after an edit only the lines from the first changed one are parsed again, up to
+* where the parser is back in the state it was in before
+*/
+pub struct SyntaxMask {
+    pub revision: u64,
+    syntax: String,
+    lines: Vec<MaskedLine>,
+}
+
+// a parsed line with the text it was parsed from and the parser state after it
+struct MaskedLine {
+    text: String,
+    state: ParseState,
+    stack: ScopeStack,
+    ranges: Vec<Range<usize>>,
+}
+
+impl SyntaxMask {
+    pub fn build(syntax_name: &str, lines: &[Line], revision: u64) -> Self {
+        let mut mask = SyntaxMask {
+            revision,
+            syntax: syntax_name.to_owned(),
+            lines: Vec::with_capacity(lines.len()),
+        };
+        mask.update(lines, revision);
+        mask
+    }
+
+    // parses the lines that changed since the last update
+    pub fn update(&mut self, lines: &[Line], revision: u64) {
+        self.revision = revision;
+        let Some(syntax) = SYNTAX_SET.find_syntax_by_name(&self.syntax) else {
+            return;
+        };
+
+        // lines that are the same at the start and at the end of the buffer
+        let prefix = self.lines.iter().zip(lines).take_while(|(old, line)| old.text == line.content).count();
+        let same_len = self.lines.len().min(lines.len()) - prefix;
+        let suffix = self
+            .lines
+            .iter()
+            .rev()
+            .zip(lines.iter().rev())
+            .take(same_len)
+            .take_while(|(old, line)| old.text == line.content)
+            .count();
+        let old = self.lines.split_off(prefix);
+        // where the unchanged lines at the end start in old
+        let old_suffix = old.len() - suffix;
+        let new_suffix = lines.len() - suffix;
+
+        let (mut state, mut stack) = match self.lines.last() {
+            Some(last) => (last.state.clone(), last.stack.clone()),
+            None => (ParseState::new(syntax), ScopeStack::new()),
+        };
+        for (idx, line) in lines.iter().enumerate().skip(prefix) {
+            // a line the parser gives up on is all code
+            let ranges = parse_line(&mut state, &mut stack, &line.content).unwrap_or_default();
+            self.lines.push(MaskedLine {
+                text: line.content.clone(),
+                state: state.clone(),
+                stack: stack.clone(),
+                ranges,
+            });
+            // an unchanged line left the parser as before, so do all the ones after it
+            if idx >= new_suffix {
+                let at = old_suffix + idx - new_suffix;
+                if old[at].state == state && old[at].stack == stack {
+                    self.lines.extend(old.into_iter().skip(at + 1));
+                    return;
+                }
+            }
+        }
+    }
+
+    pub fn is_code(&self, line: usize, offset: usize) -> bool {
+        self.lines
+            .get(line)
+            .map_or(true, |line| !line.ranges.iter().any(|range| range.contains(&offset)))
+    }
+}
+
+// the ranges of a line in strings or comments, none if the parser gave up on it
+fn parse_line(state: &mut ParseState, stack: &mut ScopeStack, line: &str) -> Option<Vec<Range<usize>>> {
+    let text = format!("{}\n", line);
+    let ops = state.parse_line(&text, &SYNTAX_SET).ok()?;
+
+    let mut ranges = Vec::new();
+    let mut last = 0;
+    for (offset, op) in ops {
+        if offset > last && is_non_code(stack) {
+            ranges.push(last..offset);
+        }
+        if stack.apply(&op).is_err() {
+            break;
+        }
+        last = offset;
+    }
+    if is_non_code(stack) {
+        ranges.push(last..text.len());
+    }
+    Some(ranges)
+}
+
+fn is_non_code(stack: &ScopeStack) -> bool {
+    stack
+        .as_slice()
+        .iter()
+        .any(|scope| STRING_SCOPE.is_prefix_of(*scope) || COMMENT_SCOPE.is_prefix_of(*scope))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn updates() {
+        // a mask brought up to date after edits tells code apart like one built from scratch
+        let lines = |text: &str| -> Vec<Line> { text.split('\n').map(|line| Line::from(line.to_owned())).collect() };
+        let same = |mask: &SyntaxMask, lines: &[Line]| {
+            let built = SyntaxMask::build("Rust", lines, mask.revision);
+            for (idx, line) in lines.iter().enumerate() {
+                for offset in 0..=line.content.len() {
+                    assert_eq!(mask.is_code(idx, offset), built.is_code(idx, offset), "line {} offset {}", idx, offset);
+                }
+            }
+        };
+
+        let edits = [
+            "fn a() {\n    let s = \"(\";\n}\n// )\nfn b() {}",
+            "fn a() {\n/*\n    let s = \"(\";\n}\n// )\nfn b() {}",
+            "fn a() {\n/*\n    let s = \"(\";\n*/}\n// )\nfn b() {}",
+            "fn a() {\n    let s = \"(\";\n}\n// )\nfn b() {}",
+            "fn a() {\n    let s = \"(;\n}\n// )\nfn b() {}",
+            "",
+            "fn a() {\n    let s = \"(\";\n}\n// )\nfn b() {}",
+        ];
+        let mut mask = SyntaxMask::build("Rust", &lines(edits[0]), 0);
+        assert!(!mask.is_code(1, 13));
+        for (revision, text) in edits.iter().enumerate() {
+            mask.update(&lines(text), revision as u64);
+            same(&mask, &lines(text));
+        }
+    }
+}
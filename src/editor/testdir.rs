@@ -0,0 +1,39 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+// a directory of its own for a test to write files in, removed with everything in it when dropped
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("fim-test-{}-{}", process::id(), name));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("the temp dir should be writable");
+        TestDir { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // writes a file at a path relative to the directory, making the directories on the way
+    pub fn file(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.path.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("the temp dir should be writable");
+        }
+        fs::write(&path, contents).expect("the temp dir should be writable");
+        path
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
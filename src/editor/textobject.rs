@@ -0,0 +1,282 @@
+use super::{
+    brackets::find_enclosing,
+    buffer::{Cursor, Line},
+};
+
+// a selected region, start inclusive and end exclusive
+#[derive(Debug, Clone, Copy)]
+pub struct TextRange {
+    pub start: Cursor,
+    pub end: Cursor,
+}
+
+// positions of a tag pair, <open_start ... open_end> ... <close_start ... close_end>
+#[derive(Debug, Clone, Copy)]
+pub struct TagPair {
+    pub open_start: Cursor,
+    pub open_end: Cursor,
+    pub close_start: Cursor,
+    pub close_end: Cursor,
+}
+
+impl TextRange {
+    fn new(start: (usize, usize), end: (usize, usize)) -> Self {
+        TextRange {
+            start: to_cursor(start),
+            end: to_cursor(end),
+        }
+    }
+}
+
+fn to_cursor((line, char): (usize, usize)) -> Cursor {
+    Cursor {
+        line_pos: line + 1,
+        char_pos: char,
+    }
+}
+
+// bracket aliases used by vim
+pub fn alias_pair(c: char) -> Option<(char, char)> {
+    match c {
+        '(' | ')' | 'b' => Some(('(', ')')),
+        '[' | ']' | 'r' => Some(('[', ']')),
+        '{' | '}' | 'B' => Some(('{', '}')),
+        '<' | '>' | 'a' => Some(('<', '>')),
+        _ => None,
+    }
+}
+
+pub fn is_quote(c: char) -> bool {
+    c == '"' || c == '\'' || c == '`'
+}
+
+/*
+* resolves a text object like "iw", "a(" or "it" around the cursor. is_code is forwarded to
+* bracket matching so that brackets in strings and comments are skipped
+*/
+pub fn select(
+    lines: &[Line],
+    cursor: Cursor,
+    object: &str,
+    is_code: impl Fn(usize, usize) -> bool,
+) -> Option<TextRange> {
+    let mut chars = object.chars();
+    let (Some(kind), Some(c), None) = (chars.next(), chars.next(), chars.next()) else {
+        return None;
+    };
+    let inner = match kind {
+        'i' => true,
+        'a' => false,
+        _ => return None,
+    };
+    let line = cursor.line_pos - 1;
+    let char_pos = cursor.char_pos;
+
+    if c == 'w' || c == 'W' {
+        return select_word(&lines.get(line)?.content, line, char_pos, c == 'W', inner);
+    }
+
+    if is_quote(c) {
+        let (start, end) = find_quotes(&lines.get(line)?.content, char_pos, c)?;
+        return Some(if inner {
+            TextRange::new((line, start + 1), (line, end))
+        } else {
+            TextRange::new((line, start), (line, end + 1))
+        });
+    }
+
+    if c == 'p' {
+        return select_paragraph(lines, line, inner);
+    }
+
+    if c == 't' {
+        let tags = find_tag(lines, cursor)?;
+        return Some(if inner {
+            TextRange {
+                start: tags.open_end,
+                end: tags.close_start,
+            }
+        } else {
+            TextRange {
+                start: tags.open_start,
+                end: tags.close_end,
+            }
+        });
+    }
+
+    let pair = alias_pair(c)?;
+    let (start, end) = find_enclosing(lines, line, char_pos, pair, is_code)?;
+    Some(if inner {
+        TextRange::new((start.0, start.1 + 1), end)
+    } else {
+        TextRange::new(start, (end.0, end.1 + 1))
+    })
+}
+
+fn char_class(c: char, big: bool) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        2
+    } else {
+        1
+    }
+}
+
+fn select_word(text: &str, line: usize, char_pos: usize, big: bool, inner: bool) -> Option<TextRange> {
+    let chars: Vec<char> = text.chars().collect();
+    let class = char_class(*chars.get(char_pos)?, big);
+
+    let mut start = char_pos;
+    while start > 0 && char_class(chars[start - 1], big) == class {
+        start -= 1;
+    }
+    let mut end = char_pos + 1;
+    while end < chars.len() && char_class(chars[end], big) == class {
+        end += 1;
+    }
+
+    // a word includes the whitespace after it, or before it if there is none after
+    if !inner && class != 0 {
+        let trailing = end;
+        while end < chars.len() && chars[end].is_whitespace() {
+            end += 1;
+        }
+        if end == trailing {
+            while start > 0 && chars[start - 1].is_whitespace() {
+                start -= 1;
+            }
+        }
+    }
+
+    Some(TextRange::new((line, start), (line, end)))
+}
+
+// runs of non-blank (or blank) lines, `ap` adds the blank lines after the paragraph
+fn select_paragraph(lines: &[Line], line: usize, inner: bool) -> Option<TextRange> {
+    let blank = |l: usize| lines[l].content.trim().is_empty();
+    let kind = blank(*lines.get(line).map(|_| &line)?);
+
+    let mut start = line;
+    while start > 0 && blank(start - 1) == kind {
+        start -= 1;
+    }
+    let mut end = line;
+    while end + 1 < lines.len() && blank(end + 1) == kind {
+        end += 1;
+    }
+
+    if !inner {
+        let last = end;
+        while end + 1 < lines.len() && blank(end + 1) != kind {
+            end += 1;
+        }
+        if end == last {
+            while start > 0 && blank(start - 1) != kind {
+                start -= 1;
+            }
+        }
+    }
+
+    Some(TextRange::new((start, 0), (end, lines[end].len())))
+}
+
+// end of the word under the cursor, used for motions like `ysw`
+pub fn word_end(text: &str, char_pos: usize, big: bool) -> usize {
+    let chars: Vec<char> = text.chars().collect();
+    let Some(&c) = chars.get(char_pos) else {
+        return char_pos;
+    };
+    let class = char_class(c, big);
+    let mut end = char_pos + 1;
+    while end < chars.len() && char_class(chars[end], big) == class {
+        end += 1;
+    }
+    end
+}
+
+// char positions of the quote pair around or after char_pos, pairing quotes from line start
+pub fn find_quotes(text: &str, char_pos: usize, quote: char) -> Option<(usize, usize)> {
+    let chars: Vec<char> = text.chars().collect();
+    let quotes: Vec<usize> = (0..chars.len())
+        .filter(|&i| chars[i] == quote && (i == 0 || chars[i - 1] != '\\'))
+        .collect();
+
+    let pairs: Vec<(usize, usize)> = quotes.chunks_exact(2).map(|q| (q[0], q[1])).collect();
+    pairs
+        .iter()
+        .find(|(start, end)| *start <= char_pos && char_pos <= *end)
+        .or_else(|| pairs.iter().find(|(start, _)| *start > char_pos))
+        .copied()
+}
+
+struct Tag {
+    start: (usize, usize),
+    end: (usize, usize),
+    name: String,
+    closing: bool,
+}
+
+// tags that open or close on a single line, self closing tags are skipped
+fn scan_tags(lines: &[Line]) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    for (l, line) in lines.iter().enumerate() {
+        let chars: Vec<char> = line.content.chars().collect();
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i] != '<' {
+                i += 1;
+                continue;
+            }
+            let Some(close) = chars[i..].iter().position(|c| *c == '>').map(|p| p + i) else {
+                break;
+            };
+            let closing = chars.get(i + 1) == Some(&'/');
+            let name_start = if closing { i + 2 } else { i + 1 };
+            let name: String = chars[name_start..close]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || **c == '-' || **c == ':' || **c == '.')
+                .collect();
+            if !name.is_empty() && chars[close - 1] != '/' {
+                tags.push(Tag {
+                    start: (l, i),
+                    end: (l, close + 1),
+                    name,
+                    closing,
+                });
+            }
+            i = close + 1;
+        }
+    }
+    tags
+}
+
+// the innermost tag pair containing the cursor
+pub fn find_tag(lines: &[Line], cursor: Cursor) -> Option<TagPair> {
+    let pos = (cursor.line_pos - 1, cursor.char_pos);
+
+    let mut stack: Vec<Tag> = Vec::new();
+    let mut best: Option<TagPair> = None;
+    for tag in scan_tags(lines) {
+        if !tag.closing {
+            stack.push(tag);
+            continue;
+        }
+        let Some(open_idx) = stack.iter().rposition(|open| open.name == tag.name) else {
+            continue;
+        };
+        let open = stack.remove(open_idx);
+        stack.truncate(open_idx);
+
+        // pairs close in order of nesting, so the first that contains the cursor is innermost
+        if best.is_none() && open.start <= pos && pos < tag.end {
+            best = Some(TagPair {
+                open_start: to_cursor(open.start),
+                open_end: to_cursor(open.end),
+                close_start: to_cursor(tag.start),
+                close_end: to_cursor(tag.end),
+            });
+        }
+    }
+    best
+}
@@ -0,0 +1,149 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    time::{Duration, SystemTime},
+};
+
+use notify::{Event, PollWatcher, RecursiveMode, Watcher};
+
+// how often the fallback watcher looks at the files
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/*
+* watches the directories of open files and reports the files that changed. inotify and friends
+* are used when available, otherwise the directories are polled
+*/
+pub struct FileWatcher {
+    watcher: Option<Box<dyn Watcher + Send>>,
+    events: Receiver<notify::Result<Event>>,
+    // files being watched with their canonical path, directories are watched since editors
+    // usually save by renaming a new file over the old one
+    files: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Self {
+        let (tx, events) = mpsc::channel();
+        let watcher: Option<Box<dyn Watcher + Send>> = match notify::recommended_watcher(tx.clone()) {
+            Ok(watcher) => Some(Box::new(watcher)),
+            Err(_) => Self::poll_watcher(tx),
+        };
+        FileWatcher {
+            watcher,
+            events,
+            files: Vec::new(),
+        }
+    }
+
+    // a watcher that only polls, for filesystems without change notifications
+    pub fn polling() -> Self {
+        let (tx, events) = mpsc::channel();
+        FileWatcher {
+            watcher: Self::poll_watcher(tx),
+            events,
+            files: Vec::new(),
+        }
+    }
+
+    // a watcher that never reports a change
+    pub fn none() -> Self {
+        let (_, events) = mpsc::channel();
+        FileWatcher {
+            watcher: None,
+            events,
+            files: Vec::new(),
+        }
+    }
+
+    // a watcher told of changes through the sender, for tests that should not wait on the filesystem
+    #[cfg(test)]
+    pub fn channel() -> (Self, mpsc::Sender<notify::Result<Event>>) {
+        let (tx, events) = mpsc::channel();
+        let watcher = FileWatcher {
+            watcher: None,
+            events,
+            files: Vec::new(),
+        };
+        (watcher, tx)
+    }
+
+    fn poll_watcher(tx: mpsc::Sender<notify::Result<Event>>) -> Option<Box<dyn Watcher + Send>> {
+        let config = notify::Config::default().with_poll_interval(POLL_INTERVAL);
+        PollWatcher::new(tx, config).ok().map(|watcher| Box::new(watcher) as Box<dyn Watcher + Send>)
+    }
+
+    pub fn watch(&mut self, file_path: &str) {
+        let Ok(path) = fs::canonicalize(file_path) else {
+            return;
+        };
+        if self.files.contains(&path) {
+            return;
+        }
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        self.files.push(path);
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+        let failed = watcher.watch(&dir, RecursiveMode::NonRecursive).is_err();
+        // the native watcher can run out of watches, polling still works
+        if failed {
+            self.fall_back_to_polling();
+        }
+    }
+
+    fn fall_back_to_polling(&mut self) {
+        let (tx, events) = mpsc::channel();
+        self.watcher = Self::poll_watcher(tx);
+        self.events = events;
+        let Some(watcher) = self.watcher.as_mut() else {
+            return;
+        };
+        for dir in self.files.iter().filter_map(|file| file.parent()) {
+            let _ = watcher.watch(dir, RecursiveMode::NonRecursive);
+        }
+    }
+
+    // watched files touched since the last call, without blocking
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        let mut changed = Vec::new();
+        while let Ok(event) = self.events.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+            if event.kind.is_access() {
+                continue;
+            }
+            for path in event.paths {
+                if self.files.contains(&path) && !changed.contains(&path) {
+                    changed.push(path);
+                }
+            }
+        }
+        changed
+    }
+}
+
+// what a file looked like on disk, compared to tell real changes from our own writes
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DiskStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl DiskStamp {
+    pub fn of(file_path: &str) -> Option<Self> {
+        let meta = fs::metadata(file_path).ok()?;
+        Some(DiskStamp {
+            modified: meta.modified().ok(),
+            len: meta.len(),
+        })
+    }
+}
+
+// true if the two paths are the same file
+pub fn same_file(a: &Path, b: &str) -> bool {
+    fs::canonicalize(b).is_ok_and(|b| a == b)
+}
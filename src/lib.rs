@@ -0,0 +1,11 @@
+// the editor without a window, main.rs and the fuzz targets in fuzz/ build on it
+use std::{sync::LazyLock, time::Instant};
+
+extern crate freetype as ft;
+
+pub mod configuration;
+pub mod editor;
+pub mod plugins;
+pub mod renderer;
+
+pub static START_TIME: LazyLock<Instant> = LazyLock::new(|| Instant::now());
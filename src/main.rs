@@ -1,41 +1,57 @@
-use std::{env, sync::LazyLock, time::Instant};
-use configuration::{Config, CONFIG};
+use std::{env, time::Instant};
+use fim::configuration::{Config, CONFIG, CONFIG_PATH};
+use fim::START_TIME;
 use sdl2::libc::sleep;
 use syntect::easy::HighlightLines;
 use syntect::parsing::SyntaxSet;
 use syntect::highlighting::{ThemeSet, Style};
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 
-use editor::Editor;
-use renderer::{camera::Camera, cursor_renderer::CursorRenderer, text_renderer::TextRenderer, primitive_renderer::PrimitiveRenderer};
+use fim::editor::{layout::Metrics, session::{Session, ViewState, DEFAULT_SESSION}, Editor};
+use fim::renderer::{camera::Camera, cursor_renderer::CursorRenderer, plugin_canvas::RendererCanvas, text_renderer::{fold_summary, TextRenderer}, primitive_renderer::PrimitiveRenderer};
+use sdl2::mouse::MouseButton;
 use sdl2::rect::Rect;
-extern crate freetype as ft;
 extern crate gl;
 extern crate sdl2;
 
-mod editor;
-mod renderer;
-mod configuration;
-
-static START_TIME: LazyLock<Instant> = LazyLock::new(|| Instant::now());
+mod sdl_input;
 
 fn main() {
-    let config_path = "example.conf";
+    let config_path = CONFIG_PATH;
     let config = Config::new(config_path);
     CONFIG.set(config).expect("Config already set");
 
     let _ = *START_TIME;
 
-    let args: Vec<String> = env::args().collect();
+    // fim [file] or fim --session <session file>
+    let mut args: Vec<String> = env::args().collect();
     let no_path = String::from("");
-    let file_path = args.get(1).unwrap_or(&no_path);
+    let session = match args.iter().position(|arg| arg == "--session") {
+        Some(idx) => {
+            // the flag and its file are taken out so a session that fails to load opens no file
+            let path = match idx + 1 < args.len() {
+                true => args.remove(idx + 1),
+                false => DEFAULT_SESSION.to_string(),
+            };
+            args.remove(idx);
+            match Session::load(&path) {
+                Ok(session) => Some(session),
+                Err(err) => {
+                    println!("Unable to load session {}: {}", path, err);
+                    None
+                }
+            }
+        }
+        None => None,
+    };
+    let file_path = args.get(1).filter(|_| session.is_none()).unwrap_or(&no_path);
 
     // load sdl + gl
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
     //
     // set up window + context
-    let window = video_subsystem
+    let mut window = video_subsystem
         .window("fim", 1200, 800)
         .opengl()
         .resizable()
@@ -69,7 +85,8 @@ fn main() {
 
     // load renderer(s)
     let mut r2d = PrimitiveRenderer::new();
-    let mut txr = TextRenderer::new(&unsafe { CONFIG.get().unwrap_unchecked() }.general.font);
+    let general = &unsafe { CONFIG.get().unwrap_unchecked() }.general;
+    let mut txr = TextRenderer::new(&general.font, general.proportional);
     let mut cur = CursorRenderer::new();
 
     let mut camera = Camera::new(
@@ -79,35 +96,55 @@ fn main() {
     );
 
     // load editor
-    let mut editor = Editor::new(file_path);
+    let mut editor = match &session {
+        Some(session) => Editor::from_session(session),
+        None => Editor::new(file_path),
+    };
+    for name in &unsafe { CONFIG.get().unwrap_unchecked() }.general.plugins {
+        match fim::plugins::by_name(name) {
+            Some(plugin) => editor.register_plugin(plugin),
+            None => println!("No plugin named {}. Skipping!", name),
+        }
+    }
 
-    
     let mut cam_z = 20.;
 
+    // a session puts the camera and window back where they were
+    if let Some(view) = session.as_ref().and_then(|session| session.view) {
+        camera.pos = glam::Vec3::from(view.camera_pos);
+        camera.to = glam::Vec3::from(view.camera_to);
+        cam_z = view.zoom;
+        let _ = window.set_size(view.window.0, view.window.1);
+        camera.set_perspective(3.14 / 4., view.window.0 as f32 / view.window.1 as f32);
+        camera.update_view();
+    }
+
     let mut start = Instant::now();
     let mut event_pump = sdl_context.event_pump().unwrap();
     'main_loop: loop {
-        let mut skip_events = false;
         for event in event_pump.poll_iter() {
-            if skip_events {
+            // dbg!(&event);
+            if let Some(input) = sdl_input::translate(&event) {
+                editor.handle_input(input);
                 continue;
             }
-
-            // dbg!(&event);
             match event {
-                sdl2::event::Event::Quit { .. } => break 'main_loop,
-                sdl2::event::Event::TextInput {
-                    text,
-                    ..
-                } => {
-                    editor.handle_text_input(&text);
-                }
-                sdl2::event::Event::KeyDown {
-                    keycode,
-                    keymod,
+                // asks first when there are unwritten changes
+                sdl2::event::Event::Quit { .. } => editor.request_quit(),
+                // clicks put the cursor on the char under the mouse
+                sdl2::event::Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x: mx,
+                    y: my,
                     ..
                 } => {
-                    editor.handle_keypress(keycode.unwrap(), keymod, &mut skip_events);
+                    let (ww, wh) = window.size();
+                    let ndc = glam::Vec2::new(2. * mx as f32 / ww as f32 - 1., 1. - 2. * my as f32 / wh as f32);
+                    if let Some(hit) = camera.unproject_to_plane(ndc, 3.14 / 4., ww as f32 / wh as f32) {
+                        // rows span from 0.2 below their baseline to a line height above it
+                        let row = ((-hit.y - 0.2) / txr.height).ceil().max(0.) as usize;
+                        editor.click(row, hit.x / txr.advance);
+                    }
                 }
                 sdl2::event::Event::MouseWheel {
                     precise_y, ..
@@ -129,6 +166,9 @@ fn main() {
                 _ => (),
             }
         }
+        if editor.should_quit() {
+            break 'main_loop;
+        }
 
         let (win_x, win_y) = window.position();
         let (ww, wh) = window.size();
@@ -139,22 +179,151 @@ fn main() {
         // print!("\x1b[2J\x1b[H");
         // print!("{}", editor.buffers.curr_buffer());
         txr.begin_scene();
+        r2d.begin_scene();
         // portal effect
         // let (x, y, w, h) = txr.draw_text(
         //     -win_x as f32 / sh2 as f32 * 8.,
         //     win_y as f32 / sh2 as f32 * 8.,
         //     &editor.get_text(),
         //     20., Some(editor.get_cursor()));
-        txr.draw_text(0., 0., &editor.get_text(), f32::MAX, Some(editor.get_cursor()));
+        // the editor measures text with the advances of the glyphs loaded so far
+        editor.set_advances(txr.advances());
+        // columns visible across the window at the text plane
+        let visible_w = 2. * cam_z * (3.14f32 / 8.).tan() * ww as f32 / wh as f32;
+        editor.set_window_columns(((visible_w / txr.advance) as usize).saturating_sub(2));
+
+        editor.poll();
+        // lines loaded above a large buffer's push the text down, the view moves along with it
+        let shifted = editor.take_rows_shifted() as f32 * txr.height;
+        if shifted > 0. {
+            camera.pos.y -= shifted;
+            camera.to.y -= shifted;
+            cur.shift(glam::Vec2::new(0., -shifted));
+        }
+
+        // heights seen at the text plane, with room for the camera to catch up with the cursor
+        let visible_h = 2. * cam_z * (3.14f32 / 8.).tan();
+        let visible_y = camera.pos.y - visible_h..camera.pos.y + visible_h;
+
+        // large files only hand out the lines around the cursor, drawn where they would be
+        let (first_line, text) = editor.get_text_window();
+        let folds = editor.get_closed_folds();
+        let window_folds: Vec<(u32, u32)> = folds.iter().map(|&(first, last)| (first + 1 - first_line, last + 1 - first_line)).collect();
+        let (cursor_line, cursor_char) = editor.get_cursor();
+        let (x, y, cursor_w, h) = txr.draw_text(
+            0.,
+            (first_line - 1) as f32 * -txr.height,
+            &text,
+            editor.get_wrap(),
+            editor.get_tabstop(),
+            &window_folds,
+            editor.get_viewport(),
+            Some(visible_y),
+            Some((cursor_line + 1 - first_line, cursor_char)),
+        );
+        // a column, the cursor is two wide on wide chars
         let w = txr.advance;
-        let h = txr.height;
+        let left = editor.get_viewport().map_or(0., |view| view.left as f32 * w);
+
+        // rows on screen, wrapped lines take several and closed folds one
+        let row_y = |row: u32| row as f32 * -h;
+
+        cur.draw_cursor_at(x, y - 0.2, cursor_w, h);
+
+        // closed folds sit on a stack of collapsed sheets, one per few hidden lines
+        if unsafe { CONFIG.get().unwrap_unchecked() }.folding.sheet_effect {
+            for &(first, last) in &folds {
+                let Some(row) = editor.get_screen_row(first) else {
+                    continue;
+                };
+                let width = Metrics::new(1, txr.advances()).text_width(&fold_summary(editor.get_line(first), (last - first + 1) as usize));
+                let sheets = ((last - first) as f32).log2().ceil().clamp(1., 4.) as u32;
+                for sheet in (1..=sheets).rev() {
+                    let offset = sheet as f32 * 0.08 * h;
+                    let (sx, ex) = (left + offset, left + width * w + offset);
+                    let sy = row_y(row) - 0.2 - offset;
+                    let shade = 0.3 - sheet as f32 * 0.05;
+                    r2d.draw_quad(
+                        &[
+                            glam::Vec2::new(sx, sy),
+                            glam::Vec2::new(ex, sy),
+                            glam::Vec2::new(ex, sy + h),
+                            glam::Vec2::new(sx, sy + h),
+                        ],
+                        glam::Vec3::new(shade, shade, shade),
+                    );
+                }
+            }
+        }
+
+        for (row, first, last) in editor.get_selection() {
+            let (sx, ex) = (first * w, last * w);
+            let sy = row_y(row) - 0.2;
+            r2d.draw_quad(
+                &[
+                    glam::Vec2::new(sx, sy),
+                    glam::Vec2::new(ex, sy),
+                    glam::Vec2::new(ex, sy + h),
+                    glam::Vec2::new(sx, sy + h),
+                ],
+                glam::Vec3::new(0.25, 0.25, 0.4),
+            );
+        }
+
+        if unsafe { CONFIG.get().unwrap_unchecked() }.brackets.highlight_match {
+            if let Some((row, col, width)) = editor.get_matching_bracket() {
+                let (bx, bw) = (col * w, width * w);
+                let by = row_y(row) - 0.2;
+                r2d.draw_quad(
+                    &[
+                        glam::Vec2::new(bx, by),
+                        glam::Vec2::new(bx + bw, by),
+                        glam::Vec2::new(bx + bw, by + h),
+                        glam::Vec2::new(bx, by + h),
+                    ],
+                    glam::Vec3::new(0.35, 0.35, 0.35),
+                );
+            }
+        }
+
+        // indexing progress and messages in the top left corner of the view
+        let visible_h = 2. * cam_z * (3.14f32 / 8.).tan();
+        let sx = camera.pos.x - visible_w / 2. + w;
+        let mut sy = camera.pos.y + visible_h / 2. - 2. * h;
+        if let Some((progress, lines)) = editor.get_load_progress() {
+            let status = format!("indexing {:.0}% ({} lines)", progress * 100., lines);
+            txr.draw_text(sx, sy, &status, None, 1, &[], None, None, None);
 
-        let x = editor.get_cursor().1 as f32 * w;
-        let y = (editor.get_cursor().0 - 1) as f32 * -h;
+            let (bar_w, by) = (20. * w, sy - 0.2 - h * 0.5);
+            for (end, shade) in [(bar_w, 0.2), (bar_w * progress, 0.5)] {
+                r2d.draw_quad(
+                    &[
+                        glam::Vec2::new(sx, by),
+                        glam::Vec2::new(sx + end, by),
+                        glam::Vec2::new(sx + end, by + h * 0.3),
+                        glam::Vec2::new(sx, by + h * 0.3),
+                    ],
+                    glam::Vec3::new(shade, shade, shade),
+                );
+            }
+            sy -= 2. * h;
+        }
+        if let Some(command_line) = editor.get_command_line() {
+            txr.draw_text(sx, sy, &command_line, None, 1, &[], None, None, None);
+            sy -= h;
+        }
+        if let Some(message) = editor.get_message() {
+            txr.draw_text(sx, sy, message, None, editor.get_tabstop(), &[], None, None, None);
+        }
 
-        cur.draw_cursor_at(x, y - 0.2, w, h);
+        editor.draw_plugins(&mut RendererCanvas { r2d: &mut r2d, txr: &mut txr });
 
-        let orig = glam::Vec3::new(x + w / 2., y + h / 4., 0.);
+        // without wrap the camera follows the scrolled view instead of the cursor
+        let focus_x = match editor.get_viewport() {
+            Some(view) => (view.left as f32 + view.width as f32 / 2.) * w,
+            None => x + w / 2.,
+        };
+        let orig = glam::Vec3::new(focus_x, y + h / 4., 0.);
 
         // camera.pos.x -= (camera.pos.x - (orig.x + (win_x  as f32 + ww as f32/2.)/aspect)) * 0.03;
         camera.pos.x -= (camera.pos.x - orig.x) * unsafe { CONFIG.get().unwrap_unchecked() }.camera.follow_strength;
@@ -168,6 +337,12 @@ fn main() {
 
         camera.to -= (camera.to - orig) * unsafe { CONFIG.get().unwrap_unchecked() }.camera.lookat_strength;
         camera.update_view();
+        editor.set_view_state(ViewState {
+            camera_pos: camera.pos.to_array(),
+            camera_to: camera.to.to_array(),
+            zoom: cam_z,
+            window: (ww, wh),
+        });
 
         unsafe {
             gl::ClearColor(0., 0., 0., 0.0);
@@ -176,6 +351,8 @@ fn main() {
             gl::Enable(gl::BLEND);
             gl::BlendEquation(gl::FUNC_ADD);
             gl::BlendFunc(gl::SRC_ALPHA, gl::ZERO);
+            // highlights go under the text
+            r2d.flush(&camera);
             txr.flush(&camera);
             cur.draw(&camera);
         }
@@ -186,5 +363,6 @@ fn main() {
         // println!("Time taken: {}", (end - start).as_micros());
         start = Instant::now();
     }
+    editor.close();
     // free resources
 }
@@ -0,0 +1,138 @@
+use glam::{Vec2, Vec3};
+
+use crate::editor::{
+    indent::display_width,
+    plugin::{Canvas, EditorPlugin},
+    Editor, Mode,
+};
+
+// used when neither the config nor an .editorconfig sets max_line_length
+const DEFAULT_COLUMN: usize = 80;
+const GUIDE_COLOR: Vec3 = Vec3::new(0.3, 0.3, 0.3);
+const MARK_COLOR: Vec3 = Vec3::new(0.6, 0.2, 0.2);
+
+/*
+* sample plugin. draws a guide at max_line_length and marks the lines that go past it, <C-g>
+* jumps to the next of them and :guide [column|off] moves or hides the guide
+*/
+pub struct ColumnGuide {
+    column: Option<usize>,
+    // line numbers of the lines wider than the column, tabs and wide chars as they are shown
+    long_lines: Vec<usize>,
+}
+
+impl ColumnGuide {
+    pub fn new() -> Self {
+        ColumnGuide {
+            column: Some(DEFAULT_COLUMN),
+            long_lines: Vec::new(),
+        }
+    }
+
+    fn scan(&mut self, editor: &Editor) {
+        let buf = editor.buffers.curr_buffer();
+        self.long_lines = match self.column {
+            // large buffers are not read whole
+            Some(column) if !buf.is_large() => buf
+                .lines
+                .iter()
+                .enumerate()
+                .filter(|(_, line)| display_width(&line.content, buf.settings.tabstop) > column)
+                .map(|(idx, _)| idx + 1)
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+}
+
+impl EditorPlugin for ColumnGuide {
+    fn name(&self) -> &str {
+        "guide"
+    }
+
+    fn on_startup(&mut self, editor: &mut Editor) {
+        if let Some(length) = editor.buffers.curr_buffer().settings.max_line_length {
+            self.column = Some(length);
+        }
+        self.scan(editor);
+    }
+
+    fn on_key(&mut self, editor: &mut Editor, key: &str) -> bool {
+        if key != "<C-g>" || editor.get_mode() != Mode::Normal {
+            return false;
+        }
+        let buf = editor.buffers.curr_buffer_mut();
+        let line_pos = buf.line_pos();
+        // wraps around to the first one
+        let next = self.long_lines.iter().find(|&&line| line > line_pos).or(self.long_lines.first());
+        match next {
+            Some(&line) => buf.move_cursor_line_to(line),
+            None => editor.set_message("no lines past the guide"),
+        }
+        true
+    }
+
+    fn on_buffer_changed(&mut self, editor: &mut Editor) {
+        self.scan(editor);
+    }
+
+    fn on_command(&mut self, editor: &mut Editor, name: &str, args: &str) -> bool {
+        if name != "guide" {
+            return false;
+        }
+        match args {
+            "" => (),
+            "off" => self.column = None,
+            _ => match args.parse() {
+                Ok(column) => self.column = Some(column),
+                Err(_) => {
+                    editor.set_message(&format!("not a column: {}", args));
+                    return true;
+                }
+            },
+        }
+        self.scan(editor);
+        match self.column {
+            Some(column) => editor.set_message(&format!("guide at {}, {} lines past it", column, self.long_lines.len())),
+            None => editor.set_message("guide off"),
+        }
+        true
+    }
+
+    fn draw(&mut self, editor: &Editor, canvas: &mut dyn Canvas) {
+        let Some(column) = self.column else {
+            return;
+        };
+        let buf = editor.buffers.curr_buffer();
+        if buf.is_large() {
+            return;
+        }
+        let Vec2 { x: w, y: h } = canvas.cell_size();
+        let rows = buf.display_rows(editor.get_wrap());
+        // rows span from 0.2 below their baseline to a line height above it
+        let row_y = |row: usize| row as f32 * -h - 0.2;
+
+        let left = editor.get_viewport().map_or(0, |view| view.left);
+        if column >= left {
+            let x = (column - left) as f32 * w;
+            canvas.draw_line(Vec2::new(x, row_y(0) + h), Vec2::new(x, row_y(rows.len().saturating_sub(1))), GUIDE_COLOR);
+            canvas.draw_text(x + w, row_y(0) + h, &format!("{} past {}", self.long_lines.len(), column));
+        }
+
+        // a mark left of the first row of every long line
+        let mut prev_line = 0;
+        for (row, display_row) in rows.iter().enumerate() {
+            if display_row.line == prev_line {
+                continue;
+            }
+            prev_line = display_row.line;
+            if self.long_lines.binary_search(&display_row.line).is_ok() {
+                let (sx, ex, sy) = (-0.6 * w, -0.2 * w, row_y(row));
+                canvas.draw_quad(
+                    &[Vec2::new(sx, sy), Vec2::new(ex, sy), Vec2::new(ex, sy + h), Vec2::new(sx, sy + h)],
+                    MARK_COLOR,
+                );
+            }
+        }
+    }
+}
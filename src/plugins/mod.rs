@@ -0,0 +1,11 @@
+use crate::editor::plugin::EditorPlugin;
+
+pub mod guide;
+
+// the plugins general.plugins can name
+pub fn by_name(name: &str) -> Option<Box<dyn EditorPlugin>> {
+    match name {
+        "guide" => Some(Box::new(guide::ColumnGuide::new())),
+        _ => None,
+    }
+}
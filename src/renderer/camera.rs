@@ -30,6 +30,20 @@ impl Camera {
         self.proj = glam::Mat4::perspective_infinite_rh(fov, aspect, 0.)
     }
 
+    /*
+    * the point on the text plane (z = 0) seen at ndc, from -1 to 1 across the window, for a
+    * projection with the given vertical fov and aspect. None when looking away from the plane
+    */
+    pub fn unproject_to_plane(&self, ndc: glam::Vec2, fov: f32, aspect: f32) -> Option<glam::Vec3> {
+        let forward = (self.to - self.pos).normalize();
+        let right = forward.cross(self.up).normalize();
+        let up = right.cross(forward);
+        let half = (fov / 2.).tan();
+        let dir = forward + right * ndc.x * half * aspect + up * ndc.y * half;
+        let t = -self.pos.z / dir.z;
+        (t.is_finite() && t > 0.).then(|| self.pos + dir * t)
+    }
+
     pub fn dx(&mut self, l: f32, r: f32, t: f32, b: f32, zn: f32, zf: f32) {
         self.proj = glam::mat4(
             glam::vec4(2.*zn/(r-l), 0.,          0.,         0.),
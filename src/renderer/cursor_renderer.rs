@@ -115,6 +115,13 @@ impl CursorRenderer {
         gl::DrawElements(gl::TRIANGLES, 30, gl::UNSIGNED_INT, std::ptr::null());
     }
 
+    // moves the cursor and its trail without animating, for text that moved under it
+    pub fn shift(&mut self, by: glam::Vec2) {
+        self.cursor_prev += by;
+        self.cursor_prev_prev += by;
+        self.trail_loc += by;
+    }
+
     pub fn draw_cursor_at(&mut self, x: f32, y: f32, w: f32, h: f32) {
         let cursor_curr = glam::vec2(x + w / 2., y + h / 2.);
         if self.cursor_prev != cursor_curr {
@@ -1,4 +1,8 @@
-use std::{collections::HashMap, mem::swap};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    mem::swap,
+    sync::Arc,
+};
 
 use font_kit::{
     family_name::FamilyName, handle::Handle, properties::Properties, source::SystemSource,
@@ -11,6 +15,23 @@ pub struct FontManager {
     handles: Vec<Handle>,
     ft_lib: ft::Library,
     system_source: SystemSource,
+    // font files the shaper reads with their face index, by index into handles
+    shaping_data: HashMap<usize, Option<FontData>>,
+}
+
+// the bytes of a font file and the index of the face in it
+type FontData = (Arc<Vec<u8>>, u32);
+
+// a glyph of a font, by index into the loaded fonts and glyph id
+pub type GlyphKey = (usize, u32);
+
+// a glyph placed by the shaper, cluster is the byte offset of the text it came from
+#[derive(Clone)]
+pub struct ShapedGlyph {
+    pub key: GlyphKey,
+    pub cluster: usize,
+    pub advance: f32,
+    pub offset: Vec2,
 }
 
 #[allow(dead_code)]
@@ -55,6 +76,7 @@ impl FontManager {
             handles: Vec::new(),
             ft_lib: ft::Library::init().expect("Freetype lib init failed"),
             system_source: SystemSource::new(),
+            shaping_data: HashMap::new(),
         }
     }
 
@@ -62,7 +84,8 @@ impl FontManager {
         &mut self,
         name: &str,
         curve_data: &mut Vec<Curve>,
-        glyph_map: &mut HashMap<u32, GlyphData>,
+        glyph_map: &mut HashMap<GlyphKey, GlyphData>,
+        char_glyphs: &mut HashMap<char, GlyphKey>,
         dilation: &mut f32,
         advance: &mut f32,
         height: &mut f32,
@@ -88,10 +111,10 @@ impl FontManager {
         let face;
         match handle {
             font_kit::handle::Handle::Path { ref path, .. } => {
-                face = self.ft_lib.new_face(path, 0).unwrap();
+                face = self.ft_lib.new_face(path, face_index(&handle) as isize).unwrap();
             }
             font_kit::handle::Handle::Memory { ref bytes, .. } => {
-                face = self.ft_lib.new_memory_face((**bytes).clone(), 0).unwrap();
+                face = self.ft_lib.new_memory_face((**bytes).clone(), face_index(&handle) as isize).unwrap();
                 // panic!("we should not be loading memory");
             }
         }
@@ -106,7 +129,7 @@ impl FontManager {
 
         match face.load_glyph(0, load_flags) {
             Ok(_) => {
-                Self::build_glyph(&face, curve_data, glyph_map, 0);
+                Self::build_glyph(&face, curve_data, glyph_map, (0, 0));
             }
             Err(e) => {
                 println!("Unable to load the undefined glyph. {}", e);
@@ -117,9 +140,17 @@ impl FontManager {
             let Some(glyph_index) = face.get_char_index(char_code) else {
                 continue;
             };
+            let Some(c) = char::from_u32(char_code as u32) else {
+                continue;
+            };
+            char_glyphs.insert(c, (0, glyph_index));
+            // chars can share a glyph
+            if glyph_map.contains_key(&(0, glyph_index)) {
+                continue;
+            }
             match face.load_glyph(glyph_index, load_flags) {
                 Ok(_) => {
-                    Self::build_glyph(&face, curve_data, glyph_map, char_code as u32);
+                    Self::build_glyph(&face, curve_data, glyph_map, (0, glyph_index));
                 }
                 Err(e) => {
                     println!(
@@ -131,7 +162,11 @@ impl FontManager {
             }
         }
 
-        *advance = glyph_map.get(&(' ' as u32)).unwrap_or(glyph_map.get(&0).unwrap()).advance;
+        *advance = char_glyphs
+            .get(&' ')
+            .and_then(|key| glyph_map.get(key))
+            .unwrap_or(glyph_map.get(&(0, 0)).unwrap())
+            .advance;
         self.handles.push(handle);
     }
 
@@ -139,31 +174,29 @@ impl FontManager {
         self.handles.extend(self.system_source.all_fonts().expect("Failed to load fonts"));
     }
 
+    // finds the font for each char not seen yet, the first one that has it
     pub fn load_glyphs_in_str(
         &self,
         curve_data: &mut Vec<Curve>,
-        glyph_map: &mut HashMap<u32, GlyphData>,
+        glyph_map: &mut HashMap<GlyphKey, GlyphData>,
+        char_glyphs: &mut HashMap<char, GlyphKey>,
         s: &str,
     ) -> bool {
         let mut modified: bool = false;
 
-        let load_flags = ft::face::LoadFlag::NO_SCALE
-            | ft::face::LoadFlag::NO_HINTING
-            | ft::face::LoadFlag::NO_BITMAP;
-
         for c in s.chars() {
-            if glyph_map.get(&(c as u32)).is_some() {
+            if char_glyphs.get(&c).is_some() {
                 continue;
             }
             if c == '\r' || c == '\n' {
                 continue;
             }
 
-            for handle in &self.handles {
+            for (font, handle) in self.handles.iter().enumerate() {
                 let face;
                 match handle {
                     font_kit::handle::Handle::Path { path, .. } => {
-                        face = self.ft_lib.new_face(path, 0).unwrap();
+                        face = self.ft_lib.new_face(path, face_index(handle) as isize).unwrap();
                     }
                     font_kit::handle::Handle::Memory { .. } => {
                         // face = self.ft_lib.new_memory_face((**bytes), 0).unwrap();
@@ -172,40 +205,133 @@ impl FontManager {
                 }
 
                 if let Some(glyph_index) = face.get_char_index(c as usize) {
-                    match face.load_glyph(glyph_index, load_flags) {
-                        Ok(_) => {
-                            Self::build_glyph(
-                                &face,
-                                curve_data,
-                                glyph_map,
-                                c as u32,
-                                // glyph_index,
-                            );
-                            modified = true;
+                    char_glyphs.insert(c, (font, glyph_index));
+                    modified |= Self::load_glyph(&face, curve_data, glyph_map, (font, glyph_index));
+                    break;
+                }
+            }
+        }
+
+        // make sure to reload shader buffers
+        modified
+    }
+
+    // loads glyphs the shaper picked that no char maps to, like ligatures and alternates
+    pub fn load_glyphs(
+        &self,
+        curve_data: &mut Vec<Curve>,
+        glyph_map: &mut HashMap<GlyphKey, GlyphData>,
+        glyphs: &[ShapedGlyph],
+    ) -> bool {
+        let mut modified: bool = false;
+        let mut faces: HashMap<usize, Face> = HashMap::new();
+
+        for glyph in glyphs {
+            if glyph_map.contains_key(&glyph.key) {
+                continue;
+            }
+            let font = glyph.key.0;
+            let face = match faces.entry(font) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let handle = &self.handles[font];
+                    let face = match handle {
+                        font_kit::handle::Handle::Path { path, .. } => self.ft_lib.new_face(path, face_index(handle) as isize),
+                        font_kit::handle::Handle::Memory { bytes, .. } => {
+                            self.ft_lib.new_memory_face((**bytes).clone(), face_index(handle) as isize)
                         }
+                    };
+                    match face {
+                        Ok(face) => entry.insert(face),
                         Err(e) => {
-                            println!(
-                                "Unable to load the glyph for {}. {}",
-                                std::char::from_u32(c as u32).unwrap(),
-                                e
-                            );
+                            println!("Unable to load font {}. {}", font, e);
+                            continue;
                         }
                     }
-                    break;
                 }
-            }
+            };
+            modified |= Self::load_glyph(face, curve_data, glyph_map, glyph.key);
         }
 
-        // make sure to reload shader buffers
         modified
     }
 
+    fn load_glyph(
+        face: &Face,
+        curve_data: &mut Vec<Curve>,
+        glyph_map: &mut HashMap<GlyphKey, GlyphData>,
+        key: GlyphKey,
+    ) -> bool {
+        if glyph_map.contains_key(&key) {
+            return false;
+        }
+        let load_flags = ft::face::LoadFlag::NO_SCALE
+            | ft::face::LoadFlag::NO_HINTING
+            | ft::face::LoadFlag::NO_BITMAP;
+        match face.load_glyph(key.1, load_flags) {
+            Ok(_) => {
+                Self::build_glyph(face, curve_data, glyph_map, key);
+                true
+            }
+            Err(e) => {
+                println!("Unable to load glyph {} of font {}. {}", key.1, key.0, e);
+                false
+            }
+        }
+    }
+
+    /*
+    * shapes text with one font into glyphs with their advances and offsets, in ems. the glyphs
+    * come left to right, a right to left run starts with its last cluster. none when the font can
+    * not be shaped with
+    */
+    pub fn shape(&mut self, font: usize, text: &str, rtl: bool) -> Option<Vec<ShapedGlyph>> {
+        let handles = &self.handles;
+        // the file is read once, the face is only parsed when a run is not cached by the renderer
+        let (data, index) = self
+            .shaping_data
+            .entry(font)
+            .or_insert_with(|| {
+                let handle = handles.get(font)?;
+                let data = match handle {
+                    font_kit::handle::Handle::Path { path, .. } => Arc::new(std::fs::read(path).ok()?),
+                    font_kit::handle::Handle::Memory { bytes, .. } => bytes.clone(),
+                };
+                Some((data, face_index(handle)))
+            })
+            .as_ref()?;
+        let face = rustybuzz::Face::from_slice(data, *index)?;
+
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.guess_segment_properties();
+        buffer.set_direction(match rtl {
+            true => rustybuzz::Direction::RightToLeft,
+            false => rustybuzz::Direction::LeftToRight,
+        });
+        let shaped = rustybuzz::shape(&face, &[], buffer);
+
+        let em_size = face.units_per_em() as f32;
+        Some(
+            shaped
+                .glyph_infos()
+                .iter()
+                .zip(shaped.glyph_positions())
+                .map(|(info, pos)| ShapedGlyph {
+                    key: (font, info.glyph_id),
+                    cluster: info.cluster as usize,
+                    advance: pos.x_advance as f32 / em_size,
+                    offset: Vec2::new(pos.x_offset as f32, pos.y_offset as f32) / em_size,
+                })
+                .collect(),
+        )
+    }
+
     fn build_glyph(
         face: &Face,
         curve_data: &mut Vec<Curve>,
-        glyph_map: &mut HashMap<u32, GlyphData>,
-        char_code: u32,
-        // glyph_index: u32,
+        glyph_map: &mut HashMap<GlyphKey, GlyphData>,
+        key: GlyphKey,
     ) {
         let start = curve_data.len();
         let em_size = face.em_size() as f32;
@@ -224,7 +350,7 @@ impl FontManager {
             bearing_y: face.glyph().metrics().horiBearingY as f32 / em_size,
             advance: face.glyph().metrics().horiAdvance as f32 / em_size,
         };
-        glyph_map.insert(char_code, g);
+        glyph_map.insert(key, g);
     }
 
     fn process_contour(curve_data: &mut Vec<Curve>, outline: Outline, em_size: f32) {
@@ -282,6 +408,13 @@ impl FontManager {
     }
 }
 
+// the face of a font file a handle is, collections hold several
+fn face_index(handle: &Handle) -> u32 {
+    match handle {
+        Handle::Path { font_index, .. } | Handle::Memory { font_index, .. } => *font_index,
+    }
+}
+
 fn midpoint(a: &Vec2, b: &Vec2) -> Vec2 {
     (*a + *b) * 0.5
 }
@@ -7,5 +7,6 @@ mod font_manager;
 
 pub mod camera;
 pub mod cursor_renderer;
+pub mod plugin_canvas;
 pub mod text_renderer;
 pub mod primitive_renderer;
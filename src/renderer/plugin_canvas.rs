@@ -0,0 +1,29 @@
+use glam::{Vec2, Vec3};
+
+use crate::editor::plugin::Canvas;
+
+use super::{primitive_renderer::PrimitiveRenderer, text_renderer::TextRenderer};
+
+// the renderers as the canvas plugins draw on
+pub struct RendererCanvas<'a> {
+    pub r2d: &'a mut PrimitiveRenderer,
+    pub txr: &'a mut TextRenderer,
+}
+
+impl Canvas for RendererCanvas<'_> {
+    fn cell_size(&self) -> Vec2 {
+        Vec2::new(self.txr.advance, self.txr.height)
+    }
+
+    fn draw_line(&mut self, from: Vec2, to: Vec2, color: Vec3) {
+        self.r2d.draw_line(from, to, color);
+    }
+
+    fn draw_quad(&mut self, corners: &[Vec2; 4], color: Vec3) {
+        self.r2d.draw_quad(corners, color);
+    }
+
+    fn draw_text(&mut self, x: f32, y: f32, text: &str) {
+        self.txr.draw_text(x, y, text, None, 1, &[], None, None, None);
+    }
+}
@@ -1,6 +1,7 @@
-use std::{collections::HashMap, ffi::CString, os::raw::c_void, time::Instant};
+use std::{collections::HashMap, ffi::CString, mem, ops::Range, os::raw::c_void, sync::Arc, time::Instant};
 
 use crate::{
+    editor::layout::{self, Metrics, Viewport, Wrap},
     renderer::{
         index_buffer::IndexBuffer,
         shader::Shader,
@@ -10,7 +11,7 @@ use crate::{
 
 use super::{
     camera::Camera,
-    font_manager::{Curve, FontManager, GlyphData},
+    font_manager::{Curve, FontManager, GlyphData, GlyphKey, ShapedGlyph},
     shader::Program,
     vertex_array::VertexArray,
 };
@@ -35,25 +36,40 @@ pub struct TextRenderer {
     curve_ssbo_capacity: usize,
 
     curve_data: Vec<Curve>,
-    glyph_map: HashMap<u32, GlyphData>,
+    glyph_map: HashMap<GlyphKey, GlyphData>,
+    // the glyph each char gets on its own, in the first font that has it
+    char_glyphs: HashMap<char, GlyphKey>,
 
     dilation: f32,
     pub advance: f32,
     pub height: f32,
+    // glyphs take their own advance instead of a cell, the editor measures text with them too
+    proportional: bool,
+    advances: Option<layout::Advances>,
+    // what the shaper added to the advance of a char before another, in columns
+    kerning: HashMap<(char, char), f32>,
+
+    // runs shaped this frame and the last one
+    shaped: HashMap<RunKey, Vec<ShapedGlyph>>,
+    shaped_last_frame: HashMap<RunKey, Vec<ShapedGlyph>>,
 }
 
 const MAX_QUADS: usize = 100000;
 
+// a shaped run by font, direction and text
+type RunKey = (usize, bool, String);
+
 impl TextRenderer {
-    pub fn new(main_font_name: &str) -> Self {
+    pub fn new(main_font_name: &str, proportional: bool) -> Self {
         let mut curve_data: Vec<Curve> = Vec::new();
-        let mut glyph_map: HashMap<u32, GlyphData> = HashMap::new();
+        let mut glyph_map: HashMap<GlyphKey, GlyphData> = HashMap::new();
+        let mut char_glyphs: HashMap<char, GlyphKey> = HashMap::new();
         let mut dilation = 0.;
         let mut advance = 0.;
         let mut height = 0.;
 
         let mut font_manager = FontManager::new();
-        font_manager.load_main_font(main_font_name, &mut curve_data, &mut glyph_map, &mut dilation, &mut advance, &mut height);
+        font_manager.load_main_font(main_font_name, &mut curve_data, &mut glyph_map, &mut char_glyphs, &mut dilation, &mut advance, &mut height);
         font_manager.prepare_fallback_fonts();
 
         let vert_shader = Shader::from_source(
@@ -114,7 +130,7 @@ impl TextRenderer {
             );
         }
 
-        Self {
+        let mut txr = Self {
             font_manager,
             text_vao: font_vao,
             text_shader: font_shader,
@@ -126,15 +142,26 @@ impl TextRenderer {
 
             curve_data,
             glyph_map,
+            char_glyphs,
 
             dilation,
             advance,
             height,
-        }
+            proportional,
+            advances: None,
+            kerning: HashMap::new(),
+
+            shaped: HashMap::new(),
+            shaped_last_frame: HashMap::new(),
+        };
+        txr.update_advances();
+        txr
     }
 
     pub fn begin_scene(&mut self) {
         self.quad_data.clear();
+        // runs not drawn last frame are dropped, like the old text of an edited line
+        self.shaped_last_frame = mem::take(&mut self.shaped);
     }
 
     pub fn flush(&mut self, cam: &Camera) {
@@ -205,12 +232,33 @@ impl TextRenderer {
     pub fn prep_text(&mut self, text: &str) {
         if self
             .font_manager
-            .load_glyphs_in_str(&mut self.curve_data, &mut self.glyph_map, text)
+            .load_glyphs_in_str(&mut self.curve_data, &mut self.glyph_map, &mut self.char_glyphs, text)
         {
             self.reload_curve_buffer();
+            self.update_advances();
         }
     }
 
+    // advances of the loaded glyphs in columns, what text is laid out with. none for a monospace font
+    pub fn advances(&self) -> Option<layout::Advances> {
+        self.advances.clone()
+    }
+
+    fn update_advances(&mut self) {
+        if !self.proportional {
+            return;
+        }
+        let chars = self
+            .char_glyphs
+            .iter()
+            .filter_map(|(&c, key)| Some((c, self.glyph_map.get(key)?.advance / self.advance)))
+            .collect();
+        self.advances = Some(Arc::new(layout::FontAdvances {
+            chars,
+            kerning: self.kerning.clone(),
+        }));
+    }
+
     fn reload_curve_buffer(&mut self) {
         if self.curve_data.len() > self.curve_ssbo_capacity {
             self.curve_ssbo_capacity = self.curve_data.len().next_power_of_two();
@@ -244,107 +292,317 @@ impl TextRenderer {
         }
     }
 
-    // additionally returns cursor position and size if given the line pos and char pos of the cursor
+    /*
+    * draws text laid out with the editor's display line model, so wrapped rows and closed folds
+    * land where the editor expects them. without wrap only the columns in view are drawn, rows
+    * with a y outside visible are only counted and never shaped.
+    * additionally returns cursor position and size if given the line pos and char pos of the cursor
+    */
     pub fn draw_text(
         &mut self,
-        mut x: f32,
-        mut y: f32,
+        x: f32,
+        y: f32,
         text: &str,
-        wrap: f32,
+        wrap: Option<&Wrap>,
+        tabstop: usize,
+        folds: &[(u32, u32)],
+        view: Option<&Viewport>,
+        visible: Option<Range<f32>>,
         cursor_pos: Option<(u32, u32)>,
     ) -> (f32, f32, f32, f32) {
         self.prep_text(text);
+        if let Some(wrap) = wrap {
+            self.prep_text(&wrap.showbreak);
+        }
+        if !folds.is_empty() {
+            self.prep_text(&fold_summary("", 0));
+        }
 
-        let original_x = x;
-        let mut found_char = false;
-
-        // cursor width is space width or null char width if space not found
-        let mut cursor_w = self
-            .glyph_map
-            .get(&(' ' as u32))
-            .unwrap_or(self.glyph_map.get(&0_u32).unwrap())
-            .advance;
-
-        let mut cursor_x = x;
-        let mut cursor_y = y;
-        let mut last_y = y;
-
-        let mut line = 1;
-        let mut char = 0;
-        let cursor_line = cursor_pos.unwrap_or((u32::MAX, u32::MAX)).0;
-        let cursor_char = cursor_pos.unwrap_or((u32::MAX, u32::MAX)).1;
-
-        // let mut prev_glyph_index: u32 = 0;
-        for c in text.chars() {
-            // we check for unknown characters behorehand
-            let glyph = self
-                .glyph_map
-                .get(&(c as u32))
-                .unwrap_or(self.glyph_map.get(&0_u32).unwrap());
-
-            // FOUND yay
-            if !found_char && line == cursor_line && char == cursor_char {
-                found_char = true;
-                cursor_x = x;
-                cursor_y = y;
-                cursor_w = glyph.advance;
+        // measured after the glyphs are loaded, they are what a proportional font is laid out with
+        let metrics = Metrics::new(tabstop, self.advances());
+        let (cursor_line, cursor_char) = cursor_pos.unwrap_or((u32::MAX, u32::MAX));
+        let (mut cursor_x, mut cursor_y) = (x, y);
+        // wide chars get a cursor as wide as they are
+        let mut cursor_cols = 1.;
+        let mut row_y = y;
+        let (left, right) = view.map_or((0., f32::INFINITY), |view| (view.left as f32, view.right() as f32));
+        let visible = visible.unwrap_or(f32::NEG_INFINITY..f32::INFINITY);
+
+        // last line of the closed fold being skipped
+        let mut fold_end = 0;
+
+        for (idx, content) in text.split('\n').enumerate() {
+            let line = idx as u32 + 1;
+            if line <= fold_end {
+                continue;
             }
-
-            if !found_char && line == cursor_line + 1 {
-                found_char = true; // pseudo true
-                cursor_x = original_x;
-                cursor_y = last_y;
-                cursor_w = glyph.advance;
+            let content = content.strip_suffix('\r').unwrap_or(content);
+
+            // a closed fold is drawn as a summary of its first line
+            if let Some(&(_, last)) = folds.iter().find(|(first, _)| *first == line) {
+                fold_end = last;
+                // the summary stays at the left edge of the view
+                let start_x = x + left * self.advance;
+                if visible.contains(&row_y) {
+                    let summary = fold_summary(content, (last - line + 1) as usize);
+                    self.prep_text(&summary);
+                    let placed = layout::placed(&summary, &metrics, &layout::whole_line(&summary));
+                    self.push_clusters(&placed, start_x, row_y);
+                }
+                if (line..=last).contains(&cursor_line) {
+                    (cursor_x, cursor_y) = (start_x, row_y);
+                }
+                row_y -= self.height;
+                continue;
             }
 
-            if c == '\r' {
-                continue;
+            let segments = layout::segments(content, &metrics, wrap);
+            // the row of the cursor is laid out once for the cursor and for drawing it
+            let mut cursor_row = None;
+            if line == cursor_line {
+                let i = layout::segment_of(&segments, cursor_char as usize);
+                let row = layout::placed_between(content, &metrics, &segments[i], left, right);
+                let col = layout::x_in_placed(&row, &segments[i], cursor_char as usize);
+                (cursor_x, cursor_y) = (x + col * self.advance, row_y - i as f32 * self.height);
+                let under = row.iter().find(|placed| placed.start == cursor_char as usize);
+                cursor_cols = under.map_or(1., |placed| placed.width);
+                cursor_row = Some((i, row));
             }
 
-            if c == '\n' {
-                x = original_x;
-                last_y = y;
-                y -= self.height;
-                char = 0;
-                line += 1;
-                continue;
+            for (i, segment) in segments.iter().enumerate() {
+                if !visible.contains(&row_y) {
+                    row_y -= self.height;
+                    continue;
+                }
+                if let Some(wrap) = wrap.filter(|_| i > 0) {
+                    let showbreak_col = segment.indent - wrap.showbreak.chars().count().min(segment.indent);
+                    let placed: Vec<_> = layout::clusters(&wrap.showbreak)
+                        .map(|(start, cluster)| layout::Placed {
+                            start,
+                            cluster,
+                            x: (showbreak_col + start) as f32,
+                            width: 1.,
+                            rtl: false,
+                        })
+                        .collect();
+                    self.push_clusters(&placed, x, row_y);
+                }
+
+                // the clusters in view, the row is shaped as a whole
+                let row = match cursor_row.take() {
+                    Some((cursor_segment, row)) if cursor_segment == i => row,
+                    other => {
+                        cursor_row = other;
+                        layout::placed_between(content, &metrics, segment, left, right)
+                    }
+                };
+                let mut in_view = Vec::new();
+                for (j, &placed) in row.iter().enumerate() {
+                    let (col, width) = (placed.x, placed.width);
+                    // only the columns in view are drawn
+                    if col < left {
+                        continue;
+                    }
+                    if col + width > right {
+                        break;
+                    }
+
+                    // markers replace the chars in the first and last column when the line continues off screen,
+                    // there is always a char left of the view once it is scrolled
+                    let marker = match view {
+                        Some(view) if col < left + 1. && left > 0. => view.precedes,
+                        Some(view) if col + width > right - 1. && j + 1 < row.len() => view.extends,
+                        _ => None,
+                    };
+                    match marker {
+                        Some(marker) => self.push_char(marker, x + col * self.advance, row_y),
+                        None => in_view.push(placed),
+                    }
+                }
+                self.push_clusters(&in_view, x, row_y);
+                row_y -= self.height;
             }
+        }
 
-            // kerning not going to be supported
+        (cursor_x, cursor_y, cursor_cols * self.advance, self.height)
+    }
 
-            char += 1;
+    // the font a cluster is shaped with, the first one with a glyph for its first char
+    fn font_of(&self, cluster: &str) -> usize {
+        layout::drawn_chars(cluster)
+            .next()
+            .and_then(|c| self.char_glyphs.get(&c))
+            .map_or(0, |key| key.0)
+    }
 
-            if x > wrap && glyph.count == 0 {
-                x = original_x;
-                last_y = y;
-                y -= self.height;
+    /*
+    * draws clusters at their columns from x on. runs in one font and direction are shaped
+    * together, tabs only advance to the next tab stop and break the run
+    */
+    fn push_clusters(&mut self, placed: &[layout::Placed], x: f32, y: f32) {
+        let mut i = 0;
+        while i < placed.len() {
+            if placed[i].cluster == "\t" {
+                i += 1;
                 continue;
             }
+            let (font, rtl) = (self.font_of(placed[i].cluster), placed[i].rtl);
+            let len = placed[i..]
+                .iter()
+                .take_while(|p| p.cluster != "\t" && p.rtl == rtl && self.font_of(p.cluster) == font)
+                .count();
+            self.push_run(font, rtl, &placed[i..i + len], x, y);
+            i += len;
+        }
+    }
+
+    /*
+    * shapes a run and draws its glyphs. each shaped cluster starts at the column of the leftmost
+    * cluster it covers and is centered in their columns when narrower, so a ligature spans the
+    * clusters it joins and the cursor still stops on each of them. with a proportional font the
+    * kerning between clusters is handed to the editor, which lays them out with it from then on
+    */
+    fn push_run(&mut self, font: usize, rtl: bool, placed: &[layout::Placed], x: f32, y: f32) {
+        // the shaper reads right to left runs in the order they are stored
+        let logical: Vec<&layout::Placed> = match rtl {
+            true => placed.iter().rev().collect(),
+            false => placed.iter().collect(),
+        };
+        // the text the shaper sees and where each cluster starts in it
+        let mut text = String::new();
+        let mut starts = Vec::with_capacity(logical.len());
+        for p in &logical {
+            starts.push(text.len());
+            text.extend(layout::drawn_chars(p.cluster));
+        }
 
-            if glyph.count != 0 {
-                self.quad_data.push(QuadData {
-                    pos: glam::Vec2 { x, y },
-                    uv0: glam::Vec2 {
-                        x: glyph.bearing_x - self.dilation,
-                        y: (glyph.bearing_y - glyph.height - self.dilation),
-                    },
-                    uv1: glam::Vec2 {
-                        x: (glyph.bearing_x + glyph.width + self.dilation),
-                        y: glyph.bearing_y + self.dilation,
-                    },
-                    start: glyph.start as u32,
-                    count: glyph.count as u32,
-                });
+        let end_of_text = text.len();
+        let glyphs = self.shape(font, rtl, text);
+        if self.font_manager.load_glyphs(&mut self.curve_data, &mut self.glyph_map, &glyphs) {
+            self.reload_curve_buffer();
+        }
+        let mut clusters: Vec<usize> = glyphs.iter().map(|glyph| glyph.cluster).collect();
+        clusters.sort_unstable();
+        clusters.dedup();
+
+        let mut kerned = false;
+        let mut first = 0;
+        while first < glyphs.len() {
+            let cluster = glyphs[first].cluster;
+            let count = glyphs[first..].iter().take_while(|glyph| glyph.cluster == cluster).count();
+            let group = &glyphs[first..first + count];
+            first += count;
+
+            let next = clusters.get(clusters.partition_point(|c| *c <= cluster)).copied().unwrap_or(end_of_text);
+            // the clusters from the one the glyphs start in up to where the next glyphs start
+            let covered = starts.partition_point(|start| *start <= cluster).max(1) - 1;
+            let end = starts.partition_point(|start| *start < next).max(covered + 1);
+            let col = logical[covered..end].iter().map(|p| p.x).fold(f32::INFINITY, f32::min);
+            let cols: f32 = logical[covered..end].iter().map(|p| p.width).sum();
+            let total: f32 = group.iter().map(|glyph| glyph.advance).sum();
+            let mut pen = x + col * self.advance + ((cols * self.advance - total) / 2.).max(0.);
+            for glyph in group {
+                self.push_glyph(glyph.key, pen + glyph.offset.x, y + glyph.offset.y);
+                pen += glyph.advance;
             }
-            // x += glyph.advance;
-            x += self.advance;
+            if self.proportional && !rtl && count == 1 && end == covered + 1 {
+                kerned |= self.note_kerning(&group[0], logical[covered].cluster, logical.get(end).map(|p| p.cluster));
+            }
+        }
+        if kerned {
+            self.update_advances();
+        }
+    }
+
+    // remembers how far the shaper moved the char after a lone glyph's, true if it is news
+    fn note_kerning(&mut self, glyph: &ShapedGlyph, cluster: &str, next: Option<&str>) -> bool {
+        let mut chars = layout::drawn_chars(cluster);
+        let (Some(c), None, Some(d)) = (chars.next(), chars.next(), next.and_then(|next| next.chars().next())) else {
+            return false;
+        };
+        // the editor lays c out with the advance of its own glyph, which the shaper may have swapped
+        let Some(plain) = self.char_glyphs.get(&c).and_then(|key| self.glyph_map.get(key)).map(|data| data.advance) else {
+            return false;
+        };
+        let kerning = (glyph.advance - plain) / self.advance;
+        if kerning.abs() < 1e-3 {
+            return false;
+        }
+        self.kerning.insert((c, d), kerning) != Some(kerning)
+    }
+
+    // shapes a run once while it stays on screen
+    fn shape(&mut self, font: usize, rtl: bool, text: String) -> Vec<ShapedGlyph> {
+        let key = (font, rtl, text);
+        if let Some(glyphs) = self.shaped.get(&key) {
+            return glyphs.clone();
         }
-        if !found_char {
-            cursor_x = x;
-            cursor_y = y;
+        let glyphs = match self.shaped_last_frame.remove(&key) {
+            Some(glyphs) => glyphs,
+            None => match self.font_manager.shape(font, &key.2, rtl) {
+                Some(glyphs) => glyphs,
+                None => self.unshaped(&key.2, rtl),
+            },
+        };
+        self.shaped.insert(key, glyphs.clone());
+        glyphs
+    }
+
+    // the glyph of each char one after another, for fonts the shaper can not read
+    fn unshaped(&self, text: &str, rtl: bool) -> Vec<ShapedGlyph> {
+        let mut glyphs: Vec<ShapedGlyph> = text
+            .char_indices()
+            .map(|(cluster, c)| {
+                let key = self.char_glyphs.get(&c).copied().unwrap_or((0, 0));
+                ShapedGlyph {
+                    key,
+                    cluster,
+                    advance: self.glyph_map.get(&key).map_or(self.advance, |glyph| glyph.advance),
+                    offset: glam::Vec2::ZERO,
+                }
+            })
+            .collect();
+        if rtl {
+            glyphs.reverse();
         }
-        // (cursor_x, cursor_y, cursor_w, self.height)
-        (cursor_x, cursor_y, self.advance, self.height)
+        glyphs
+    }
+
+    fn push_char(&mut self, c: char, x: f32, y: f32) {
+        let key = self.char_glyphs.get(&c).copied().unwrap_or((0, 0));
+        self.push_glyph(key, x, y);
     }
 
+    fn push_glyph(&mut self, key: GlyphKey, x: f32, y: f32) {
+        // the quad buffer is allocated once, drop what doesn't fit
+        if self.quad_data.len() >= MAX_QUADS {
+            return;
+        }
+        let glyph = self
+            .glyph_map
+            .get(&key)
+            .unwrap_or(self.glyph_map.get(&(0, 0)).unwrap());
+        if glyph.count == 0 {
+            return;
+        }
+        self.quad_data.push(QuadData {
+            pos: glam::Vec2 { x, y },
+            uv0: glam::Vec2 {
+                x: glyph.bearing_x - self.dilation,
+                y: (glyph.bearing_y - glyph.height - self.dilation),
+            },
+            uv1: glam::Vec2 {
+                x: (glyph.bearing_x + glyph.width + self.dilation),
+                y: glyph.bearing_y + self.dilation,
+            },
+            start: glyph.start as u32,
+            count: glyph.count as u32,
+        });
+    }
+
+}
+
+// the line shown in place of a closed fold, like vim's `+--  5 lines: fn main() {`
+pub fn fold_summary(first_line: &str, line_count: usize) -> String {
+    format!("+--{:>3} lines: {}", line_count, first_line.trim().replace('\t', " "))
 }
@@ -0,0 +1,82 @@
+use sdl2::{
+    event::Event,
+    keyboard::{Keycode, Mod},
+};
+
+use fim::editor::input::{InputEvent, Key, KeyEvent, Modifiers};
+
+// the input an sdl event stands for, None for events the editor does not take
+pub fn translate(event: &Event) -> Option<InputEvent> {
+    match event {
+        Event::TextInput { text, .. } => Some(InputEvent::Text(text.clone())),
+        Event::KeyDown {
+            keycode: Some(keycode),
+            keymod,
+            ..
+        } => key_event(*keycode, *keymod).map(InputEvent::Key),
+        _ => None,
+    }
+}
+
+// None for keys the editor has no use for, such as a lone shift
+pub fn key_event(keycode: Keycode, keymod: Mod) -> Option<KeyEvent> {
+    let key = match keycode {
+        Keycode::Escape => Key::Escape,
+        Keycode::Return | Keycode::KpEnter => Key::Return,
+        Keycode::Backspace => Key::Backspace,
+        Keycode::Tab => Key::Tab,
+        Keycode::Delete => Key::Delete,
+        Keycode::Up => Key::Up,
+        Keycode::Down => Key::Down,
+        Keycode::Left => Key::Left,
+        Keycode::Right => Key::Right,
+        Keycode::Home => Key::Home,
+        Keycode::End => Key::End,
+        _ => Key::Char(key_char(keycode, keymod)?),
+    };
+    Some(KeyEvent {
+        key,
+        mods: Modifiers {
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        },
+    })
+}
+
+// char typed by a key on a us layout
+fn key_char(keycode: Keycode, keymod: Mod) -> Option<char> {
+    let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+    // sdl keycodes of letters are their lowercase ascii
+    if let Some(c) = char::from_u32(keycode as i32 as u32).filter(|c| c.is_ascii_lowercase()) {
+        let upper = shift != keymod.intersects(Mod::CAPSMOD);
+        return Some(if upper { c.to_ascii_uppercase() } else { c });
+    }
+
+    let (plain, shifted) = match keycode {
+        Keycode::Num0 => ('0', ')'),
+        Keycode::Num1 => ('1', '!'),
+        Keycode::Num2 => ('2', '@'),
+        Keycode::Num3 => ('3', '#'),
+        Keycode::Num4 => ('4', '$'),
+        Keycode::Num5 => ('5', '%'),
+        Keycode::Num6 => ('6', '^'),
+        Keycode::Num7 => ('7', '&'),
+        Keycode::Num8 => ('8', '*'),
+        Keycode::Num9 => ('9', '('),
+        Keycode::Minus => ('-', '_'),
+        Keycode::Equals => ('=', '+'),
+        Keycode::LeftBracket => ('[', '{'),
+        Keycode::RightBracket => (']', '}'),
+        Keycode::Backslash => ('\\', '|'),
+        Keycode::Semicolon => (';', ':'),
+        Keycode::Quote => ('\'', '"'),
+        Keycode::Backquote => ('`', '~'),
+        Keycode::Comma => (',', '<'),
+        Keycode::Period => ('.', '>'),
+        Keycode::Slash => ('/', '?'),
+        Keycode::Space => (' ', ' '),
+        _ => return None,
+    };
+    Some(if shift { shifted } else { plain })
+}